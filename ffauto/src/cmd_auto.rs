@@ -1,14 +1,22 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
 
+use crate::cmd_auto_copy::ffmpeg_auto_copy;
 use crate::commands::AutoArgs;
 use crate::common::*;
-use crate::vec_push_ext::PushStrExt;
 use anyhow::{Context, Result};
+use ffmpeg::ffmpeg::capabilities::capabilities;
+use ffmpeg::ffmpeg::command::{FfmpegCommand, Input, Output};
 use ffmpeg::ffmpeg::enums::{OptimizeTarget, VideoCodec};
+use ffmpeg::ffmpeg::error::path_to_str;
 use ffmpeg::ffmpeg::ffmpeg::ffmpeg;
-use ffmpeg::ffmpeg::ffprobe_struct::{Stream, StreamType, Tags};
+use ffmpeg::ffmpeg::ffprobe_struct::{FFProbeOutput, Stream, StreamType, Tags};
+use ffmpeg::ffmpeg::filtergraph::{Chain, Filter, Graph, Label};
+use ffmpeg::ffmpeg::progress::ProgressTotal;
+use ffmpeg::ffmpeg::subtitles::{
+	SubtitleKind, font_attachment_path, font_attachments, text_subtitle_filter, text_subtitle_filters,
+};
 use isolang::Language;
 
 fn fix_language_code(s: &str) -> &str {
@@ -18,67 +26,108 @@ fn fix_language_code(s: &str) -> &str {
 		.map_or(s, |l| l.to_639_2b())
 }
 
-#[derive(PartialEq)]
-enum StreamIndex {
-	Index(usize),
-	Language(Language),
+/// Where the subtitles for `--burn-subtitle` come from.
+enum SubtitleSource {
+	/// A subtitle stream of the input, by its index among the input's subtitle streams.
+	Embedded(usize),
+	/// An external subtitle file.
+	External(PathBuf),
 }
 
-pub(crate) fn ffmpeg_auto(args: &AutoArgs, debug: bool) -> Result<()> {
-	let probe = ffprobe_output(&args.input)?;
-
-	let first_video_stream = probe.get_first_video_stream();
-	let first_audio_stream = probe.get_first_audio_stream();
-
-	if !probe.has_video_streams() && !probe.has_audio_streams() {
-		anyhow::bail!("The input file contains no usable audio/video streams")
-	}
-
-	let video_stream = first_video_stream
-		.context("The input file needs to contain a usable video stream")?
-		.clone();
-	let video_duration = probe.duration()?;
-
-	let mut ffmpeg_args: Vec<String> = vec![
-		"-hide_banner".to_string(),
-		"-loglevel".to_string(),
-		"warning".to_string(),
-		"-y".to_string(),
-	];
+struct BurnSubtitle {
+	source: SubtitleSource,
+	kind: SubtitleKind,
+}
 
-	let seek = args.parse_seek();
-	let duration = args.parse_duration();
+/// Picks the subtitles to burn in: the first `--sub-streams` selection, or the input's first subtitle stream.
+fn burn_subtitle_source(args: &AutoArgs, probe: &FFProbeOutput) -> Result<BurnSubtitle> {
+	let subtitle_streams: Vec<&Stream> = probe
+		.streams
+		.iter()
+		.filter(|s| s.codec_type == StreamType::Subtitle)
+		.collect();
+	let selection = args.sub_streams.first().map_or("0", |s| s.trim());
+
+	let index = if let Ok(i) = selection.parse::<usize>() {
+		Some(i)
+	} else if Language::from_str(selection).is_ok() {
+		let position = subtitle_streams.iter().position(|s| {
+			s.tags
+				.as_ref()
+				.and_then(|t| t.language.as_deref())
+				.is_some_and(|lang| lang == selection)
+		});
+		Some(position.with_context(|| format!("The input has no subtitle stream with language \"{selection}\""))?)
+	} else {
+		None
+	};
 
-	if let Some(seek) = seek {
-		ffmpeg_args.add_two("-ss", format!("{}", seek.as_secs_f64()));
-	}
+	let kind_of = |stream: &Stream| {
+		SubtitleKind::of_stream(stream).with_context(|| {
+			format!(
+				"Subtitles in the {} format can't be burned in",
+				stream.codec_name.as_deref().unwrap_or("unknown")
+			)
+		})
+	};
 
-	let input = args.input.as_os_str().to_str().unwrap();
-	ffmpeg_args.add_two("-i", input);
-	if let Some(duration) = duration {
-		ffmpeg_args.add_two("-t", format!("{}", duration.as_secs_f64()));
+	if let Some(i) = index {
+		let stream = subtitle_streams
+			.get(i)
+			.with_context(|| format!("The input has no subtitle stream with index {i} to burn in"))?;
+		return Ok(BurnSubtitle {
+			source: SubtitleSource::Embedded(i),
+			kind: kind_of(stream)?,
+		});
 	}
 
-	ffmpeg_args.add_two("-disposition", "0");
-	ffmpeg_args.add_two("-metadata:s", "handler_name=\"\"");
-	ffmpeg_args.add_two("-empty_hdlr_name", "1");
+	// like in add_stream_maps, a subtitle file may be followed by its language
+	let path = selection
+		.split_once(':')
+		.map_or(selection, |(path, _)| path);
+	let path = Path::new(path)
+		.canonicalize()
+		.with_context(|| format!("The subtitle file \"{path}\" doesn't exist"))?;
+	let subtitle_probe = ffprobe_output(&path)?;
+	let stream = subtitle_probe
+		.get_first_subtitle_stream()
+		.with_context(|| format!("\"{}\" contains no subtitles", path.display()))?;
+
+	Ok(BurnSubtitle {
+		kind: kind_of(stream)?,
+		source: SubtitleSource::External(path),
+	})
+}
 
-	let streams_and_types = [
-		(&args.video_streams, StreamType::Video),
-		(&args.audio_streams, StreamType::Audio),
-		(&args.sub_streams, StreamType::Subtitle),
-	];
+#[derive(PartialEq)]
+enum StreamIndex {
+	Index(usize),
+	Language(Language),
+}
 
+/// Adds stream maps and language metadata for the given stream selections to the output. Muted audio isn't mapped,
+/// and subtitles are only mapped if they're among the selections. External subtitle files are added as extra inputs,
+/// so the original input has to be input 0.
+pub(crate) fn add_stream_maps(
+	args: &AutoArgs,
+	probe: &FFProbeOutput,
+	streams_and_types: &[(&Vec<String>, StreamType)],
+	first_output_idx: usize,
+	command: &mut FfmpegCommand,
+	output: &mut Output,
+) {
 	// -metadata expects output stream indices, so keep track of those
-	let mut output_stream_idx: usize = 0;
+	let mut output_stream_idx = first_output_idx;
 
 	// let subtitle_lang_re = Regex::new(r"(?i)\.(?<lang>[a-z0-9\-_]+)\.[a-z0-9]+$").unwrap();
 
 	// select appropriate streams, default to the first one respectively if none were specified
 	for (streams, stream_type) in streams_and_types {
+		let stream_type = stream_type.clone();
+		#[allow(clippy::collapsible_match)]
 		match stream_type {
 			StreamType::Audio => {
-				if !probe.has_audio_streams() {
+				if !probe.has_audio_streams() || args.mute {
 					continue;
 				}
 			}
@@ -91,7 +140,7 @@ pub(crate) fn ffmpeg_auto(args: &AutoArgs, debug: bool) -> Result<()> {
 		}
 
 		let mut used_indices: Vec<StreamIndex> = vec![];
-		for stream in streams {
+		for stream in *streams {
 			let stream = stream.trim();
 			if let Ok(i) = stream.parse::<usize>() {
 				// value is a numeric stream ID
@@ -100,7 +149,7 @@ pub(crate) fn ffmpeg_auto(args: &AutoArgs, debug: bool) -> Result<()> {
 					continue;
 				}
 
-				ffmpeg_args.add_two("-map", format!("0:{}:{i}", stream_type.identifier()));
+				output.map(format!("0:{}:{i}", stream_type.identifier()));
 				if let Some(Stream {
 					tags: Some(Tags { language: Some(lang), .. }),
 					..
@@ -110,8 +159,7 @@ pub(crate) fn ffmpeg_auto(args: &AutoArgs, debug: bool) -> Result<()> {
 					StreamType::Subtitle => probe.get_subtitle_stream(i),
 					_ => panic!("you shouldn't be here"),
 				} {
-					let lang = fix_language_code(lang);
-					ffmpeg_args.add_two(format!("-metadata:s:{output_stream_idx}"), format!("language={lang}"));
+					output.stream_metadata(&output_stream_idx.to_string(), "language", fix_language_code(lang));
 				}
 
 				used_indices.push(used_idx);
@@ -122,10 +170,8 @@ pub(crate) fn ffmpeg_auto(args: &AutoArgs, debug: bool) -> Result<()> {
 					continue;
 				}
 
-				ffmpeg_args.add_two("-map", format!("0:{}:m:language:{stream}", stream_type.identifier()));
-
-				let lang = fix_language_code(stream);
-				ffmpeg_args.add_two(format!("-metadata:s:{output_stream_idx}"), format!("language={lang}"));
+				output.map(format!("0:{}:m:language:{stream}", stream_type.identifier()));
+				output.stream_metadata(&output_stream_idx.to_string(), "language", fix_language_code(stream));
 
 				used_indices.push(used_lang);
 			} else if stream_type == StreamType::Subtitle {
@@ -145,16 +191,13 @@ pub(crate) fn ffmpeg_auto(args: &AutoArgs, debug: bool) -> Result<()> {
 					};
 				}
 
-				match path.canonicalize() {
-					Ok(canon) => {
-						ffmpeg_args.add_two("-i", canon.into_os_string().into_string().unwrap());
-						ffmpeg_args.add_two(
-							format!("-metadata:s:{output_stream_idx}"),
-							format!("language={}", fix_language_code(lang)),
-						);
-					}
-					_ => continue,
-				}
+				// subtitle files that don't exist are skipped
+				let Ok(canon) = path.canonicalize() else {
+					continue;
+				};
+				output.map(format!("{}:s:0", command.input_count()));
+				command.input(Input::new(canon));
+				output.stream_metadata(&output_stream_idx.to_string(), "language", fix_language_code(lang));
 			}
 
 			output_stream_idx += 1;
@@ -162,7 +205,10 @@ pub(crate) fn ffmpeg_auto(args: &AutoArgs, debug: bool) -> Result<()> {
 	}
 
 	// subtitle fixup
-	if args.sub_streams.is_empty() {
+	let maps_subtitles = streams_and_types
+		.iter()
+		.any(|(_, stream_type)| *stream_type == StreamType::Subtitle);
+	if args.sub_streams.is_empty() && maps_subtitles {
 		if probe
 			.streams
 			.iter()
@@ -170,69 +216,188 @@ pub(crate) fn ffmpeg_auto(args: &AutoArgs, debug: bool) -> Result<()> {
 		{
 			// there are subtitles that are not of type hdmv_pgs_subtitle, so we can actually use this
 			// TODO: this might fail for files that have both usable subtitles and hdmv_pgs_subtitle subtitles
-			ffmpeg_args.add_two("-map", "0:s?");
+			output.map("0:s?");
 		} else {
 			// there are only hdmv_pgs_subtitle subtitles, so ignore them
-			ffmpeg_args.add("-sn");
+			output.flag("sn");
+		}
+	}
+}
+
+pub(crate) fn ffmpeg_auto(args: &AutoArgs, debug: bool) -> Result<()> {
+	let probe = ffprobe_output(&args.input)?;
+
+	if args.copy {
+		return ffmpeg_auto_copy(args, &probe, debug);
+	}
+
+	let first_video_stream = probe.get_first_video_stream();
+	let first_audio_stream = probe.get_first_audio_stream();
+
+	// fail before encoding anything if this ffmpeg build is missing an encoder or the pixel format
+	let capabilities = capabilities()?;
+	capabilities.require_encoders(&[args.video_codec.video_codec()])?;
+	capabilities.require_pix_fmts(&[args.video_codec.pix_fmt()])?;
+	// audio is only encoded if there is some, it isn't muted and it can't be copied
+	if let Some(audio_stream) = first_audio_stream
+		&& !args.mute
+		&& !args.audio_copy_possible(audio_stream.codec_name.as_deref())
+	{
+		capabilities.require_encoders(&[args.video_codec.audio_codec()])?;
+	}
+
+	if !probe.has_video_streams() && !probe.has_audio_streams() {
+		anyhow::bail!("The input file contains no usable audio/video streams")
+	}
+
+	let video_stream = first_video_stream
+		.context("The input file needs to contain a usable video stream")?
+		.clone();
+	let video_duration = probe.duration()?;
+
+	let mut command = ffmpeg_command("warning");
+	let mut input = Input::new(&args.input);
+	let mut output = Output::new(&args.output);
+
+	let seek = args.parse_seek(&probe, video_stream.frame_rate())?;
+	let duration = args.parse_duration(&probe, video_stream.frame_rate())?;
+
+	let burn_subtitle = if args.burn_subtitle {
+		Some(burn_subtitle_source(args, &probe)?)
+	} else {
+		None
+	};
+	let burn_bitmap = burn_subtitle
+		.as_ref()
+		.is_some_and(|burn| burn.kind == SubtitleKind::Bitmap);
+
+	// libass only loads fonts attached to the file it reads itself, so they're dumped for external subtitle files too
+	let fonts_dir = match &burn_subtitle {
+		Some(BurnSubtitle { kind: SubtitleKind::Text, .. }) if !font_attachments(&probe).is_empty() => {
+			let fonts_dir = tempfile::tempdir().context("Could not create a directory for the attached fonts")?;
+			for attachment in font_attachments(&probe) {
+				let path = font_attachment_path(fonts_dir.path(), attachment);
+				input.option(format!("dump_attachment:{}", attachment.index), path_to_str(&path)?);
+			}
+			Some(fonts_dir)
+		}
+		_ => None,
+	};
+
+	if let Some(seek) = seek {
+		input.seek(seek);
+	}
+	if args.hwaccel {
+		input.hwaccel(args.accelerator.clone());
+	}
+	command.input(input);
+
+	// bitmap subtitles are overlaid as a stream, so an external file is an input that's seeked the same way
+	let bitmap_subtitle = match &burn_subtitle {
+		Some(BurnSubtitle {
+			source: SubtitleSource::Embedded(i),
+			kind: SubtitleKind::Bitmap,
+		}) => Some(Label::new(format!("0:s:{i}"))),
+		Some(BurnSubtitle {
+			source: SubtitleSource::External(path),
+			kind: SubtitleKind::Bitmap,
+		}) => {
+			let mut subtitle_input = Input::new(path);
+			if let Some(seek) = seek {
+				subtitle_input.seek(seek);
+			}
+			let label = Label::new(format!("{}:s:0", command.input_count()));
+			command.input(subtitle_input);
+			Some(label)
 		}
+		_ => None,
+	};
+
+	if let Some(duration) = duration {
+		output.duration(duration);
 	}
 
+	output
+		.option("disposition", 0)
+		.stream_metadata("", "handler_name", "\"\"")
+		.option("empty_hdlr_name", 1);
+
+	let mut streams_and_types = vec![
+		(&args.video_streams, StreamType::Video),
+		(&args.audio_streams, StreamType::Audio),
+		(&args.sub_streams, StreamType::Subtitle),
+	];
+	// burned subtitles end up in the video instead, and the 5th gen iPod does not support subtitles
+	if burn_subtitle.is_some() || args.optimize_target == Some(OptimizeTarget::Ipod5) {
+		streams_and_types.retain(|(_, stream_type)| *stream_type != StreamType::Subtitle);
+		output.flag("sn");
+	}
+	// the overlaid video comes out of the filtergraph, and is mapped as the first output stream
+	let burned_video = Label::new("burned");
+	if burn_bitmap {
+		streams_and_types.retain(|(_, stream_type)| *stream_type != StreamType::Video);
+		output.map(burned_video.to_string());
+	}
+	add_stream_maps(
+		args,
+		&probe,
+		&streams_and_types,
+		usize::from(burn_bitmap),
+		&mut command,
+		&mut output,
+	);
+
 	let (mut fade_in, mut fade_out) = (args.fade_in, args.fade_out);
 	if args.fade > 0.0 {
 		fade_in = args.fade;
 		fade_out = args.fade;
 	}
-	let fade_out_start = if let Some(duration) = duration {
-		// duration was given
-		duration.as_secs_f64() - fade_out
-	} else {
-		// duration wasn't given, use video duration
-		(video_duration.saturating_sub(seek.unwrap_or(Duration::ZERO))).as_secs_f64() - fade_out
-	};
+	// if no duration was given, the output runs until the end of the video
+	let output_duration = duration.unwrap_or_else(|| video_duration.saturating_sub(seek.unwrap_or(Duration::ZERO)));
+	let fade_out_start = output_duration.as_secs_f64() - fade_out;
 
 	// region Audio Filtering
 
 	if first_audio_stream.is_none() || args.mute {
 		// input has no audio streams or explicit mute was requested
-		ffmpeg_args.add("-an");
+		output.flag("an");
 	} else if let Some(audio_stream) = first_audio_stream.cloned() {
 		if args.audio_copy_possible(audio_stream.codec_name.as_deref()) {
 			// input stream is already aac, copy stream
-			ffmpeg_args.add_two("-c:a", "copy");
+			output.codec("a", "copy");
 		} else {
 			// input stream is not aac or transcoding is needed
-			ffmpeg_args.add_two("-c:a", args.video_codec.audio_codec());
+			output.codec("a", args.video_codec.audio_codec());
 
 			match args.optimize_target {
 				Some(OptimizeTarget::Ipod) => {
-					ffmpeg_args.add_two("-b:a", "160k");
+					output.option("b:a", "160k");
 				}
 				_ => {
-					ffmpeg_args.add_two("-b:a", "256k");
+					output.option("b:a", "256k");
 				}
 			}
 
 			if let Some(audio_channels) = &args.audio_channels {
-				ffmpeg_args.add_two("-ac", audio_channels.clone());
+				output.option("ac", audio_channels);
 			}
 
 			if args.needs_audio_filter() {
-				let mut audio_filter: Vec<String> = vec![];
+				let mut audio_filter = Chain::new();
 
 				#[allow(clippy::float_cmp)]
 				if args.audio_volume != 1.0 {
-					audio_filter.push(format!("volume={:.3}", args.audio_volume));
+					audio_filter.push(Filter::new("volume").positional(format!("{:.3}", args.audio_volume)));
 				}
 
 				if fade_in > 0.0 {
-					audio_filter.push(format!("afade=t=in:st=0:d={fade_in:.3}:curve=losi"));
+					audio_filter.push(fade_filter("afade", "in", 0.0, fade_in).arg("curve", "losi"));
 				}
 				if fade_out > 0.0 {
-					audio_filter.push(format!("afade=t=out:st={fade_out_start:.3}:d={fade_out:.3}:curve=losi"));
+					audio_filter.push(fade_filter("afade", "out", fade_out_start, fade_out).arg("curve", "losi"));
 				}
 
-				let audio_filter_str = audio_filter.join(",");
-				ffmpeg_args.add_two("-af", audio_filter_str);
+				output.audio_filter(audio_filter);
 			}
 		}
 	}
@@ -241,67 +406,73 @@ pub(crate) fn ffmpeg_auto(args: &AutoArgs, debug: bool) -> Result<()> {
 
 	// region Video Filtering
 
-	ffmpeg_args.add_two("-c:v", args.video_codec.video_codec());
-	ffmpeg_args.add_two("-crf", format!("{}", &args.video_codec.crf_with_garbage(args.garbage)));
-	ffmpeg_args.add_two("-pix_fmt", args.video_codec.pix_fmt());
-	ffmpeg_args.add_two("-preset", "slower");
-	ffmpeg_args.add("-tune");
+	output
+		.codec("v", args.video_codec.video_codec())
+		.option("crf", args.video_codec.crf_with_garbage(args.garbage))
+		.option("pix_fmt", args.video_codec.pix_fmt())
+		.option("preset", "slower");
 	match args.video_codec {
 		VideoCodec::H264 => {
-			ffmpeg_args.add("film");
+			output.option("tune", "film");
 		}
 		VideoCodec::H265 | VideoCodec::H265_10 => {
-			ffmpeg_args.add("grain");
-			ffmpeg_args.add("-tag:v");
-			ffmpeg_args.add("hvc1");
+			output.option("tune", "grain").option("tag:v", "hvc1");
 		}
 	}
 
-	ffmpeg_args.add_two("-partitions", "all");
-	ffmpeg_args.add_two("-me_method", "tesa");
+	output.option("partitions", "all");
+	output.option("me_method", "tesa");
 
 	// add extra ffmpeg arguments that aren't handled by optimize_settings()
 	// TODO: test this on actual target devices
 	match args.optimize_target {
 		None => (),
 		Some(OptimizeTarget::Ipod5) => {
-			ffmpeg_args.add_two("-profile:v", "baseline"); // apple: baseline
-			ffmpeg_args.add_two("-level", "1.3"); // apple: 1.3
-			ffmpeg_args.add_two("-maxrate", "768K"); // apple: 768 kbps, actual level limit
-			ffmpeg_args.add_two("-bufsize", "2M");
-			ffmpeg_args.add("-sn"); // the 5th gen iPod does not support subtitles
-			ffmpeg_args.add_two("-map_chapters", "0"); // it does however support video chapters
+			output.option("profile:v", "baseline"); // apple: baseline
+			output.option("level", "1.3"); // apple: 1.3
+			output.option("map_chapters", "0"); // it does however support video chapters
 		}
 		Some(OptimizeTarget::Ipod) => {
-			ffmpeg_args.add_two("-profile:v", "baseline"); // apple: baseline
-			ffmpeg_args.add_two("-level", "3.0"); // apple: 3.0
-			ffmpeg_args.add_two("-maxrate", "2.5M"); // apple: 2.5 mbps
-			ffmpeg_args.add_two("-bufsize", "5M");
-			ffmpeg_args.add_two("-c:s", "mov_text");
-			ffmpeg_args.add_two("-tag:s", "tx3g");
-			ffmpeg_args.add_two("-map_chapters", "0");
+			output.option("profile:v", "baseline"); // apple: baseline
+			output.option("level", "3.0"); // apple: 3.0
+			output.codec("s", "mov_text");
+			output.option("tag:s", "tx3g");
+			output.option("map_chapters", "0");
 		}
 		Some(OptimizeTarget::Psp) => {
-			ffmpeg_args.add_two("-profile:v", "main");
-			ffmpeg_args.add_two("-level", "3.0");
-			ffmpeg_args.add_two("-maxrate", "3M"); // needs verification
-			ffmpeg_args.add_two("-bufsize", "6M");
+			output.option("profile:v", "main");
+			output.option("level", "3.0");
 		}
 		Some(OptimizeTarget::PsVita) => {
 			// H.264/MPEG-4 AVC Hi/Main/Baseline Profile (AAC)
-			ffmpeg_args.add_two("-profile:v", "high");
-			ffmpeg_args.add_two("-level", "4.1");
-			ffmpeg_args.add_two("-maxrate", "10M");
-			ffmpeg_args.add_two("-bufsize", "20M");
+			output.option("profile:v", "high");
+			output.option("level", "4.1");
 		}
 	}
 
+	if let Some(target) = &args.optimize_target {
+		let (maxrate, bufsize) = target.rate_limits();
+		output.option("maxrate", maxrate);
+		output.option("bufsize", bufsize);
+	}
+
 	if args.faststart {
-		ffmpeg_args.add_two("-movflags", "faststart");
+		output.option("movflags", "faststart");
 	}
 
 	if args.needs_video_filter() {
-		let mut video_filter: Vec<String> = vec![];
+		let mut video_filter = match &bitmap_subtitle {
+			// bitmaps are overlaid at the size they were authored for, before anything is cropped or scaled
+			Some(subtitle) => Chain::new()
+				.input(Label::new(format!("0:{}", video_stream.index)))
+				.input(subtitle.clone())
+				.filter(
+					Filter::new("overlay")
+						.arg("eof_action", "pass")
+						.arg("format", "auto"),
+				),
+			None => Chain::new(),
+		};
 
 		match args.optimize_target {
 			// limit fps to 30
@@ -321,39 +492,47 @@ pub(crate) fn ffmpeg_auto(args: &AutoArgs, debug: bool) -> Result<()> {
 			}
 		}
 
-		if let Some(crop_filter) = args.generate_crop_filter() {
-			video_filter.push(crop_filter);
-		}
+		video_filter.extend(args.generate_crop_filter(&probe, &video_stream)?);
+		video_filter.extend(args.generate_scale_filters()?);
 
-		if let Some(scale_filter) = args.generate_scale_filter() {
-			video_filter.push(scale_filter);
+		if args.tonemap || args.video_codec != VideoCodec::H265_10 {
+			video_filter.extend(args.generate_tonemap_filters(&video_stream, &mut command)?);
 		}
 
-		if (args.tonemap || args.video_codec != VideoCodec::H265_10) && video_stream.is_hdr() {
-			video_filter.push(TONEMAP_FILTER.parse()?);
+		// text is rendered at the output size and after tonemapping, so it stays sharp and keeps its colors
+		if let Some(BurnSubtitle { source, kind: SubtitleKind::Text }) = &burn_subtitle {
+			let (filename, stream_index) = match source {
+				SubtitleSource::Embedded(i) => (args.input.as_path(), Some(*i)),
+				SubtitleSource::External(path) => (path.as_path(), None),
+			};
+			capabilities.require_filters(&[text_subtitle_filter(filename, stream_index)])?;
+			video_filter.extend(text_subtitle_filters(
+				filename,
+				stream_index,
+				fonts_dir.as_ref().map(tempfile::TempDir::path),
+				seek,
+			)?);
 		}
 
 		if fade_in > 0.0 {
-			video_filter.push(format!("fade=t=in:st=0:d={fade_in:.3}"));
+			video_filter.push(fade_filter("fade", "in", 0.0, fade_in));
 		}
 		if fade_out > 0.0 {
-			video_filter.push(format!("fade=t=out:st={fade_out_start:.3}:d={fade_out:.3}"));
+			video_filter.push(fade_filter("fade", "out", fade_out_start, fade_out));
 		}
 
-		let video_filter_str = video_filter.join(",");
-		if !video_filter_str.is_empty() {
-			ffmpeg_args.add_two("-vf", video_filter_str);
+		if burn_bitmap {
+			let mut graph = Graph::new();
+			graph.push(video_filter.output(burned_video));
+			command.filter_complex(graph);
+		} else {
+			output.video_filter(video_filter);
 		}
 	}
 
 	// endregion
 
-	ffmpeg_args.push(args.output.to_str().unwrap().to_string());
+	command.output(output);
 
-	ffmpeg(
-		&ffmpeg_args,
-		args.hwaccel.then(|| args.accelerator.clone()),
-		true,
-		debug,
-	)
+	Ok(ffmpeg(&command, Some(ProgressTotal::Duration(output_duration)), debug)?)
 }
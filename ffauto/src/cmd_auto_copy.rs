@@ -0,0 +1,381 @@
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use ffmpeg::ffmpeg::capabilities::capabilities;
+use ffmpeg::ffmpeg::command::{Input, Output};
+use ffmpeg::ffmpeg::enums::VideoCodec;
+use ffmpeg::ffmpeg::ffmpeg::ffmpeg;
+use ffmpeg::ffmpeg::ffprobe::{ffprobe, ffprobe_packets};
+use ffmpeg::ffmpeg::ffprobe_struct::{FFProbeOutput, Stream, StreamType};
+use ffmpeg::ffmpeg::keyframes::{
+	CutSegment, keyframe_at_or_after, keyframe_at_or_before, keyframe_times, keyframes_from_start, smart_cut_segments,
+};
+use ffmpeg::ffmpeg::progress::ProgressTotal;
+use ffmpeg::ffmpeg::script::{RunMode, run_mode, write_file};
+use ffmpeg::ffmpeg::timestamps::{TimestampFormat, format_ffmpeg_timestamp};
+use ffmpeg::outln;
+use tempfile::Builder;
+
+use crate::cmd_auto::add_stream_maps;
+use crate::commands::AutoArgs;
+use crate::common::*;
+
+fn format_time(seconds: f64) -> String {
+	format_ffmpeg_timestamp(Duration::from_secs_f64(seconds.max(0.0)), &TimestampFormat::Full)
+}
+
+/// Reports where a cut point ended up. Times are counted like `-ss` does, from the container's start time,
+/// which is added back to show where the keyframe sits in the file's own timestamps.
+fn report_cut(name: &str, requested: f64, snapped: f64, start_time: f64) {
+	let in_file = if start_time == 0.0 {
+		String::new()
+	} else {
+		format!(", {} in the file", format_time(snapped + start_time))
+	};
+
+	if (requested - snapped).abs() < 0.0005 {
+		outln!("{name}: {} (on a keyframe{in_file})", format_time(snapped));
+	} else {
+		outln!(
+			"{name}: {} → {} ({:+.3}s{in_file})",
+			format_time(requested),
+			format_time(snapped),
+			snapped - requested
+		);
+	}
+}
+
+/// Returns the video stream that [`add_stream_maps`] maps first, whose keyframes the cut points are moved to.
+fn mapped_video_stream<'a>(args: &AutoArgs, probe: &'a FFProbeOutput) -> Result<&'a Stream> {
+	let stream = match args.video_streams.first().map(|s| s.trim()) {
+		None => probe.get_first_video_stream(),
+		Some(stream) => match stream.parse::<usize>() {
+			Ok(i) => probe.get_video_stream(i),
+			Err(_) => probe.get_video_stream_by_language(stream),
+		},
+	};
+
+	stream.context("The input file needs to contain a usable video stream")
+}
+
+/// Picks the encoder for the re-encoded parts of a smart cut. These have to use the same codec as the copied part,
+/// otherwise the concat demuxer can't join them.
+fn smart_cut_codec(video_stream: &Stream) -> Result<VideoCodec> {
+	match (video_stream.codec_name.as_deref(), video_stream.pix_fmt.as_deref()) {
+		(Some("h264"), _) => Ok(VideoCodec::H264),
+		(Some("hevc"), Some(pix_fmt)) if pix_fmt.contains("10") => Ok(VideoCodec::H265_10),
+		(Some("hevc"), _) => Ok(VideoCodec::H265),
+		(codec, _) => anyhow::bail!(
+			"Smart cutting only supports H.264 and H.265 input, not {}",
+			codec.unwrap_or("unknown")
+		),
+	}
+}
+
+/// Maps ffprobe's profile names to the encoder's.
+fn encoder_profile(codec: &VideoCodec, profile: &str) -> Result<&'static str> {
+	Ok(match (codec, profile) {
+		(_, "Main") => "main",
+		(VideoCodec::H264, "Constrained Baseline") => "baseline",
+		(VideoCodec::H264, "High") => "high",
+		(VideoCodec::H264, "High 10") => "high10",
+		(VideoCodec::H264, "High 4:2:2") => "high422",
+		(VideoCodec::H264, "High 4:4:4 Predictive") => "high444",
+		(VideoCodec::H265 | VideoCodec::H265_10, "Main 10") => "main10",
+		_ => anyhow::bail!("Smart cutting can't encode the {profile} profile of the input"),
+	})
+}
+
+/// Sets up the encoder for the re-encoded parts of a smart cut with the source's pixel format, profile, level and
+/// colour properties. Size, sample aspect ratio and timestamps stay as they are, since nothing is filtered.
+fn add_smart_cut_encoder_args(source: &Stream, codec: &VideoCodec, output: &mut Output) -> Result<()> {
+	let pix_fmt = source
+		.pix_fmt
+		.as_deref()
+		.context("ffprobe couldn't find the pixel format of the video stream, which smart cutting needs to match")?;
+
+	// stay close to the quality of the copied part
+	output
+		.codec("v", codec.video_codec())
+		.option("crf", codec.default_crf().saturating_sub(5))
+		.option("pix_fmt", pix_fmt)
+		.option("preset", "slower")
+		.option("fps_mode", "passthrough");
+
+	if let Some(profile) = &source.profile {
+		output.option("profile:v", encoder_profile(codec, profile)?);
+	}
+	if let Some(level) = source.level.filter(|l| *l > 0) {
+		match codec {
+			VideoCodec::H264 => output.option("level", format!("{}.{}", level / 10, level % 10)),
+			// H.265 levels are reported times 30
+			VideoCodec::H265 | VideoCodec::H265_10 => {
+				output.option("x265-params", format!("level-idc={}.{}", level / 30, level % 30 / 3))
+			}
+		};
+	}
+	for (option, value) in [
+		("color_primaries", &source.color_primaries),
+		("color_trc", &source.color_transfer),
+		("colorspace", &source.color_space),
+		("color_range", &source.color_range),
+	] {
+		if let Some(value) = value {
+			output.option(option, value);
+		}
+	}
+
+	Ok(())
+}
+
+/// The parameters that the re-encoded parts of a smart cut have to share with the copied ones.
+fn stream_parameters(stream: &Stream) -> [(&'static str, Option<String>); 6] {
+	[
+		("codec", stream.codec_name.clone()),
+		("profile", stream.profile.clone()),
+		("level", stream.level.filter(|l| *l > 0).map(|l| l.to_string())),
+		("pixel format", stream.pix_fmt.clone()),
+		(
+			"size",
+			stream
+				.width
+				.zip(stream.height)
+				.map(|(w, h)| format!("{w}x{h}")),
+		),
+		("sample aspect ratio", stream.sar.clone()),
+	]
+}
+
+/// Checks with ffprobe that the video in `path` matches the source's parameters and, if given, its duration.
+/// Nothing is written in a dry run, so there's nothing to check then either.
+fn check_matches_source(source: &Stream, path: &Path, what: &str, duration: Option<f64>) -> Result<()> {
+	if run_mode() != RunMode::Run {
+		return Ok(());
+	}
+
+	let probe = ffprobe(path, false)?;
+	let stream = probe
+		.get_first_video_stream()
+		.with_context(|| format!("The {what} of the smart cut has no video stream"))?;
+	for ((name, expected), (_, actual)) in stream_parameters(source)
+		.into_iter()
+		.zip(stream_parameters(stream))
+	{
+		if expected.is_some() && actual != expected {
+			anyhow::bail!(
+				"The {what} of the smart cut has the {name} {}, but the input has {}",
+				actual.as_deref().unwrap_or("unknown"),
+				expected.as_deref().unwrap_or("unknown")
+			)
+		}
+	}
+
+	if let Some(duration) = duration {
+		// allow for a few frames, since the parts are cut at frame boundaries
+		let tolerance = 5.0 / source.frame_rate().unwrap_or(25.0);
+		let actual = probe.duration()?.as_secs_f64();
+		if (actual - duration).abs() > tolerance {
+			anyhow::bail!(
+				"The {what} of the smart cut is {actual:.3}s long instead of {duration:.3}s, some of its parts didn't join"
+			)
+		}
+	}
+
+	Ok(())
+}
+
+fn add_output_args(args: &AutoArgs, output: &mut Output) {
+	output.codec("", "copy");
+	if args.mute {
+		output.flag("an");
+	}
+	output.option("avoid_negative_ts", "make_zero");
+	if args.faststart {
+		output.option("movflags", "faststart");
+	}
+}
+
+/// The `--copy` mode of `ff auto`: stream-copies everything, moving the cut points to keyframes
+/// or, with `--smart-cut`, re-encoding only the partial GOPs at either end.
+pub(crate) fn ffmpeg_auto_copy(args: &AutoArgs, probe: &FFProbeOutput, debug: bool) -> Result<()> {
+	let video_stream = mapped_video_stream(args, probe)?;
+	let video_duration = probe.duration()?.as_secs_f64();
+
+	let start = args
+		.parse_seek(probe, video_stream.frame_rate())?
+		.map_or(0.0, |s| s.as_secs_f64());
+	let end = args
+		.parse_duration(probe, video_stream.frame_rate())?
+		.map_or(video_duration, |d| (start + d.as_secs_f64()).min(video_duration));
+
+	// packets carry keyframe flags too and don't need to be decoded, which makes this much faster than probing frames
+	let packets = ffprobe_packets(&args.input, Some(&video_stream.index.to_string()), None)?;
+	let start_time = probe.start_time();
+	let keyframes = keyframes_from_start(&keyframe_times(&packets, video_stream.index), start_time);
+	if keyframes.is_empty() {
+		anyhow::bail!(
+			"ffprobe couldn't find any keyframes in video stream {}",
+			video_stream.index
+		)
+	}
+
+	if args.smart_cut {
+		return smart_cut(args, probe, video_stream, &keyframes, start, end, debug);
+	}
+
+	let snapped_start = keyframe_at_or_before(&keyframes, start).unwrap_or(0.0);
+	let snapped_end = keyframe_at_or_after(&keyframes, end)
+		.filter(|e| *e > snapped_start)
+		.unwrap_or(video_duration);
+	report_cut("Start", start, snapped_start, start_time);
+	report_cut("End", end, snapped_end, start_time);
+
+	let mut command = ffmpeg_command("warning");
+	let mut input = Input::new(&args.input);
+	let mut output = Output::new(&args.output);
+
+	if snapped_start > 0.0 {
+		input.seek(Duration::from_secs_f64(snapped_start));
+	}
+	command.input(input);
+	if snapped_end < video_duration {
+		output.duration(Duration::from_secs_f64(snapped_end - snapped_start));
+	}
+
+	let streams_and_types = [
+		(&args.video_streams, StreamType::Video),
+		(&args.audio_streams, StreamType::Audio),
+		(&args.sub_streams, StreamType::Subtitle),
+	];
+	add_stream_maps(args, probe, &streams_and_types, 0, &mut command, &mut output);
+	add_output_args(args, &mut output);
+	command.output(output);
+
+	let output_duration = Duration::from_secs_f64(snapped_end - snapped_start);
+	Ok(ffmpeg(&command, Some(ProgressTotal::Duration(output_duration)), debug)?)
+}
+
+/// Cuts at the exact times by re-encoding the partial GOPs at either end and stream-copying the ones in between.
+/// The concat demuxer only joins these if they decode alike, so the re-encoded parts get the source's parameters
+/// and are checked with ffprobe. Every part is kept as MPEG-TS, which carries the parameter sets in-band,
+/// since the encoder's differ from the source's even when the parameters match.
+fn smart_cut(
+	args: &AutoArgs,
+	probe: &FFProbeOutput,
+	video_stream: &Stream,
+	keyframes: &[f64],
+	start: f64,
+	end: f64,
+	debug: bool,
+) -> Result<()> {
+	let codec = smart_cut_codec(video_stream)?;
+	capabilities()?.require_encoders(&[codec.video_codec()])?;
+	let segments = smart_cut_segments(keyframes, start, end);
+
+	let encoded = segments
+		.iter()
+		.filter(|s| matches!(s, CutSegment::Encode { .. }))
+		.map(CutSegment::duration)
+		.sum::<f64>();
+	let copied = segments
+		.iter()
+		.filter(|s| matches!(s, CutSegment::Copy { .. }))
+		.map(CutSegment::duration)
+		.sum::<f64>();
+	outln!("Cutting {} to {}", format_time(start), format_time(end));
+	outln!("Re-encoding {encoded:.3}s, copying {copied:.3}s");
+
+	let input = &args.input;
+	let temp_dir = Builder::new()
+		.prefix("ffauto")
+		.tempdir()
+		.context("Couldn't create temp directory")?;
+
+	let mut concat_list = Vec::new();
+	for (i, segment) in segments.iter().enumerate() {
+		let segment_path = temp_dir.path().join(format!("segment{i}.ts"));
+
+		let mut segment_input = Input::new(input);
+		segment_input.seek(Duration::from_secs_f64(segment.start()));
+
+		let mut segment_output = Output::new(&segment_path);
+		segment_output
+			.map(format!("0:{}", video_stream.index))
+			.duration(Duration::from_secs_f64(segment.duration()))
+			.flag("an")
+			.flag("sn")
+			.flag("dn");
+
+		match segment {
+			CutSegment::Copy { .. } => {
+				let annexb = match codec {
+					VideoCodec::H264 => "h264_mp4toannexb",
+					VideoCodec::H265 | VideoCodec::H265_10 => "hevc_mp4toannexb",
+				};
+				segment_output.codec("v", "copy").option("bsf:v", annexb);
+			}
+			CutSegment::Encode { .. } => add_smart_cut_encoder_args(video_stream, &codec, &mut segment_output)?,
+		}
+		segment_output.option("f", "mpegts");
+
+		let mut command = ffmpeg_command("warning");
+		command.input(segment_input).output(segment_output);
+		ffmpeg(&command, None, debug)?;
+		if let CutSegment::Encode { .. } = segment {
+			check_matches_source(video_stream, &segment_path, "re-encoded part", None)?;
+		}
+
+		// the list is written as bytes, so that paths don't need to be valid UTF-8
+		concat_list.extend_from_slice(b"file '");
+		for &byte in segment_path.as_os_str().as_encoded_bytes() {
+			match byte {
+				b'\'' => concat_list.extend_from_slice(br"'\''"),
+				byte => concat_list.push(byte),
+			}
+		}
+		concat_list.extend_from_slice(b"'\n");
+	}
+
+	let concat_list_path = temp_dir.path().join("segments.txt");
+	write_file(&concat_list_path, &concat_list).context("Couldn't write concat list")?;
+
+	// input 0 is the original file so that add_stream_maps can map audio and subtitles from it,
+	// and external subtitle files come after the joined parts
+	let mut command = ffmpeg_command("warning");
+	let mut original = Input::new(input);
+	if start > 0.0 {
+		original.seek(Duration::from_secs_f64(start));
+	}
+	let mut joined = Input::new(&concat_list_path);
+	joined.format("concat").option("safe", 0);
+	command.input(original).input(joined);
+
+	let mut output = Output::new(&args.output);
+	output
+		.duration(Duration::from_secs_f64(end - start))
+		.map("1:v:0");
+	let streams_and_types = [
+		(&args.audio_streams, StreamType::Audio),
+		(&args.sub_streams, StreamType::Subtitle),
+	];
+	add_stream_maps(args, probe, &streams_and_types, 1, &mut command, &mut output);
+	add_output_args(args, &mut output);
+	// MP4 needs to be told that the parameter sets change within the stream
+	let is_mp4 = args
+		.output
+		.extension()
+		.and_then(|ext| ext.to_str())
+		.is_some_and(|ext| ["mp4", "m4v", "mov"].contains(&ext.to_lowercase().as_str()));
+	if is_mp4 {
+		match codec {
+			VideoCodec::H264 => output.option("tag:v", "avc3"),
+			VideoCodec::H265 | VideoCodec::H265_10 => output.option("tag:v", "hev1"),
+		};
+	}
+	command.output(output);
+
+	let output_duration = Duration::from_secs_f64(end - start);
+	ffmpeg(&command, Some(ProgressTotal::Duration(output_duration)), debug)?;
+	check_matches_source(video_stream, &args.output, "output", Some(end - start))
+}
@@ -1,11 +1,13 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 use crate::commands::BarcodeArgs;
 use crate::common::*;
-use crate::vec_push_ext::PushStrExt;
+use ffmpeg::ffmpeg::command::{Input, Output};
 use ffmpeg::ffmpeg::enums::BarcodeMode;
 use ffmpeg::ffmpeg::ffmpeg::ffmpeg;
 use ffmpeg::ffmpeg::ffprobe::ffprobe;
+use ffmpeg::ffmpeg::filtergraph::{Chain, Filter, Graph, Label};
+use ffmpeg::ffmpeg::progress::ProgressTotal;
 
 pub(crate) fn ffmpeg_barcode(args: &BarcodeArgs, debug: bool) -> Result<()> {
 	let probe = match args.video_frames {
@@ -13,92 +15,129 @@ pub(crate) fn ffmpeg_barcode(args: &BarcodeArgs, debug: bool) -> Result<()> {
 		Some(_) => ffprobe(&args.input, false)?,
 	};
 
-	let mut ffmpeg_args: Vec<String> = vec![
-		"-hide_banner".to_string(),
-		"-loglevel".to_string(),
-		"warning".to_string(),
-		"-y".to_string(),
-	];
-
-	let input = args.input.as_os_str().to_str().unwrap();
-	ffmpeg_args.add_two("-i", input);
+	let mut command = ffmpeg_command("warning");
+	command.input(Input::new(&args.input));
 
 	let (video_stream, video_stream_id) =
 		probe.checked_get_video_stream_by_index_or_language(&args.video_language, args.video_stream)?;
 
-	let video_height = video_stream.height.unwrap();
-	let video_frames = &args
-		.video_frames
-		.unwrap_or_else(|| video_stream.total_frames().unwrap());
+	// checked_get_video_stream_by_index_or_language already made sure the height is set
+	let video_height = video_stream.height.unwrap_or_default();
+	let video_frames = &match args.video_frames {
+		Some(frames) => frames,
+		None => video_stream
+			.total_frames()
+			.context("ffprobe couldn't determine the number of frames, use --frames to set it manually")?,
+	};
 	check_frame_size(*video_frames, video_height)?;
 
 	// region Filtering
 
-	let mut video_pipelines: Vec<Vec<String>> = vec![];
-	let mut input_pipeline: Vec<String> = vec![];
-
-	if video_stream.is_hdr() {
-		input_pipeline.push(format!("[{video_stream_id}]{TONEMAP_FILTER}"));
-		input_pipeline.add("format=rgb48be");
-	} else {
-		input_pipeline.add(format!("[{video_stream_id}]format=rgb48be"));
-	}
+	let mut graph = Graph::new();
+	let video_out = graph.label("video_out");
+
+	let mut input_pipeline = Chain::new().input(Label::new(video_stream_id));
+	input_pipeline.extend(args.generate_tonemap_filters(&video_stream, &mut command)?);
+	input_pipeline.push(Filter::new("format").positional("rgb48be"));
+
+	// squeezes every frame into a single column and puts them all next to each other
+	let strip = |chain: Chain, flags: &str| {
+		chain
+			.filter(
+				Filter::new("scale")
+					.arg("w", 1)
+					.arg("h", video_height)
+					.arg("flags", format!("{flags}+{SCALE_FLAGS}")),
+			)
+			.filter(Filter::new("tile").positional(format!("{video_frames}x1")))
+	};
 
 	match args.barcode_mode {
 		BarcodeMode::Frames => {
-			input_pipeline.add(format!("scale=w=1:h={video_height}:flags=bicubic+{SCALE_FLAGS}"));
-			input_pipeline.add(format!("tile={video_frames}x1 [video_out]"));
-			video_pipelines.push(input_pipeline);
+			graph.push(strip(input_pipeline, "bicubic").output(video_out.clone()));
 		}
 		BarcodeMode::Colors => {
-			input_pipeline.add(format!("scale=w=320:h=-2:flags=bicubic+{SCALE_FLAGS}"));
-			input_pipeline.add("colorspace=all=bt709:trc=srgb:range=pc"); // palettegen complains if this isn't here
-			input_pipeline.add("palettegen=max_colors=2:reserve_transparent=0:stats_mode=single");
-			input_pipeline.add("split [p1][p2]");
-			video_pipelines.push(input_pipeline);
-
-			video_pipelines.push(vec![
-				"[p1] crop=1:1:0:0".to_string(), // dark
-				format!("scale=w=1:h={video_height}:flags=neighbor+{SCALE_FLAGS}"),
-				format!("tile={video_frames}x1 [s1]"),
-			]);
-			video_pipelines.push(vec![
-				"[p2] crop=1:1:1:0".to_string(), // light
-				format!("scale=w=1:h={video_height}:flags=neighbor+{SCALE_FLAGS}"),
-				format!("tile={video_frames}x1 [s2]"),
-			]);
-			video_pipelines.push(vec!["[s2][s1] blend=all_mode=softlight [video_out]".to_string()]);
+			let (p1, p2) = (graph.label("p"), graph.label("p"));
+			let (s1, s2) = (graph.label("s"), graph.label("s"));
+
+			graph.push(
+				input_pipeline
+					.filter(
+						Filter::new("scale")
+							.arg("w", 320)
+							.arg("h", -2)
+							.arg("flags", format!("bicubic+{SCALE_FLAGS}")),
+					)
+					// palettegen complains if this isn't here
+					.filter(
+						Filter::new("colorspace")
+							.arg("all", "bt709")
+							.arg("trc", "srgb")
+							.arg("range", "pc"),
+					)
+					.filter(
+						Filter::new("palettegen")
+							.arg("max_colors", 2)
+							.arg("reserve_transparent", 0)
+							.arg("stats_mode", "single"),
+					)
+					.filter(Filter::new("split"))
+					.outputs([p1.clone(), p2.clone()]),
+			);
+
+			let crop = |x: u8| {
+				Filter::new("crop")
+					.positional(1)
+					.positional(1)
+					.positional(x)
+					.positional(0)
+			};
+			graph.push(strip(Chain::new().input(p1).filter(crop(0)), "neighbor").output(s1.clone())); // dark
+			graph.push(strip(Chain::new().input(p2).filter(crop(1)), "neighbor").output(s2.clone())); // light
+			graph.push(
+				Chain::new()
+					.inputs([s2, s1])
+					.filter(Filter::new("blend").arg("all_mode", "softlight"))
+					.output(video_out.clone()),
+			);
 		}
 	}
 
-	let mut output_pipeline = vec![];
-	output_pipeline.add("[video_out] setsar=1");
+	let mut output_pipeline = Chain::new()
+		.input(video_out)
+		.filter(Filter::new("setsar").positional(1));
 
 	if args.deep_color {
-		output_pipeline.add("format=rgb48be");
+		output_pipeline.push(Filter::new("format").positional("rgb48be"));
 	} else {
-		output_pipeline.add("format=rgb24");
+		output_pipeline.push(Filter::new("format").positional("rgb24"));
 	}
 	if let Some(output_height) = args.height {
-		output_pipeline.add(format!("scale=h={output_height}:flags=bicubic+{SCALE_FLAGS}"));
+		output_pipeline.push(
+			Filter::new("scale")
+				.arg("h", output_height)
+				.arg("flags", format!("bicubic+{SCALE_FLAGS}")),
+		);
 	}
-	output_pipeline.add("setparams=colorspace=bt709:color_primaries=bt709:color_trc=iec61966-2-1");
-	video_pipelines.push(output_pipeline);
+	output_pipeline.push(
+		Filter::new("setparams")
+			.arg("colorspace", "bt709")
+			.arg("color_primaries", "bt709")
+			.arg("color_trc", "iec61966-2-1"),
+	);
+	graph.push(output_pipeline);
 
-	let filter_graph = video_pipelines
-		.iter()
-		.map(|p| p.join(","))
-		.collect::<Vec<String>>()
-		.join(";");
-	ffmpeg_args.add_two("-filter_complex", filter_graph);
+	command.filter_complex(graph);
 
 	// endregion
 
-	ffmpeg_args.add_two("-c:v", "png");
-	ffmpeg_args.add_two("-f", "image2");
-	ffmpeg_args.add_two("-update", "1");
-
-	ffmpeg_args.push(args.output.to_str().unwrap().to_string());
+	let mut output = Output::new(&args.output);
+	output
+		.codec("v", "png")
+		.format("image2")
+		.option("update", 1);
+	command.output(output);
 
-	ffmpeg(&ffmpeg_args, None, false, debug)
+	// tile only outputs a single frame at the very end, so there's nothing to measure progress against
+	Ok(ffmpeg(&command, Some(ProgressTotal::Unknown), debug)?)
 }
@@ -0,0 +1,341 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use ffmpeg::ffmpeg::bitrate::{bitrate_per_second, keyframe_intervals, peak_bitrate};
+use ffmpeg::ffmpeg::command::{Input, Output};
+use ffmpeg::ffmpeg::ffmpeg::ffmpeg;
+use ffmpeg::ffmpeg::ffprobe::{ffprobe_frames_detail, ffprobe_packets};
+use ffmpeg::ffmpeg::ffprobe_struct::{PacketInfo, PictType, Stream, StreamType};
+use ffmpeg::ffmpeg::script::write_file;
+use ffmpeg::outln;
+use serde::Serialize;
+use tempfile::Builder;
+
+use crate::commands::BitrateArgs;
+use crate::common::{ffmpeg_command, ffprobe_output};
+
+const SPARKLINE_WIDTH: usize = 72;
+const SPARKLINE_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+const CHART_WIDTH: usize = 1280;
+const CHART_HEIGHT: usize = 360;
+const CHART_BACKGROUND: [u8; 3] = [24, 24, 24];
+const CHART_VIDEO: [u8; 3] = [66, 135, 245];
+const CHART_AUDIO: [u8; 3] = [230, 80, 80];
+const CHART_LIMIT: [u8; 3] = [250, 200, 50];
+
+#[derive(Serialize)]
+struct StreamReport {
+	index: u64,
+	codec_name: Option<String>,
+	average_bitrate: f64,
+	peak_bitrate: f64,
+	per_second: Vec<u64>,
+}
+
+#[derive(Serialize)]
+struct IntervalStats {
+	count: usize,
+	min: f64,
+	avg: f64,
+	max: f64,
+}
+
+#[derive(Serialize)]
+struct FrameTypeRatios {
+	i: f64,
+	p: f64,
+	b: f64,
+}
+
+#[derive(Serialize)]
+struct BitrateReport {
+	window: f64,
+	maxrate: Option<f64>,
+	video: Option<StreamReport>,
+	audio: Option<StreamReport>,
+	keyframe_intervals: Option<IntervalStats>,
+	frame_types: Option<FrameTypeRatios>,
+}
+
+/// Parses bitrates the way ffmpeg does for `-maxrate` and `-bufsize`, i.e. `768K`, `2.5M` or plain bits.
+fn parse_bitrate(bitrate: &str) -> Result<f64> {
+	let bitrate = bitrate.trim();
+	let (number, factor) = match bitrate.chars().last() {
+		Some('k' | 'K') => (&bitrate[..bitrate.len() - 1], 1_000.0),
+		Some('m' | 'M') => (&bitrate[..bitrate.len() - 1], 1_000_000.0),
+		Some('g' | 'G') => (&bitrate[..bitrate.len() - 1], 1_000_000_000.0),
+		_ => (bitrate, 1.0),
+	};
+
+	number
+		.parse::<f64>()
+		.map(|n| n * factor)
+		.map_err(|_| anyhow::anyhow!("\"{bitrate}\" is not a valid bitrate"))
+}
+
+fn format_kbps(bitrate: f64) -> String {
+	format!("{:.0} kb/s", bitrate / 1000.0)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn stream_report(packets: &[PacketInfo], stream: &Stream, window: f64) -> StreamReport {
+	let per_second = bitrate_per_second(packets, stream.index);
+	let average_bitrate = if per_second.is_empty() {
+		0.0
+	} else {
+		per_second.iter().sum::<u64>() as f64 / per_second.len() as f64
+	};
+
+	StreamReport {
+		index: stream.index,
+		codec_name: stream.codec_name.clone(),
+		average_bitrate,
+		peak_bitrate: peak_bitrate(packets, stream.index, window),
+		per_second,
+	}
+}
+
+/// Condenses the given values into a single line of block characters, keeping the maximum of each column.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+fn sparkline(values: &[u64]) -> String {
+	let Some(max) = values.iter().max().copied().filter(|m| *m > 0) else {
+		return String::new();
+	};
+
+	let columns = values.len().min(SPARKLINE_WIDTH);
+	(0..columns)
+		.map(|c| {
+			let start = c * values.len() / columns;
+			let end = ((c + 1) * values.len() / columns).max(start + 1);
+			let value = values[start..end].iter().max().copied().unwrap_or_default();
+			let level = (value as f64 / max as f64 * (SPARKLINE_CHARS.len() - 1) as f64).round() as usize;
+			SPARKLINE_CHARS[level]
+		})
+		.collect()
+}
+
+/// Renders a stacked video/audio bitrate bar chart as RGB pixels.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+fn render_chart(video: &[u64], audio: &[u64], maxrate: Option<f64>) -> Vec<u8> {
+	let mut pixels = CHART_BACKGROUND.repeat(CHART_WIDTH * CHART_HEIGHT);
+	let seconds = video.len().max(audio.len());
+	if seconds == 0 {
+		return pixels;
+	}
+
+	let get = |values: &[u64], i: usize| values.get(i).copied().unwrap_or_default() as f64;
+	let max_total = (0..seconds)
+		.map(|i| get(video, i) + get(audio, i))
+		.fold(0.0, f64::max);
+	let scale = max_total.max(maxrate.unwrap_or_default()) * 1.1;
+	if scale <= 0.0 {
+		return pixels;
+	}
+
+	let to_height = |bitrate: f64| ((bitrate / scale) * CHART_HEIGHT as f64).round() as usize;
+	let mut set_pixel = |x: usize, y: usize, color: [u8; 3]| {
+		let offset = ((CHART_HEIGHT - 1 - y) * CHART_WIDTH + x) * 3;
+		pixels[offset..offset + 3].copy_from_slice(&color);
+	};
+
+	for x in 0..CHART_WIDTH {
+		let start = x * seconds / CHART_WIDTH;
+		let end = ((x + 1) * seconds / CHART_WIDTH).max(start + 1);
+		let Some(second) =
+			(start..end).max_by(|a, b| (get(video, *a) + get(audio, *a)).total_cmp(&(get(video, *b) + get(audio, *b))))
+		else {
+			continue;
+		};
+
+		let video_height = to_height(get(video, second)).min(CHART_HEIGHT);
+		let total_height = to_height(get(video, second) + get(audio, second)).min(CHART_HEIGHT);
+		for y in 0..total_height {
+			set_pixel(x, y, if y < video_height { CHART_VIDEO } else { CHART_AUDIO });
+		}
+
+		if let Some(maxrate) = maxrate {
+			let limit_y = to_height(maxrate).min(CHART_HEIGHT - 1);
+			set_pixel(x, limit_y, CHART_LIMIT);
+		}
+	}
+
+	pixels
+}
+
+fn write_png(output: &Path, pixels: &[u8], debug: bool) -> Result<()> {
+	let ppm_file = Builder::new()
+		.prefix("ffauto")
+		.suffix(".ppm")
+		.tempfile()
+		.context("Couldn't create temp file")?;
+	let mut ppm = format!("P6\n{CHART_WIDTH} {CHART_HEIGHT}\n255\n").into_bytes();
+	ppm.extend_from_slice(pixels);
+	write_file(ppm_file.path(), &ppm)?;
+
+	let mut png = Output::new(output);
+	png.codec("v", "png").format("image2").option("update", 1);
+
+	let mut command = ffmpeg_command("error");
+	command.input(Input::new(ppm_file.path())).output(png);
+
+	Ok(ffmpeg(&command, None, debug)?)
+}
+
+fn write_csv(output: &Path, video: &[u64], audio: &[u64]) -> Result<()> {
+	let mut csv = String::from("second,video_bps,audio_bps,total_bps\n");
+	for second in 0..video.len().max(audio.len()) {
+		let v = video.get(second).copied().unwrap_or_default();
+		let a = audio.get(second).copied().unwrap_or_default();
+		let _ = writeln!(csv, "{second},{v},{a},{}", v + a);
+	}
+
+	fs::write(output, csv).context("Couldn't write CSV file")
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn ffmpeg_bitrate(args: &BitrateArgs, debug: bool) -> Result<()> {
+	let probe = ffprobe_output(&args.input)?;
+
+	let video_stream = probe.get_video_stream(args.video_stream);
+	let audio_stream = probe.get_audio_stream(args.audio_stream);
+	if video_stream.is_none() && audio_stream.is_none() {
+		anyhow::bail!("The input file contains no usable audio/video streams")
+	}
+
+	let (maxrate, bufsize) = match (&args.optimize_target, &args.maxrate) {
+		(Some(target), _) => {
+			let (maxrate, bufsize) = target.rate_limits();
+			(Some(parse_bitrate(maxrate)?), Some(parse_bitrate(bufsize)?))
+		}
+		(None, Some(maxrate)) => (
+			Some(parse_bitrate(maxrate)?),
+			args.bufsize.as_deref().map(parse_bitrate).transpose()?,
+		),
+		(None, None) => (None, None),
+	};
+
+	let window = match (args.window, maxrate, bufsize) {
+		(Some(window), _, _) if window > 0.0 => window,
+		(Some(window), _, _) => anyhow::bail!("The window length needs to be positive, got {window}"),
+		(None, Some(maxrate), Some(bufsize)) => bufsize / maxrate,
+		_ => 1.0,
+	};
+
+	let packets = ffprobe_packets(&args.input, None, None)?;
+
+	let video = video_stream.map(|s| stream_report(&packets, s, window));
+	let audio = audio_stream.map(|s| stream_report(&packets, s, window));
+
+	for (report, stream_type) in [(&video, StreamType::Video), (&audio, StreamType::Audio)] {
+		let Some(report) = report else {
+			continue;
+		};
+
+		outln!(
+			"{} (stream {}, {}): avg {}, peak {} over {window:.3}s",
+			stream_type.to_string().color(stream_type.color()),
+			report.index,
+			report.codec_name.as_deref().unwrap_or("unknown"),
+			format_kbps(report.average_bitrate),
+			format_kbps(report.peak_bitrate),
+		);
+		outln!("  {}", sparkline(&report.per_second));
+	}
+
+	let keyframe_stats = video_stream.and_then(|s| {
+		let intervals = keyframe_intervals(&packets, s.index);
+		if intervals.is_empty() {
+			return None;
+		}
+
+		Some(IntervalStats {
+			count: intervals.len() + 1,
+			min: intervals.iter().copied().fold(f64::INFINITY, f64::min),
+			avg: intervals.iter().sum::<f64>() / intervals.len() as f64,
+			max: intervals.iter().copied().fold(0.0, f64::max),
+		})
+	});
+	if let Some(stats) = &keyframe_stats {
+		outln!(
+			"Keyframe interval: min {:.3}s, avg {:.3}s, max {:.3}s ({} keyframes)",
+			stats.min,
+			stats.avg,
+			stats.max,
+			stats.count
+		);
+	}
+
+	let frame_types = if args.frame_types && video_stream.is_some() {
+		let frames = ffprobe_frames_detail(&args.input, &format!("V:{}", args.video_stream), None)?;
+		let ratio = |t: PictType| {
+			frames.iter().filter(|f| f.pict_type == t).count() as f64 / frames.len().max(1) as f64 * 100.0
+		};
+		let ratios = FrameTypeRatios {
+			i: ratio(PictType::I),
+			p: ratio(PictType::P),
+			b: ratio(PictType::B),
+		};
+		outln!(
+			"Frame types: I {:.1}%, P {:.1}%, B {:.1}%",
+			ratios.i,
+			ratios.p,
+			ratios.b
+		);
+		Some(ratios)
+	} else {
+		None
+	};
+
+	if let (Some(maxrate), Some(video)) = (maxrate, &video) {
+		if video.peak_bitrate > maxrate {
+			outln!(
+				"{}",
+				format!(
+					"Peak video bitrate exceeds the maxrate of {} by {:.1}%",
+					format_kbps(maxrate),
+					(video.peak_bitrate / maxrate - 1.0) * 100.0
+				)
+				.red()
+			);
+		} else {
+			outln!(
+				"{}",
+				format!(
+					"Peak video bitrate stays within the maxrate of {}",
+					format_kbps(maxrate)
+				)
+				.green()
+			);
+		}
+	}
+
+	let empty: Vec<u64> = vec![];
+	let video_per_second = video.as_ref().map_or(&empty, |v| &v.per_second);
+	let audio_per_second = audio.as_ref().map_or(&empty, |a| &a.per_second);
+
+	if let Some(csv) = &args.csv {
+		write_csv(csv, video_per_second, audio_per_second)?;
+	}
+
+	if let Some(png) = &args.png {
+		write_png(png, &render_chart(video_per_second, audio_per_second, maxrate), debug)?;
+	}
+
+	if let Some(json_file) = &args.json_file {
+		let report = BitrateReport {
+			window,
+			maxrate,
+			video,
+			audio,
+			keyframe_intervals: keyframe_stats,
+			frame_types,
+		};
+		fs::write(json_file, serde_json::to_string_pretty(&report)?).context("Couldn't write JSON file")?;
+	}
+
+	Ok(())
+}
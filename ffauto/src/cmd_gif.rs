@@ -1,11 +1,13 @@
 use std::time::Duration;
 
 use anyhow::Result;
+use ffmpeg::ffmpeg::command::{Input, Output};
 use ffmpeg::ffmpeg::ffmpeg::ffmpeg;
+use ffmpeg::ffmpeg::filtergraph::{Chain, Label};
+use ffmpeg::ffmpeg::progress::ProgressTotal;
 
 use crate::commands::GIFArgs;
 use crate::common::*;
-use crate::vec_push_ext::PushStrExt;
 
 pub(crate) fn ffmpeg_gif(args: &GIFArgs, debug: bool) -> Result<()> {
 	let probe = ffprobe_output(&args.input)?;
@@ -15,60 +17,34 @@ pub(crate) fn ffmpeg_gif(args: &GIFArgs, debug: bool) -> Result<()> {
 
 	let video_duration = probe.duration()?;
 
-	let mut ffmpeg_args: Vec<String> = vec![
-		"-hide_banner".to_string(),
-		"-loglevel".to_string(),
-		"error".to_string(),
-		"-y".to_string(),
-	];
+	let mut command = ffmpeg_command("error");
+	let mut input = Input::new(&args.input);
+	let mut output = Output::new(&args.output);
 
-	let seek = args.parse_seek();
-	let duration = args.parse_duration();
+	let seek = args.parse_seek(&probe, video_stream.frame_rate())?;
+	let duration = args.parse_duration(&probe, video_stream.frame_rate())?;
 
 	if let Some(seek) = seek {
-		ffmpeg_args.add_two("-ss", format!("{}", seek.as_secs_f64()));
+		input.seek(seek);
 	}
 
-	// input option to limit the amount of data read
+	// limiting the input is enough, nothing past it gets decoded or written
 	if let Some(duration) = duration {
-		ffmpeg_args.add_two("-t", format!("{}", duration.as_secs_f64()));
+		input.duration(duration);
 	}
 
-	let input = args.input.as_os_str().to_str().unwrap();
-	ffmpeg_args.add_two("-i", input);
-
-	// repeat as output option to limit the amount of data written
-	if let Some(duration) = duration {
-		ffmpeg_args.add_two("-t", format!("{}", duration.as_secs_f64()));
-	}
-
-	ffmpeg_args.add("-an");
-	ffmpeg_args.add("-dn");
-	ffmpeg_args.add("-sn");
+	output.flag("an").flag("dn").flag("sn");
 
 	// region Video Filtering
 
-	let mut video_filter: Vec<String> = vec![];
-
-	if let Some(fps_filter) = args.generate_fps_filter(video_stream.frame_rate()) {
-		video_filter.push(fps_filter);
-	}
-
-	if let Some(crop_filter) = args.generate_crop_filter() {
-		video_filter.push(crop_filter);
-	}
-
-	if let Some(scale_filter) = args.generate_scale_filter() {
-		video_filter.push(scale_filter);
-	}
+	let mut video_filter = Chain::new().input(Label::new(video_stream_id));
 
-	if video_stream.is_hdr() {
-		video_filter.push(TONEMAP_FILTER.parse()?);
-	}
+	video_filter.extend(args.generate_fps_filter(video_stream.frame_rate()));
 
-	if let Some(color_filters) = args.generate_color_filters() {
-		video_filter.push(color_filters);
-	}
+	video_filter.extend(args.generate_crop_filter(&probe, &video_stream)?);
+	video_filter.extend(args.generate_scale_filters()?);
+	video_filter.extend(args.generate_tonemap_filters(&video_stream, &mut command)?);
+	video_filter.extend(args.generate_color_filters());
 
 	let (mut fade_in, mut fade_out) = (args.fade_in, args.fade_out);
 	if args.fade > 0.0 {
@@ -84,28 +60,23 @@ pub(crate) fn ffmpeg_gif(args: &GIFArgs, debug: bool) -> Result<()> {
 	};
 
 	if fade_in > 0.0 {
-		video_filter.push(format!("fade=t=in:st=0:d={fade_in:.3}"));
+		video_filter.push(fade_filter("fade", "in", 0.0, fade_in));
 	}
 	if fade_out > 0.0 {
-		video_filter.push(format!("fade=t=out:st={fade_out_start:.3}:d={fade_out:.3}"));
+		video_filter.push(fade_filter("fade", "out", fade_out_start, fade_out));
 	}
 
-	let video_filter_str = video_filter.join(",");
-	let palette_filters = args.generate_palette_filters()?;
-	ffmpeg_args.add_two(
-		"-filter_complex",
-		format!("[{video_stream_id}]{video_filter_str}{palette_filters}"),
-	);
+	command.filter_complex(args.generate_palette_graph(video_filter)?);
 
 	// endregion
 
 	if args.dedup {
-		ffmpeg_args.add_two("-fps_mode", "vfr");
+		output.option("fps_mode", "vfr");
 	}
-	ffmpeg_args.add_two("-f", "gif");
-	ffmpeg_args.add_two("-loop", "0");
+	output.format("gif").option("loop", 0);
 
-	ffmpeg_args.push(args.output.to_str().unwrap().to_string());
+	command.input(input).output(output);
 
-	ffmpeg(&ffmpeg_args, None, false, debug)
+	let output_duration = duration.unwrap_or_else(|| video_duration.saturating_sub(seek.unwrap_or_default()));
+	Ok(ffmpeg(&command, Some(ProgressTotal::Duration(output_duration)), debug)?)
 }
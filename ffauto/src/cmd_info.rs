@@ -1,18 +1,121 @@
 use crate::commands::InfoArgs;
 use crate::common::ffprobe_output;
-use crate::vec_push_ext::PushStrExt;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::Colorize;
-use ffmpeg::ffmpeg::ffprobe_struct::StreamType;
+use ffmpeg::ffmpeg::ffprobe::{ffprobe_frames_detail, ffprobe_packets};
+use ffmpeg::ffmpeg::ffprobe_struct::{FrameInfo, PacketInfo, PictType, Stream, StreamType};
+use ffmpeg::ffmpeg::report::warn;
+use ffmpeg::ffmpeg::timestamps::{TimestampFormat, format_ffmpeg_timestamp};
+use ffmpeg::{out, outln};
+use std::path::Path;
+use std::time::Duration;
+
+/// Only this many frame types of the first GOP are printed before the pattern is cut off.
+const MAX_GOP_PATTERN_LEN: usize = 64;
+
+fn print_keyframes(input: &Path, format: &TimestampFormat) -> Result<()> {
+	let packets = ffprobe_packets(input, Some("V:0"), None)?;
+	let keyframe_times = packets
+		.iter()
+		.filter(|p| p.key)
+		.filter_map(PacketInfo::time)
+		.collect::<Vec<f64>>();
+
+	outln!("Keyframes: {}", keyframe_times.len());
+
+	let mut last_time: Option<f64> = None;
+	for time in keyframe_times {
+		let timestamp = format_ffmpeg_timestamp(Duration::from_secs_f64(time.max(0.0)), format);
+		match last_time {
+			Some(last_time) => outln!("  {timestamp} (+{:.3}s)", time - last_time),
+			None => outln!("  {timestamp}"),
+		}
+		last_time = Some(time);
+	}
+
+	Ok(())
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn print_gop_structure(input: &Path) -> Result<()> {
+	let frames = ffprobe_frames_detail(input, "V:0", None)?;
+	if frames.is_empty() {
+		anyhow::bail!("ffprobe returned no frames for the first video stream")
+	}
+
+	// every keyframe starts a new GOP. frames before the first keyframe are counted as their own GOP
+	let mut gops: Vec<&[FrameInfo]> = vec![];
+	let mut gop_start = 0;
+	for (i, frame) in frames.iter().enumerate().skip(1) {
+		if frame.key_frame {
+			gops.push(&frames[gop_start..i]);
+			gop_start = i;
+		}
+	}
+	gops.push(&frames[gop_start..]);
+
+	let lengths = gops.iter().map(|g| g.len()).collect::<Vec<usize>>();
+	let min_len = lengths.iter().min().copied().unwrap_or_default();
+	let max_len = lengths.iter().max().copied().unwrap_or_default();
+	let avg_len = frames.len() as f64 / gops.len() as f64;
+
+	outln!("GOPs: {} ({} frames)", gops.len(), frames.len());
+	out!("  Length: min {min_len}, avg {avg_len:.1}, max {max_len} frames");
+
+	let gop_durations = gops
+		.iter()
+		.filter_map(|g| Some(g.last()?.pts_time? - g.first()?.pts_time?))
+		.collect::<Vec<f64>>();
+	if gop_durations.is_empty() {
+		outln!();
+	} else {
+		let avg_duration = gop_durations.iter().sum::<f64>() / gop_durations.len() as f64;
+		outln!(" (avg {avg_duration:.3}s)");
+	}
+
+	let type_ratios = [PictType::I, PictType::P, PictType::B]
+		.iter()
+		.map(|t| {
+			let count = frames.iter().filter(|f| f.pict_type == *t).count();
+			format!("{t} {:.1}%", count as f64 / frames.len() as f64 * 100.0)
+		})
+		.collect::<Vec<String>>();
+	outln!("  Frame types: {}", type_ratios.join(", "));
+
+	let pattern = gops[0]
+		.iter()
+		.take(MAX_GOP_PATTERN_LEN)
+		.map(|f| f.pict_type.to_string())
+		.collect::<String>();
+	if gops[0].len() > MAX_GOP_PATTERN_LEN {
+		outln!("  First GOP: {pattern}…");
+	} else {
+		outln!("  First GOP: {pattern}");
+	}
+
+	let interlaced = frames.iter().filter(|f| f.interlaced_frame).count();
+	if interlaced > 0 {
+		let tff = frames
+			.iter()
+			.filter(|f| f.interlaced_frame && f.top_field_first)
+			.count();
+		outln!(
+			"  Interlaced: {interlaced} frames ({tff} top field first, {} bottom field first)",
+			interlaced - tff
+		);
+	}
+
+	Ok(())
+}
 
 pub(crate) fn ffmpeg_info(args: &InfoArgs) -> Result<()> {
 	let probe = ffprobe_output(&args.input)?;
 
 	if probe.get_first_video_stream().is_none() {
-		eprintln!("NOTE: The input file has no video streams!");
+		warn("NOTE: The input file has no video streams!");
 	}
 	if probe.get_first_audio_stream().is_none() {
-		eprintln!("NOTE: The input file has no audio streams!");
+		warn("NOTE: The input file has no audio streams!");
 	}
 
 	let mut stream_type_index = 0;
@@ -33,7 +136,7 @@ pub(crate) fn ffmpeg_info(args: &InfoArgs) -> Result<()> {
 		let default = stream.disposition.as_ref().is_some_and(|d| d.default);
 
 		let type_color = codec_type.color();
-		print!(
+		out!(
 			"[{}|{}] {}",
 			index,
 			stream_type_index,
@@ -42,19 +145,19 @@ pub(crate) fn ffmpeg_info(args: &InfoArgs) -> Result<()> {
 
 		let mut extra_info: Vec<String> = Vec::new();
 		if let Some(language) = language {
-			extra_info.add(language);
+			extra_info.push(language.clone());
 		}
 		if let Some(title) = title {
-			extra_info.add(format!("\"{title}\""));
+			extra_info.push(format!("\"{title}\""));
 		}
 		if default {
-			extra_info.add("default");
+			extra_info.push("default".to_string());
 		}
 		if !extra_info.is_empty() {
-			print!("({})", extra_info.join(", "));
+			out!("({})", extra_info.join(", "));
 		}
 
-		print!(": ");
+		out!(": ");
 
 		match codec_type {
 			StreamType::Video => {
@@ -62,7 +165,7 @@ pub(crate) fn ffmpeg_info(args: &InfoArgs) -> Result<()> {
 				let codec_profile = stream.profile.as_ref().unwrap();
 				let pix_fmt = stream.pix_fmt.as_ref().unwrap();
 
-				print!("{codec_name} ({codec_profile}), {pix_fmt} ");
+				out!("{codec_name} ({codec_profile}), {pix_fmt} ");
 
 				let width = stream.width.unwrap_or(0);
 				let height = stream.height.unwrap_or(0);
@@ -70,47 +173,80 @@ pub(crate) fn ffmpeg_info(args: &InfoArgs) -> Result<()> {
 
 				let mut format_info: Vec<String> = Vec::new();
 				if let Some(field_order) = &stream.field_order {
-					format_info.add(field_order);
+					format_info.push(field_order.clone());
 				}
 				if let Some(color_range) = &stream.color_range {
-					format_info.add(color_range);
+					format_info.push(color_range.clone());
 				}
 
 				let mut color_info: Vec<String> = Vec::new();
 				if let Some(color_space) = &stream.color_space {
-					color_info.add(color_space);
+					color_info.push(color_space.clone());
 				}
 				if let Some(color_primaries) = &stream.color_primaries {
-					color_info.add(color_primaries);
+					color_info.push(color_primaries.clone());
 				}
 				if let Some(color_transfer) = &stream.color_transfer {
-					color_info.add(color_transfer);
+					color_info.push(color_transfer.clone());
 				}
 				if !color_info.is_empty() {
 					// if color space, primaries, and transfer are the same,
 					// only print one of them instead of repeating it three times
 					if color_info.iter().all(|c| c == &color_info[0]) {
-						format_info.add(&color_info[0]);
+						format_info.push(color_info[0].clone());
 					} else {
-						format_info.add(color_info.join("/"));
+						format_info.push(color_info.join("/"));
 					}
 				}
 				if !format_info.is_empty() {
-					print!("({})", format_info.join(", "));
+					out!("({})", format_info.join(", "));
 				}
 
 				let fps = format!("{fps:.3}");
 				let fps = fps.trim_end_matches('0').trim_end_matches('.');
 				if let (Some(sar), Some(dar)) = (&stream.sar, &stream.dar) {
-					print!(", {width}×{height}");
+					out!(", {width}×{height}");
 					if sar == dar {
-						print!(" ({sar})");
+						out!(" ({sar})");
 					} else {
-						print!(" ({sar}/{dar})");
+						out!(" ({sar}/{dar})");
 					}
-					println!(", {fps} fps");
+					outln!(", {fps} fps");
 				} else {
-					println!(", {width}×{height}, {fps} fps");
+					outln!(", {width}×{height}, {fps} fps");
+				}
+
+				if let Some(hdr_format) = stream.hdr_format() {
+					let mut hdr_info: Vec<String> = vec![hdr_format.to_string()];
+					if let Some(dovi) = stream.dovi_configuration() {
+						let mut layers: Vec<String> = Vec::new();
+						if dovi.bl_present_flag {
+							layers.push("BL".to_string());
+						}
+						if dovi.el_present_flag {
+							layers.push("EL".to_string());
+						}
+						if dovi.rpu_present_flag {
+							layers.push("RPU".to_string());
+						}
+						hdr_info.push(format!("level {}, {}", dovi.dv_level, layers.join("+")));
+					}
+					if let Some(mastering) = stream.mastering_display() {
+						match (mastering.min_luminance, mastering.max_luminance) {
+							(Some(min), Some(max)) => {
+								hdr_info.push(format!("mastering display: {min:.4}–{max:.0} cd/m²"));
+							}
+							(None, Some(max)) => hdr_info.push(format!("mastering display: up to {max:.0} cd/m²")),
+							_ => (),
+						}
+					}
+					if let Some(light_level) = stream.content_light_level() {
+						hdr_info.push(format!(
+							"MaxCLL: {} cd/m², MaxFALL: {} cd/m²",
+							light_level.max_content, light_level.max_average
+						));
+					}
+					outln!("  {}", hdr_info.join(", "));
 				}
 			}
 			StreamType::Audio => {
@@ -118,42 +254,41 @@ pub(crate) fn ffmpeg_info(args: &InfoArgs) -> Result<()> {
 				let sample_rate = stream.sample_rate.as_ref().unwrap();
 				let channels = stream.channels.unwrap_or(0);
 
-				print!("{codec_name}");
+				out!("{codec_name}");
 				if let Some(codec_profile) = &stream.profile {
-					print!(" ({codec_profile})");
+					out!(" ({codec_profile})");
 				}
 
 				if let Some(channel_layout) = stream.channel_layout.as_ref() {
-					print!(", {sample_rate} Hz, {channels}ch: {channel_layout}");
+					out!(", {sample_rate} Hz, {channels}ch: {channel_layout}");
 				} else {
-					print!(", {sample_rate} Hz, {channels}ch");
+					out!(", {sample_rate} Hz, {channels}ch");
 				}
 
 				if let Some(sample_fmt) = stream.sample_fmt.as_ref() {
-					print!(", {sample_fmt}");
+					out!(", {sample_fmt}");
 				}
 
 				if let Some(bits_per_sample) = &stream.bits_per_raw_sample {
-					print!(" ({bits_per_sample})");
+					out!(" ({bits_per_sample})");
 				}
 
 				if let Some(bit_rate) = &stream.bit_rate {
 					let bitrate = bit_rate.parse::<f64>().unwrap() / 1000.0;
-					print!(", {bitrate} kb/s");
+					out!(", {bitrate} kb/s");
 				}
 
-				// println!("{codec_name}({codec_profile}), ")
-				println!();
+				outln!();
 			}
 			StreamType::Subtitle => {
 				let codec_name = stream.codec_name.as_ref().unwrap();
-				println!("{codec_name}");
+				outln!("{codec_name}");
 			}
 			StreamType::Data => {
 				if let Some(codec_type_string) = stream.codec_tag_string.as_ref() {
-					print!("{codec_type_string}");
+					out!("{codec_type_string}");
 				} else {
-					print!("data?");
+					out!("data?");
 				}
 
 				if let Some(handler_name) = stream
@@ -161,34 +296,51 @@ pub(crate) fn ffmpeg_info(args: &InfoArgs) -> Result<()> {
 					.as_ref()
 					.and_then(|tags| tags.handler_name.as_ref())
 				{
-					print!(" ({handler_name})");
+					out!(" ({handler_name})");
 				}
 
-				println!();
+				outln!();
 			}
 			StreamType::Attachment => {
 				if let Some(tags) = &stream.tags {
 					if let Some(filename) = &tags.filename
 						&& let Some(mimetype) = &tags.mimetype
 					{
-						print!("\"{filename}\", ({mimetype})");
+						out!("\"{filename}\", ({mimetype})");
 					} else {
-						print!("Unknown");
+						out!("Unknown");
 					}
 				} else {
-					print!("No information");
+					out!("No information");
 				}
 
 				if let Some(disposition) = &stream.disposition
 					&& disposition.any_true()
 				{
-					print!(" ({disposition})");
+					out!(" ({disposition})");
 				}
 
-				println!();
+				outln!();
 			}
 		}
 	}
 
+	if args.keyframes {
+		let format = if args.timecode {
+			let frame_rate = probe
+				.get_first_video_stream()
+				.and_then(Stream::frame_rate)
+				.context("The frame rate of the first video stream is needed for timecodes")?;
+			TimestampFormat::Smpte { frame_rate, drop_frame: true }
+		} else {
+			TimestampFormat::Full
+		};
+		print_keyframes(&args.input, &format)?;
+	}
+
+	if args.gop {
+		print_gop_structure(&args.input)?;
+	}
+
 	Ok(())
 }
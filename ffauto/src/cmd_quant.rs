@@ -1,11 +1,15 @@
+use std::time::Duration;
+
 use anyhow::Result;
 
+use ffmpeg::ffmpeg::command::{Input, Output};
 use ffmpeg::ffmpeg::ffmpeg::ffmpeg;
 use ffmpeg::ffmpeg::ffprobe::ffprobe;
+use ffmpeg::ffmpeg::filtergraph::{Chain, Filter, Label};
+use ffmpeg::ffmpeg::progress::ProgressTotal;
 
 use crate::commands::QuantArgs;
 use crate::common::*;
-use crate::vec_push_ext::PushStrExt;
 
 pub(crate) fn ffmpeg_quant(args: &QuantArgs, debug: bool) -> Result<()> {
 	let probe = ffprobe(&args.input, false)?;
@@ -13,68 +17,47 @@ pub(crate) fn ffmpeg_quant(args: &QuantArgs, debug: bool) -> Result<()> {
 	let (video_stream, video_stream_id) =
 		probe.checked_get_video_stream_by_index_or_language(&args.video_language, args.video_stream)?;
 
-	let mut ffmpeg_args: Vec<String> = vec![
-		"-hide_banner".to_string(),
-		"-loglevel".to_string(),
-		"error".to_string(),
-		"-y".to_string(),
-	];
+	let mut command = ffmpeg_command("error");
+	let mut input = Input::new(&args.input);
+	let mut output = Output::new(&args.output);
 
-	let seek = args.parse_seek();
+	let seek = args.parse_seek(&probe, video_stream.frame_rate())?;
 	if let Some(seek) = seek {
-		ffmpeg_args.add_two("-ss", format!("{}", seek.as_secs_f64()));
+		input.seek(seek);
 	}
 
-	// add input -t argument to ensure ffmpeg only reads one frame
-	ffmpeg_args.add("-t");
+	// limit the input duration to ensure ffmpeg only reads one frame
 	if let Some(fps) = video_stream.frame_rate() {
 		// if we know the input video's frame rate, we can accurately limit the number of read frames to just one
-		ffmpeg_args.push(format!("{}", 1.0 / fps));
+		input.duration(Duration::from_secs_f64(1.0 / fps));
 	} else {
 		// else we just say "take the first second's worth of frames" and hope for the best
-		ffmpeg_args.add("1");
+		input.duration(Duration::from_secs(1));
 	}
 
-	let input = args.input.as_os_str().to_str().unwrap();
-	ffmpeg_args.add_two("-i", input);
-
-	ffmpeg_args.add("-an");
-	ffmpeg_args.add("-dn");
-	ffmpeg_args.add("-sn");
-	ffmpeg_args.add_two("-frames:v", "1");
-	ffmpeg_args.add_two("-update", "1");
+	output
+		.flag("an")
+		.flag("dn")
+		.flag("sn")
+		.option("frames:v", 1)
+		.option("update", 1);
 
 	// region Video Filtering
 
-	let mut video_filter: Vec<String> = vec![];
-	video_filter.add("select=eq(n\\,0)");
+	let mut video_filter = Chain::new()
+		.input(Label::new(video_stream_id))
+		.filter(Filter::new("select").positional("eq(n,0)"));
 
-	if let Some(crop_filter) = args.generate_crop_filter() {
-		video_filter.push(crop_filter);
-	}
-
-	if let Some(scale_filter) = args.generate_scale_filter() {
-		video_filter.push(scale_filter);
-	}
-
-	if video_stream.is_hdr() {
-		video_filter.push(TONEMAP_FILTER.parse()?);
-	}
-
-	if let Some(color_filters) = args.generate_color_filters() {
-		video_filter.push(color_filters);
-	}
+	video_filter.extend(args.generate_crop_filter(&probe, &video_stream)?);
+	video_filter.extend(args.generate_scale_filters()?);
+	video_filter.extend(args.generate_tonemap_filters(&video_stream, &mut command)?);
+	video_filter.extend(args.generate_color_filters());
 
-	let video_filter_str = video_filter.join(",");
-	let palette_filters = args.generate_palette_filters()?;
-	ffmpeg_args.add_two(
-		"-filter_complex",
-		format!("[{video_stream_id}]{video_filter_str}{palette_filters}"),
-	);
+	command.filter_complex(args.generate_palette_graph(video_filter)?);
 
 	// endregion
 
-	ffmpeg_args.push(args.output.to_str().unwrap().to_string());
+	command.input(input).output(output);
 
-	ffmpeg(&ffmpeg_args, None, false, debug)
+	Ok(ffmpeg(&command, Some(ProgressTotal::Frames(1)), debug)?)
 }
@@ -3,11 +3,15 @@
 use clap::ArgAction;
 use clap::Parser;
 use clap::Subcommand;
+use clap::value_parser;
 use const_format::formatcp;
 use std::path::PathBuf;
 
 use crate::palettes::BuiltInPalette;
-use ffmpeg::ffmpeg::enums::{BarcodeMode, DitherMode, OptimizeTarget, ScaleMode, StatsMode, VideoCodec};
+use ffmpeg::ffmpeg::enums::{
+	AspectMode, BarcodeMode, CropSpec, DitherMode, OptimizeTarget, ScaleMode, StatsMode, TonemapMode, VideoCodec,
+};
+use ffmpeg::ffmpeg::size::{AspectRatio, Resize, Size};
 
 const GIT_HASH: &str = env!("GIT_HASH");
 const GIT_BRANCH: &str = env!("GIT_BRANCH");
@@ -24,6 +28,36 @@ pub(crate) struct Cli {
 
 	#[arg(long, global = true)]
 	pub debug: bool,
+
+	/// The ffmpeg binary to use, ffprobe is looked for in the same directory.
+	/// Takes precedence over the FFAUTO_FFMPEG environment variable.
+	#[arg(long, global = true)]
+	pub ffmpeg: Option<PathBuf>,
+
+	/// Prints the ffmpeg commands, quoted for POSIX sh and PowerShell, instead of running them.
+	#[arg(long, global = true, conflicts_with = "emit_script")]
+	pub dry_run: bool,
+
+	/// Writes the ffmpeg commands to a runnable sh script instead of running them.
+	#[arg(long, global = true, value_name = "FILE")]
+	pub emit_script: Option<PathBuf>,
+
+	/// Kills ffmpeg when it hasn't made any progress for this many seconds, e.g. because of a broken network mount.
+	#[arg(long, global = true, value_name = "SECONDS")]
+	pub stall_timeout: Option<u64>,
+
+	/// Kills ffmpeg when a single run takes longer than this many seconds.
+	#[arg(long, global = true, value_name = "SECONDS")]
+	pub max_runtime: Option<u64>,
+
+	/// Keeps the partially written output file when an encode is cancelled or killed. Files that existed before are always kept.
+	#[arg(long, global = true)]
+	pub keep_partial: bool,
+
+	/// Writes progress, warnings, the output size and errors to stdout as JSON lines, one object per line.
+	/// Everything meant for humans goes to stderr instead.
+	#[arg(long, global = true)]
+	pub json: bool,
 }
 
 #[allow(unreachable_code)]
@@ -53,40 +87,70 @@ pub(crate) struct AutoArgs {
 	#[arg(long, alias = "Ss")]
 	pub sub_streams: Vec<String>,
 
-	/// Burns the first specified subtitle stream into the output video stream. All further specified subtitle streams will be ignored.
+	/// Burns the first specified subtitle stream (or the input's first one) into the output video stream. All further
+	/// specified subtitle streams will be ignored. Works with text subtitles (SRT, ASS, VTT), including external files,
+	/// and with bitmap subtitles (PGS, DVD).
 	#[arg(short = 'B', long, alias = "B")]
 	pub burn_subtitle: bool,
 
-	/// The start time offset.
-	#[arg(short = 's', long)]
+	/// The start time offset. Also accepts unit suffixes (`1h30m`, `500ms`), timecodes (`HH:MM:SS:FF`, or `HH:MM:SS;FF`
+	/// for drop-frame), frame numbers (`f1234`), offsets from the end (`-10`) and percentages (`50%`).
+	#[arg(short = 's', long, allow_hyphen_values = true)]
 	pub seek: Option<String>,
 
-	/// The output duration.
-	#[arg(short = 't', group = "seeking")]
+	/// The output duration. A negative value stops that long before the end.
+	#[arg(short = 't', group = "seeking", allow_hyphen_values = true)]
 	pub duration: Option<String>,
-	#[arg(long = "to", group = "seeking")]
+	/// The end time offset. Negative values count from the end.
+	#[arg(long = "to", group = "seeking", allow_hyphen_values = true)]
 	pub duration_to: Option<String>,
 
-	/// Crops the output video. Format H, WxH, or WxH,X;Y. (applied before scaling)
+	/// Crops the output video. Format H, WxH or WxH,X;Y in pixels or percent, an aspect ratio like 16:9, edge trims
+	/// like t=10,b=10,l=4,r=4, or auto to detect black bars. Sizes and ratios take an anchor like ,top-left
+	/// (default center). (applied before scaling)
 	#[arg(short, long)]
-	pub crop: Option<String>,
+	pub crop: Option<CropSpec>,
 
 	/// Sets the output video width, preserving aspect ratio.
-	#[arg(long = "vw", group = "resize")]
+	#[arg(long = "vw", group = "resize", value_parser = value_parser!(u64).range(1..))]
 	pub width: Option<u64>,
 	/// Sets the output video height, preserving aspect ratio.
-	#[arg(long = "vh", group = "resize")]
+	#[arg(long = "vh", group = "resize", value_parser = value_parser!(u64).range(1..))]
 	pub height: Option<u64>,
-	/// Sets the rectangle the output video size must fit into. Format WxH or an ffmpeg size name.
+	/// Resizes the output video. WxH or an ffmpeg size name fits into that rectangle, WxH! stretches to it
+	/// and WxH^ fills it and crops the rest. 1280x, x720 and 50% keep the aspect ratio.
 	#[arg(long = "vs", group = "resize")]
-	pub size: Option<String>,
+	pub size: Option<Resize>,
+	/// Crops or pads the output video to a display aspect ratio like 1:1 or 9:16. (applied before resizing)
+	#[arg(long)]
+	pub aspect: Option<AspectRatio>,
+	/// Sets whether --aspect crops the video or pads it with black bars.
+	#[arg(long, value_enum, default_value_t = AspectMode::default(), requires = "aspect")]
+	pub aspect_mode: AspectMode,
 	/// Sets the scaling algorithm used.
 	#[arg(short = 'S', long, value_enum, default_value_t = ScaleMode::default())]
 	pub scale_mode: ScaleMode,
 
+	/// Stream-copies video, audio, and subtitles instead of re-encoding them. Cut points are moved to the nearest keyframes.
+	#[arg(long, conflicts_with_all = [
+		"burn_subtitle", "crop", "width", "height", "size", "aspect", "tonemap", "tonemap_mode", "tonemap_peak", "audio_volume", "audio_channels",
+		"fade", "fade_in", "fade_out", "framerate", "framerate_mult", "video_codec", "optimize_target", "garbage",
+	])]
+	pub copy: bool,
+	/// Used with --copy. Keeps the exact cut points by only re-encoding the partial GOPs at the start and end, with the
+	/// input's codec, profile and level.
+	#[arg(long, requires = "copy")]
+	pub smart_cut: bool,
+
 	/// Performs an HDR-to-SDR tonemap.
 	#[arg(short = 'T', long)]
 	pub tonemap: bool,
+	/// Sets the operator used when tonemapping HDR input.
+	#[arg(long, value_enum, default_value_t = TonemapMode::default())]
+	pub tonemap_mode: TonemapMode,
+	/// Overrides the HDR input's peak luminance in nits, which is otherwise read from its metadata.
+	#[arg(long)]
+	pub tonemap_peak: Option<f64>,
 	/// Moves moov atom to the start. (Enabled by default, use -F=false to disable)
 	#[arg(short = 'F', long, default_value_t = true)]
 	pub faststart: bool,
@@ -158,12 +222,14 @@ pub(crate) fn needs_video_filter(&self) -> bool {
 		self.width.is_some()
 			|| self.height.is_some()
 			|| self.size.is_some()
+			|| self.aspect.is_some()
 			|| self.fade > 0.0
 			|| self.fade_in > 0.0
 			|| self.fade_out > 0.0
 			|| self.crop.is_some()
 			|| self.framerate.is_some()
 			|| self.tonemap
+			|| self.burn_subtitle
 	}
 
 	pub(crate) fn optimize_settings(&mut self) {
@@ -180,18 +246,18 @@ pub(crate) fn optimize_settings(&mut self) {
 		match self.optimize_target {
 			None => (),
 			Some(OptimizeTarget::Ipod5) => {
-				self.size = Some("320x240".to_string());
+				self.size = Some(Resize::Fit(Size::new(320, 240)));
 			}
 			Some(OptimizeTarget::Ipod) => {
-				self.size = Some("640x480".to_string());
+				self.size = Some(Resize::Fit(Size::new(640, 480)));
 			}
 			Some(OptimizeTarget::Psp) => {
 				// as of firmware 3.30, allegedly supports MPEG-4 AVC Main Profile 720x480, 352x480 and 480x272
 				// extra info: also supports 160x120 JPEG thumbnails with a .THM extension, next to the video files
-				self.size = Some("480x272".to_string());
+				self.size = Some(Resize::Fit(Size::new(480, 272)));
 			}
 			Some(OptimizeTarget::PsVita) => {
-				self.size = Some("960x540".to_string());
+				self.size = Some(Resize::Fit(Size::new(960, 540)));
 			}
 		}
 	}
@@ -225,8 +291,15 @@ pub(crate) struct BarcodeArgs {
 	pub deep_color: bool,
 
 	/// Sets the output barcode image's height.
-	#[arg(long = "vh", group = "resize")]
+	#[arg(long = "vh", group = "resize", value_parser = value_parser!(u64).range(1..))]
 	pub height: Option<u64>,
+
+	/// Sets the operator used when tonemapping HDR input.
+	#[arg(long, value_enum, default_value_t = TonemapMode::default())]
+	pub tonemap_mode: TonemapMode,
+	/// Overrides the HDR input's peak luminance in nits, which is otherwise read from its metadata.
+	#[arg(long)]
+	pub tonemap_peak: Option<f64>,
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -245,34 +318,51 @@ pub(crate) struct GIFArgs {
 	#[arg(long = "video-lang", group = "video_select")]
 	pub video_language: Option<String>,
 
-	/// The start time offset.
-	#[arg(short = 's', long)]
+	/// The start time offset. Also accepts unit suffixes (`1h30m`, `500ms`), timecodes (`HH:MM:SS:FF`, or `HH:MM:SS;FF`
+	/// for drop-frame), frame numbers (`f1234`), offsets from the end (`-10`) and percentages (`50%`).
+	#[arg(short = 's', long, allow_hyphen_values = true)]
 	pub seek: Option<String>,
 
-	/// The output duration.
-	#[arg(short = 't', group = "seeking")]
+	/// The output duration. A negative value stops that long before the end.
+	#[arg(short = 't', group = "seeking", allow_hyphen_values = true)]
 	pub duration: Option<String>,
-	/// The end time offset.
-	#[arg(long = "to", group = "seeking")]
+	/// The end time offset. Negative values count from the end.
+	#[arg(long = "to", group = "seeking", allow_hyphen_values = true)]
 	pub duration_to: Option<String>,
 
-	/// Crops the output video. Format H, WxH, or WxH,X;Y. (applied before scaling)
+	/// Crops the output video. Format H, WxH or WxH,X;Y in pixels or percent, an aspect ratio like 16:9, edge trims
+	/// like t=10,b=10,l=4,r=4, or auto to detect black bars. Sizes and ratios take an anchor like ,top-left
+	/// (default center). (applied before scaling)
 	#[arg(short, long)]
-	pub crop: Option<String>,
+	pub crop: Option<CropSpec>,
 
 	/// Sets the output video width, preserving aspect ratio.
-	#[arg(long = "vw", group = "resize")]
+	#[arg(long = "vw", group = "resize", value_parser = value_parser!(u64).range(1..))]
 	pub width: Option<u64>,
 	/// Sets the output video height, preserving aspect ratio.
-	#[arg(long = "vh", group = "resize")]
+	#[arg(long = "vh", group = "resize", value_parser = value_parser!(u64).range(1..))]
 	pub height: Option<u64>,
-	/// Sets the rectangle the output video size must fit into. Format WxH or an ffmpeg size name.
+	/// Resizes the output video. WxH or an ffmpeg size name fits into that rectangle, WxH! stretches to it
+	/// and WxH^ fills it and crops the rest. 1280x, x720 and 50% keep the aspect ratio.
 	#[arg(long = "vs", group = "resize")]
-	pub size: Option<String>,
+	pub size: Option<Resize>,
+	/// Crops or pads the output video to a display aspect ratio like 1:1 or 9:16. (applied before resizing)
+	#[arg(long)]
+	pub aspect: Option<AspectRatio>,
+	/// Sets whether --aspect crops the video or pads it with black bars.
+	#[arg(long, value_enum, default_value_t = AspectMode::default(), requires = "aspect")]
+	pub aspect_mode: AspectMode,
 	/// Sets the scaling algorithm used.
 	#[arg(short = 'S', long, value_enum, default_value_t = ScaleMode::default())]
 	pub scale_mode: ScaleMode,
 
+	/// Sets the operator used when tonemapping HDR input.
+	#[arg(long, value_enum, default_value_t = TonemapMode::default())]
+	pub tonemap_mode: TonemapMode,
+	/// Overrides the HDR input's peak luminance in nits, which is otherwise read from its metadata.
+	#[arg(long)]
+	pub tonemap_peak: Option<f64>,
+
 	/// Sets the fade in and out durations. Takes precedence over --fi/--fo.
 	#[arg(short, long, default_value_t = 0.0)]
 	pub fade: f64,
@@ -348,27 +438,44 @@ pub(crate) struct QuantArgs {
 	#[arg(long = "video-lang", group = "video_select")]
 	pub video_language: Option<String>,
 
-	/// The start time offset.
-	#[arg(short = 's', long)]
+	/// The start time offset. Also accepts unit suffixes (`1h30m`, `500ms`), timecodes (`HH:MM:SS:FF`, or `HH:MM:SS;FF`
+	/// for drop-frame), frame numbers (`f1234`), offsets from the end (`-10`) and percentages (`50%`).
+	#[arg(short = 's', long, allow_hyphen_values = true)]
 	pub seek: Option<String>,
 
-	/// Crops the output video. Format H, WxH, or WxH,X;Y. (applied before scaling)
+	/// Crops the output video. Format H, WxH or WxH,X;Y in pixels or percent, an aspect ratio like 16:9, edge trims
+	/// like t=10,b=10,l=4,r=4, or auto to detect black bars. Sizes and ratios take an anchor like ,top-left
+	/// (default center). (applied before scaling)
 	#[arg(short, long)]
-	pub crop: Option<String>,
+	pub crop: Option<CropSpec>,
 
 	/// Sets the output video width, preserving aspect ratio.
-	#[arg(long = "vw", group = "resize")]
+	#[arg(long = "vw", group = "resize", value_parser = value_parser!(u64).range(1..))]
 	pub width: Option<u64>,
 	/// Sets the output video height, preserving aspect ratio.
-	#[arg(long = "vh", group = "resize")]
+	#[arg(long = "vh", group = "resize", value_parser = value_parser!(u64).range(1..))]
 	pub height: Option<u64>,
-	/// Sets the rectangle the output video size must fit into. Format WxH or an ffmpeg size name.
+	/// Resizes the output video. WxH or an ffmpeg size name fits into that rectangle, WxH! stretches to it
+	/// and WxH^ fills it and crops the rest. 1280x, x720 and 50% keep the aspect ratio.
 	#[arg(long = "vs", group = "resize")]
-	pub size: Option<String>,
+	pub size: Option<Resize>,
+	/// Crops or pads the output video to a display aspect ratio like 1:1 or 9:16. (applied before resizing)
+	#[arg(long)]
+	pub aspect: Option<AspectRatio>,
+	/// Sets whether --aspect crops the video or pads it with black bars.
+	#[arg(long, value_enum, default_value_t = AspectMode::default(), requires = "aspect")]
+	pub aspect_mode: AspectMode,
 	/// Sets the scaling algorithm used.
 	#[arg(short = 'S', long, value_enum, default_value_t = ScaleMode::default())]
 	pub scale_mode: ScaleMode,
 
+	/// Sets the operator used when tonemapping HDR input.
+	#[arg(long, value_enum, default_value_t = TonemapMode::default())]
+	pub tonemap_mode: TonemapMode,
+	/// Overrides the HDR input's peak luminance in nits, which is otherwise read from its metadata.
+	#[arg(long)]
+	pub tonemap_peak: Option<f64>,
+
 	/// Affects the output brightness, range [-1.0;1.0]
 	#[arg(long, allow_negative_numbers = true, default_value_t = 0.0)]
 	pub brightness: f64,
@@ -405,6 +512,57 @@ pub(crate) struct InfoArgs {
 	/// The input file.
 	#[arg(short)]
 	pub input: PathBuf,
+
+	/// Lists the keyframe timestamps of the first video stream.
+	#[arg(short, long)]
+	pub keyframes: bool,
+	/// Lists the keyframes as SMPTE timecodes like editors show them, drop-frame at 29.97 and 59.94 fps.
+	#[arg(long, requires = "keyframes")]
+	pub timecode: bool,
+	/// Decodes the first video stream and summarizes its GOP structure. (slow)
+	#[arg(short, long)]
+	pub gop: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct BitrateArgs {
+	/// The input file.
+	#[arg(short)]
+	pub input: PathBuf,
+
+	/// Selects a video stream by index.
+	#[arg(long, default_value_t = 0)]
+	pub video_stream: usize,
+	/// Selects an audio stream by index.
+	#[arg(long, default_value_t = 0)]
+	pub audio_stream: usize,
+
+	/// The sliding window length in seconds used to determine peak bitrates. Defaults to bufsize/maxrate if both are known, 1 second otherwise.
+	#[arg(short, long)]
+	pub window: Option<f64>,
+	/// The video bitrate limit to check against, e.g. 768K or 2.5M.
+	#[arg(long, group = "limits")]
+	pub maxrate: Option<String>,
+	/// The decoder buffer size that belongs to --maxrate, e.g. 2M.
+	#[arg(long, requires = "maxrate")]
+	pub bufsize: Option<String>,
+	/// Checks against the bitrate limits `ff auto` uses for a device.
+	#[arg(short = 'O', long = "optimize", group = "limits")]
+	pub optimize_target: Option<OptimizeTarget>,
+
+	/// Decodes the video stream to determine I/P/B frame ratios. (slow)
+	#[arg(short = 'F', long)]
+	pub frame_types: bool,
+
+	/// Writes the per-second bitrates to a CSV file.
+	#[arg(long)]
+	pub csv: Option<PathBuf>,
+	/// Writes the full report to a JSON file.
+	#[arg(long)]
+	pub json_file: Option<PathBuf>,
+	/// Renders a bitrate chart to a PNG file.
+	#[arg(long)]
+	pub png: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -423,4 +581,7 @@ pub(crate) enum Commands {
 
 	#[command(about = "Formats and prints ffprobe information")]
 	Info(InfoArgs),
+
+	#[command(about = "Analyzes bitrates and keyframe placement")]
+	Bitrate(BitrateArgs),
 }
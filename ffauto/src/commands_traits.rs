@@ -1,43 +1,57 @@
-use crate::commands::{AutoArgs, GIFArgs, QuantArgs};
+use crate::commands::{AutoArgs, BarcodeArgs, GIFArgs, QuantArgs};
 use crate::common::*;
 use anyhow::Result;
+use ffmpeg::ffmpeg::command::FfmpegCommand;
 use ffmpeg::ffmpeg::enums::StatsMode;
+use ffmpeg::ffmpeg::ffprobe_struct::{FFProbeOutput, Stream};
+use ffmpeg::ffmpeg::filtergraph::{Chain, Filter, Graph};
 use std::time::Duration;
 
 impl CanSeek for AutoArgs {
-	fn parse_seek(&self) -> Option<Duration> {
-		parse_seek(self.seek.as_deref())
+	fn parse_seek(&self, probe: &FFProbeOutput, frame_rate: Option<f64>) -> Result<Option<Duration>> {
+		parse_seek(self.seek.as_deref(), probe, frame_rate)
 	}
 }
 
 impl CanSetDuration for AutoArgs {
-	fn parse_duration(&self) -> Option<Duration> {
+	fn parse_duration(&self, probe: &FFProbeOutput, frame_rate: Option<f64>) -> Result<Option<Duration>> {
 		parse_duration(
 			self.seek.as_deref(),
 			self.duration.as_deref(),
 			self.duration_to.as_deref(),
+			probe,
+			frame_rate,
 		)
 	}
 }
 
 impl CanCrop for AutoArgs {
-	fn generate_crop_filter(&self) -> Option<String> {
-		generate_crop_filter(self.crop.as_deref())
+	fn generate_crop_filter(&self, probe: &FFProbeOutput, stream: &Stream) -> Result<Option<Filter>> {
+		generate_crop_filter(self.crop.as_ref(), &self.input, probe, stream)
 	}
 }
 
 impl CanScale for AutoArgs {
-	fn generate_scale_filter(&self) -> Option<String> {
-		generate_scale_filter(self.width, self.height, self.size.as_deref(), &self.scale_mode)
+	fn generate_scale_filters(&self) -> Result<Vec<Filter>> {
+		generate_scale_filters(
+			self.width,
+			self.height,
+			self.size.as_ref(),
+			self.aspect,
+			&self.aspect_mode,
+			&self.scale_mode,
+			// libx264 and libx265 only take even sizes for 4:2:0 video
+			true,
+		)
 	}
 }
 
 impl CanChangeFPS for AutoArgs {
-	fn generate_fps_filter(&self, stream_fps: Option<f64>) -> Option<String> {
+	fn generate_fps_filter(&self, stream_fps: Option<f64>) -> Option<Filter> {
 		generate_fps_filter(self.framerate, self.framerate_mult, stream_fps)
 	}
 
-	fn generate_fps_filter_explicit(&self, stream_fps: Option<f64>, target: f64) -> Option<String> {
+	fn generate_fps_filter_explicit(&self, stream_fps: Option<f64>, target: f64) -> Option<Filter> {
 		if let Some(fps) = stream_fps {
 			let divisor = (fps / target).ceil();
 			let adj_fps = (fps / divisor).round().min(target);
@@ -48,53 +62,76 @@ fn generate_fps_filter_explicit(&self, stream_fps: Option<f64>, target: f64) ->
 	}
 }
 
+impl CanTonemap for AutoArgs {
+	fn generate_tonemap_filters(&self, stream: &Stream, command: &mut FfmpegCommand) -> Result<Vec<Filter>> {
+		generate_tonemap_filters(stream, &self.tonemap_mode, self.tonemap_peak, command)
+	}
+}
+
 impl CanSeek for GIFArgs {
-	fn parse_seek(&self) -> Option<Duration> {
-		parse_seek(self.seek.as_deref())
+	fn parse_seek(&self, probe: &FFProbeOutput, frame_rate: Option<f64>) -> Result<Option<Duration>> {
+		parse_seek(self.seek.as_deref(), probe, frame_rate)
 	}
 }
 
 impl CanSetDuration for GIFArgs {
-	fn parse_duration(&self) -> Option<Duration> {
+	fn parse_duration(&self, probe: &FFProbeOutput, frame_rate: Option<f64>) -> Result<Option<Duration>> {
 		parse_duration(
 			self.seek.as_deref(),
 			self.duration.as_deref(),
 			self.duration_to.as_deref(),
+			probe,
+			frame_rate,
 		)
 	}
 }
 
 impl CanCrop for GIFArgs {
-	fn generate_crop_filter(&self) -> Option<String> {
-		generate_crop_filter(self.crop.as_deref())
+	fn generate_crop_filter(&self, probe: &FFProbeOutput, stream: &Stream) -> Result<Option<Filter>> {
+		generate_crop_filter(self.crop.as_ref(), &self.input, probe, stream)
 	}
 }
 
 impl CanScale for GIFArgs {
-	fn generate_scale_filter(&self) -> Option<String> {
-		generate_scale_filter(self.width, self.height, self.size.as_deref(), &self.scale_mode)
+	fn generate_scale_filters(&self) -> Result<Vec<Filter>> {
+		generate_scale_filters(
+			self.width,
+			self.height,
+			self.size.as_ref(),
+			self.aspect,
+			&self.aspect_mode,
+			&self.scale_mode,
+			false,
+		)
 	}
 }
 
 impl CanChangeFPS for GIFArgs {
-	fn generate_fps_filter(&self, stream_fps: Option<f64>) -> Option<String> {
+	fn generate_fps_filter(&self, stream_fps: Option<f64>) -> Option<Filter> {
 		generate_fps_filter(self.framerate, self.framerate_mult, stream_fps)
 	}
 
-	fn generate_fps_filter_explicit(&self, _: Option<f64>, _: f64) -> Option<String> {
+	fn generate_fps_filter_explicit(&self, _: Option<f64>, _: f64) -> Option<Filter> {
 		None
 	}
 }
 
+impl CanTonemap for GIFArgs {
+	fn generate_tonemap_filters(&self, stream: &Stream, command: &mut FfmpegCommand) -> Result<Vec<Filter>> {
+		generate_tonemap_filters(stream, &self.tonemap_mode, self.tonemap_peak, command)
+	}
+}
+
 impl CanColorFilter for GIFArgs {
-	fn generate_color_filters(&self) -> Option<String> {
+	fn generate_color_filters(&self) -> Vec<Filter> {
 		generate_color_sharpness_filters(self.brightness, self.contrast, self.saturation, self.sharpness)
 	}
 }
 
 impl CanGeneratePalette for GIFArgs {
-	fn generate_palette_filters(&self) -> Result<String> {
+	fn generate_palette_graph(&self, video: Chain) -> Result<Graph> {
 		generate_palette_filtergraph(
+			video,
 			self.palette_file.as_deref(),
 			self.palette_name.as_ref(),
 			self.num_colors,
@@ -107,32 +144,47 @@ fn generate_palette_filters(&self) -> Result<String> {
 }
 
 impl CanSeek for QuantArgs {
-	fn parse_seek(&self) -> Option<Duration> {
-		parse_seek(self.seek.as_deref())
+	fn parse_seek(&self, probe: &FFProbeOutput, frame_rate: Option<f64>) -> Result<Option<Duration>> {
+		parse_seek(self.seek.as_deref(), probe, frame_rate)
 	}
 }
 
 impl CanCrop for QuantArgs {
-	fn generate_crop_filter(&self) -> Option<String> {
-		generate_crop_filter(self.crop.as_deref())
+	fn generate_crop_filter(&self, probe: &FFProbeOutput, stream: &Stream) -> Result<Option<Filter>> {
+		generate_crop_filter(self.crop.as_ref(), &self.input, probe, stream)
 	}
 }
 
 impl CanScale for QuantArgs {
-	fn generate_scale_filter(&self) -> Option<String> {
-		generate_scale_filter(self.width, self.height, self.size.as_deref(), &self.scale_mode)
+	fn generate_scale_filters(&self) -> Result<Vec<Filter>> {
+		generate_scale_filters(
+			self.width,
+			self.height,
+			self.size.as_ref(),
+			self.aspect,
+			&self.aspect_mode,
+			&self.scale_mode,
+			false,
+		)
+	}
+}
+
+impl CanTonemap for QuantArgs {
+	fn generate_tonemap_filters(&self, stream: &Stream, command: &mut FfmpegCommand) -> Result<Vec<Filter>> {
+		generate_tonemap_filters(stream, &self.tonemap_mode, self.tonemap_peak, command)
 	}
 }
 
 impl CanColorFilter for QuantArgs {
-	fn generate_color_filters(&self) -> Option<String> {
+	fn generate_color_filters(&self) -> Vec<Filter> {
 		generate_color_sharpness_filters(self.brightness, self.contrast, self.saturation, self.sharpness)
 	}
 }
 
 impl CanGeneratePalette for QuantArgs {
-	fn generate_palette_filters(&self) -> Result<String> {
+	fn generate_palette_graph(&self, video: Chain) -> Result<Graph> {
 		generate_palette_filtergraph(
+			video,
 			self.palette_file.as_deref(),
 			self.palette_name.as_ref(),
 			self.num_colors,
@@ -143,3 +195,9 @@ fn generate_palette_filters(&self) -> Result<String> {
 		)
 	}
 }
+
+impl CanTonemap for BarcodeArgs {
+	fn generate_tonemap_filters(&self, stream: &Stream, command: &mut FfmpegCommand) -> Result<Vec<Filter>> {
+		generate_tonemap_filters(stream, &self.tonemap_mode, self.tonemap_peak, command)
+	}
+}
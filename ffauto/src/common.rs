@@ -1,153 +1,423 @@
 use crate::palettes::{BuiltInPalette, get_builtin_palette};
-use anyhow::Result;
-use ffmpeg::ffmpeg::enums::{Crop, DitherMode, ScaleMode, StatsMode};
+use anyhow::{Context, Result};
+use ffmpeg::ffmpeg::capabilities::capabilities;
+use ffmpeg::ffmpeg::command::FfmpegCommand;
+use ffmpeg::ffmpeg::cropdetect::detect_crop;
+use ffmpeg::ffmpeg::enums::{AspectMode, CropSpec, DitherMode, ScaleMode, StatsMode, TonemapMode};
 use ffmpeg::ffmpeg::ffprobe::ffprobe;
-use ffmpeg::ffmpeg::ffprobe_struct::{FFProbeOutput, StreamType};
-use ffmpeg::ffmpeg::size::parse_ffmpeg_size;
-use ffmpeg::ffmpeg::timestamps::parse_ffmpeg_duration;
+use ffmpeg::ffmpeg::ffprobe_struct::{DoviCompatibility, FFProbeOutput, HdrFormat, Stream, StreamType};
+use ffmpeg::ffmpeg::filtergraph::{Chain, Filter, Graph, Label};
+use ffmpeg::ffmpeg::report::warn;
+use ffmpeg::ffmpeg::size::{AspectRatio, Resize};
+use ffmpeg::ffmpeg::timestamps::{Timestamp, TimestampFormat, format_ffmpeg_timestamp, parse_relative_timestamp};
+use ffmpeg::outln;
 use ffmpeg::palettes::palette::{Color, Palette};
-use std::collections::HashMap;
-use std::fmt::Write;
 use std::path::Path;
 use std::time::Duration;
 
 const MAX32: u64 = i32::MAX as u64;
 
-pub(crate) const TONEMAP_FILTER: &str =
-	"zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,tonemap=tonemap=hable:desat=0,zscale=t=bt709:m=bt709";
+/// Linearized HDR input is normalized so that 1.0 equals SDR reference white.
+/// ffmpeg's tonemap filter makes the same assumption, so its peak parameter is relative to this.
+const TONEMAP_REFERENCE_WHITE: f64 = 100.0;
+/// HLG rarely comes with luminance metadata, so assume the BT.2100 reference display.
+const HLG_PEAK_LUMINANCE: f64 = 1000.0;
 pub(crate) const SCALE_FLAGS: &str = "accurate_rnd+full_chroma_int+full_chroma_inp";
 
+/// Timestamps can be timecodes or frame numbers, which are resolved against the frame rate of the selected video stream,
+/// or relative to the end, which is resolved against the probed duration.
 pub trait CanSeek {
-	fn parse_seek(&self) -> Option<Duration>;
+	fn parse_seek(&self, probe: &FFProbeOutput, frame_rate: Option<f64>) -> Result<Option<Duration>>;
 }
 
 pub trait CanSetDuration {
-	fn parse_duration(&self) -> Option<Duration>;
+	fn parse_duration(&self, probe: &FFProbeOutput, frame_rate: Option<f64>) -> Result<Option<Duration>>;
 }
 
 pub trait CanCrop {
-	fn generate_crop_filter(&self) -> Option<String>;
+	/// Fails if the crop doesn't fit into the given video stream's frames.
+	/// Detecting the crop runs ffmpeg over parts of the input, which is why this needs the probe.
+	fn generate_crop_filter(&self, probe: &FFProbeOutput, stream: &Stream) -> Result<Option<Filter>>;
 }
 
 pub trait CanScale {
-	fn generate_scale_filter(&self) -> Option<String>;
+	fn generate_scale_filters(&self) -> Result<Vec<Filter>>;
 }
 
 pub trait CanChangeFPS {
-	fn generate_fps_filter(&self, stream_fps: Option<f64>) -> Option<String>;
-	fn generate_fps_filter_explicit(&self, stream_fps: Option<f64>, target: f64) -> Option<String>;
+	fn generate_fps_filter(&self, stream_fps: Option<f64>) -> Option<Filter>;
+	fn generate_fps_filter_explicit(&self, stream_fps: Option<f64>, target: f64) -> Option<Filter>;
 }
 
 pub trait CanColorFilter {
-	fn generate_color_filters(&self) -> Option<String>;
+	fn generate_color_filters(&self) -> Vec<Filter>;
+}
+
+pub trait CanTonemap {
+	fn generate_tonemap_filters(&self, stream: &Stream, command: &mut FfmpegCommand) -> Result<Vec<Filter>>;
 }
 
 pub trait CanGeneratePalette {
-	fn generate_palette_filters(&self) -> Result<String>;
+	/// Finishes the given video chain with the palette filters, which needs a complete filtergraph.
+	fn generate_palette_graph(&self, video: Chain) -> Result<Graph>;
+}
+
+/// Starts an ffmpeg command with the global options that every subcommand uses.
+pub(crate) fn ffmpeg_command(loglevel: &str) -> FfmpegCommand {
+	let mut command = FfmpegCommand::new();
+	command.hide_banner().loglevel(loglevel).overwrite();
+	command
+}
+
+/// Parses a timestamp option, with an error that lists what's accepted.
+fn parse_timestamp_arg(option: &str, value: &str, frame_rate: Option<f64>) -> Result<Timestamp> {
+	parse_relative_timestamp(value, frame_rate).with_context(|| {
+		format!(
+			"Invalid {option} value \"{value}\". Expected seconds (90, 1.5s, 1h30m, 500ms), a timestamp (00:01:30.5), \
+			a timecode (00:01:30:12 or 00:01:30;12), a frame number (f1234), an offset from the end (-10) or a percentage (50%)"
+		)
+	})
+}
+
+/// Turns a timestamp into an offset from the start, probing the input's duration only if it's needed.
+fn resolve_timestamp(option: &str, value: &str, timestamp: Timestamp, probe: &FFProbeOutput) -> Result<Duration> {
+	if let Timestamp::Start(offset) = timestamp {
+		return Ok(offset);
+	}
+
+	let total = probe.duration()?;
+	timestamp.resolve(total).with_context(|| {
+		format!(
+			"{option} {value} lies outside the input, which is {} long",
+			format_ffmpeg_timestamp(total, &TimestampFormat::Auto)
+		)
+	})
 }
 
 /// Parses the seek string and returns it as a [Duration], if present.
-pub(crate) fn parse_seek(seek: Option<&str>) -> Option<Duration> {
-	if let Some(seek_str) = seek {
-		return parse_ffmpeg_duration(seek_str);
+pub(crate) fn parse_seek(
+	seek: Option<&str>,
+	probe: &FFProbeOutput,
+	frame_rate: Option<f64>,
+) -> Result<Option<Duration>> {
+	let Some(seek) = seek else {
+		return Ok(None);
+	};
+
+	let timestamp = parse_timestamp_arg("--seek", seek, frame_rate)?;
+	let offset = resolve_timestamp("--seek", seek, timestamp, probe)?;
+	if let Ok(total) = probe.duration()
+		&& offset >= total
+	{
+		anyhow::bail!(
+			"--seek {seek} lies past the end of the input, which is {} long",
+			format_ffmpeg_timestamp(total, &TimestampFormat::Auto)
+		)
 	}
 
-	None
+	Ok(Some(offset))
 }
 
 /// Parses the duration strings and returns an appropriate [Duration].
+///
+/// A negative `-t` stops that long before the end, and a percentage is a share of the whole input.
 pub(crate) fn parse_duration(
 	seek: Option<&str>,
 	duration: Option<&str>,
 	duration_to: Option<&str>,
-) -> Option<Duration> {
+	probe: &FFProbeOutput,
+	frame_rate: Option<f64>,
+) -> Result<Option<Duration>> {
+	let start = parse_seek(seek, probe, frame_rate)?.unwrap_or(Duration::ZERO);
+	let until = |option: &str, end: Duration| {
+		let duration = end
+			.checked_sub(start)
+			.filter(|d| !d.is_zero())
+			.with_context(|| format!("{option} has to end after the start of the output"))?;
+		Ok(Some(duration))
+	};
+
 	if let Some(t) = duration {
-		return parse_ffmpeg_duration(t);
+		let timestamp = parse_timestamp_arg("-t", t, frame_rate)?;
+		let duration = resolve_timestamp("-t", t, timestamp, probe)?;
+		if let Timestamp::End(_) = timestamp {
+			return until("-t", duration);
+		}
+		if duration.is_zero() {
+			anyhow::bail!("-t {t} would make the output empty")
+		}
+		return Ok(Some(duration));
 	}
 
-	if let (Some(seek), Some(to)) = (parse_seek(seek), duration_to) {
-		return parse_ffmpeg_duration(to).map(|to| to.saturating_sub(seek));
+	if let Some(to) = duration_to {
+		let timestamp = parse_timestamp_arg("--to", to, frame_rate)?;
+		return until("--to", resolve_timestamp("--to", to, timestamp, probe)?);
 	}
 
-	None
+	Ok(None)
 }
 
-pub(crate) fn palette_to_ffmpeg(pal: &Palette) -> String {
+/// Adds the chains that turn the palette into a 16×16 image to the graph and returns the label of that image.
+pub(crate) fn palette_to_ffmpeg(pal: &Palette, graph: &mut Graph) -> Label {
+	fn color_source(color: Color) -> Chain {
+		Chain::new()
+			.filter(
+				Filter::new("color")
+					.arg("c", color)
+					.arg("r", 1)
+					.arg("s", "1x1"),
+			)
+			.filter(Filter::new("format").positional("rgb24"))
+	}
+
 	let colors = pal.colors.iter().map(|e| e.color).collect::<Vec<Color>>();
 
-	let mut color_sources: Vec<String> = Vec::new();
-	for (color_idx, color) in colors.iter().enumerate() {
-		let source = format!("color=c={color}:r=1:s=1x1,format=rgb24[p{}]", color_idx + 1);
-		color_sources.push(source);
+	let mut all_sources = Vec::new();
+	for color in &colors {
+		let label = graph.label("p");
+		graph.push(color_source(*color).output(label.clone()));
+		all_sources.push(label);
 	}
 
-	let mut all_sources = (0..color_sources.len()).fold(String::new(), |mut output, i| {
-		let _ = write!(output, "[p{}]", i + 1);
-		output
-	});
-
-	if color_sources.len() < 256 {
-		let num_dummies = 256 - color_sources.len();
-		let all_dummies = (0..num_dummies).fold(String::new(), |mut output, i| {
-			let _ = write!(output, "[d{}]", i + 1);
-			output
-		});
-		let dummy_color = colors.last().unwrap().to_string();
-		let source = format!("color=c={dummy_color}:r=1:s=1x1,format=rgb24,split={num_dummies} {all_dummies}");
-		color_sources.push(source);
-
-		all_sources += &*all_dummies;
+	if colors.len() < 256 {
+		let num_dummies = 256 - colors.len();
+		let all_dummies = (0..num_dummies)
+			.map(|_| graph.label("d"))
+			.collect::<Vec<Label>>();
+		let dummy_color = *colors.last().unwrap();
+		graph.push(
+			color_source(dummy_color)
+				.filter(Filter::new("split").positional(num_dummies))
+				.outputs(all_dummies.iter().cloned()),
+		);
+
+		all_sources.extend(all_dummies);
 	}
 
-	color_sources.push(format!("{all_sources}xstack=grid=16x16"));
-	color_sources.join(";")
+	let palette = graph.label("pal");
+	graph.push(
+		Chain::new()
+			.inputs(all_sources)
+			.filter(Filter::new("xstack").arg("grid", "16x16"))
+			.output(palette.clone()),
+	);
+
+	palette
 }
 
-pub(crate) fn generate_crop_filter(crop: Option<&str>) -> Option<String> {
-	if let Some(crop_str) = crop {
-		return Crop::new(crop_str).map(|c| format!("crop={c}")).ok();
-	}
+pub(crate) fn generate_crop_filter(
+	crop: Option<&CropSpec>,
+	input: &Path,
+	probe: &FFProbeOutput,
+	stream: &Stream,
+) -> Result<Option<Filter>> {
+	let crop = match crop {
+		None => return Ok(None),
+		Some(CropSpec::Region(region)) => region.resolve(stream)?,
+		Some(CropSpec::Auto) => {
+			let Some(crop) = detect_crop(input, stream, probe.duration()?)? else {
+				outln!("Detected crop: none, the video has no black bars");
+				return Ok(None);
+			};
+			outln!(
+				"Detected crop: {}x{},{};{}",
+				crop.width,
+				crop.height,
+				crop.x.unwrap_or_default(),
+				crop.y.unwrap_or_default()
+			);
+			crop
+		}
+	};
 
-	None
+	Ok(Some(crop.filter()))
+}
+
+/// Crops or pads the video to the display aspect ratio, keeping it centered. The sample aspect ratio
+/// is taken into account, so anamorphic video ends up with the right shape too.
+fn aspect_filter(aspect: AspectRatio, mode: &AspectMode) -> Filter {
+	let AspectRatio { width: w, height: h } = aspect;
+	let (name, extreme) = match mode {
+		AspectMode::Crop => ("crop", "min"),
+		AspectMode::Pad => ("pad", "max"),
+	};
+
+	let filter = Filter::new(name)
+		.arg("w", format!("trunc({extreme}(iw,ih*{w}/{h}/sar)/2)*2"))
+		.arg("h", format!("trunc({extreme}(ih,iw*sar*{h}/{w})/2)*2"));
+	match mode {
+		// crop centers by default, pad doesn't
+		AspectMode::Crop => filter,
+		AspectMode::Pad => filter.arg("x", "(ow-iw)/2").arg("y", "(oh-ih)/2"),
+	}
 }
 
-pub(crate) fn generate_scale_filter(
+/// Generates the filters for `--aspect` and the size options. With `even_size`, exact and filled sizes have to be
+/// even in both dimensions, which encoding to 4:2:0 needs.
+pub(crate) fn generate_scale_filters(
 	width: Option<u64>,
 	height: Option<u64>,
-	size: Option<&str>,
+	size: Option<&Resize>,
+	aspect: Option<AspectRatio>,
+	aspect_mode: &AspectMode,
 	scale_mode: &ScaleMode,
-) -> Option<String> {
-	if let Some(width) = width {
-		return Some(format!("scale=w={width}:h=-2:flags={scale_mode}+{SCALE_FLAGS}"));
-	} else if let Some(height) = height {
-		return Some(format!("scale=w=-2:h={height}:flags={scale_mode}+{SCALE_FLAGS}"));
-	} else if let Some(size) = size {
-		return match parse_ffmpeg_size(size) {
-			Ok(size) => Some(format!(
-				"scale=w={}:h={}:force_original_aspect_ratio=decrease:force_divisible_by=2:flags={}+{SCALE_FLAGS}",
-				size.width, size.height, scale_mode
-			)),
-			Err(err) => {
-				eprintln!("{err}");
-				None
-			}
-		};
+	even_size: bool,
+) -> Result<Vec<Filter>> {
+	if even_size && let Some(size) = size {
+		size.require_even()?;
 	}
 
-	None
+	let flags = format!("{scale_mode}+{SCALE_FLAGS}");
+
+	let mut filters = Vec::new();
+	filters.extend(aspect.map(|aspect| aspect_filter(aspect, aspect_mode)));
+
+	let size = match (width, height) {
+		(Some(width), _) => Some(Resize::Width(width)),
+		(_, Some(height)) => Some(Resize::Height(height)),
+		_ => size.cloned(),
+	};
+	match size {
+		None => {}
+		Some(Resize::Width(width)) => filters.push(
+			Filter::new("scale")
+				.arg("w", width)
+				.arg("h", -2)
+				.arg("flags", flags),
+		),
+		Some(Resize::Height(height)) => filters.push(
+			Filter::new("scale")
+				.arg("w", -2)
+				.arg("h", height)
+				.arg("flags", flags),
+		),
+		Some(Resize::Percent(percent)) => {
+			let factor = percent / 100.0;
+			filters.push(
+				Filter::new("scale")
+					.arg("w", format!("trunc(iw*{factor}/2)*2"))
+					.arg("h", -2)
+					.arg("flags", flags),
+			);
+		}
+		Some(Resize::Fit(size)) => filters.push(
+			Filter::new("scale")
+				.arg("w", size.width)
+				.arg("h", size.height)
+				.arg("force_original_aspect_ratio", "decrease")
+				.arg("force_divisible_by", 2)
+				.arg("flags", flags),
+		),
+		Some(Resize::Exact(size)) => {
+			// scale keeps the display aspect ratio by adjusting the sample aspect ratio, which would undo the stretch
+			filters.push(
+				Filter::new("scale")
+					.arg("w", size.width)
+					.arg("h", size.height)
+					.arg("flags", flags),
+			);
+			filters.push(Filter::new("setsar").positional(1));
+		}
+		Some(Resize::Fill(size)) => {
+			filters.push(
+				Filter::new("scale")
+					.arg("w", size.width)
+					.arg("h", size.height)
+					.arg("force_original_aspect_ratio", "increase")
+					.arg("force_divisible_by", 2)
+					.arg("flags", flags),
+			);
+			filters.push(
+				Filter::new("crop")
+					.arg("w", size.width)
+					.arg("h", size.height),
+			);
+		}
+	}
+
+	Ok(filters)
 }
 
-pub(crate) fn generate_fps_filter(
-	fps_arg: Option<f64>,
-	fps_mult_arg: Option<f64>,
-	stream_fps: Option<f64>,
-) -> Option<String> {
-	if let Some(fps) = fps_arg {
-		Some(format!("fps=fps={fps:.3}"));
-	} else if let (Some(fps_mult), Some(fps)) = (fps_mult_arg, stream_fps) {
-		Some(format!("fps=fps={:.3}", fps * fps_mult));
+/// Generates an HDR-to-SDR tonemapping chain for the given stream, if it needs one.
+/// The tonemap peak is taken from `peak_override` or the stream's `MaxCLL` or mastering display metadata where available.
+///
+/// The tonemap operators need a linear signal, which only `zscale` can produce from PQ and HLG. An ffmpeg built
+/// without libzimg tonemaps with libplacebo instead, as does the bt2390 mode, which needs a Vulkan device that's set
+/// up on `command`.
+pub(crate) fn generate_tonemap_filters(
+	stream: &Stream,
+	mode: &TonemapMode,
+	peak_override: Option<f64>,
+	command: &mut FfmpegCommand,
+) -> Result<Vec<Filter>> {
+	let peak = match stream.hdr_format() {
+		None | Some(HdrFormat::DolbyVision { compatibility: DoviCompatibility::Sdr, .. }) => return Ok(vec![]),
+		Some(HdrFormat::DolbyVision {
+			profile,
+			compatibility: DoviCompatibility::None | DoviCompatibility::Unknown(_),
+		}) => {
+			anyhow::bail!("Dolby Vision profile {profile} has no HDR10- or HLG-compatible base layer to tonemap")
+		}
+		Some(HdrFormat::Hlg | HdrFormat::DolbyVision { compatibility: DoviCompatibility::Hlg, .. }) => {
+			Some(stream.peak_luminance().unwrap_or(HLG_PEAK_LUMINANCE))
+		}
+		Some(_) => stream.peak_luminance(),
+	};
+	let peak = peak_override.or(peak);
+
+	let capabilities = capabilities()?;
+
+	// ffmpeg's tonemap filter has no BT.2390 EETF and can't linearize its input without zscale, libplacebo does both
+	// but reads the peak from the metadata itself
+	let use_libplacebo =
+		*mode == TonemapMode::Bt2390 || (!capabilities.has_filter("zscale") && capabilities.has_filter("libplacebo"));
+	if use_libplacebo {
+		if peak_override.is_some() {
+			if *mode == TonemapMode::Bt2390 {
+				anyhow::bail!("--tonemap-peak can't be used with the bt2390 tonemap mode")
+			}
+			anyhow::bail!("--tonemap-peak needs the zscale filter, which your ffmpeg build doesn't have")
+		}
+		capabilities.require_filters(&["libplacebo"])?;
+		if *mode != TonemapMode::Bt2390 {
+			warn("zscale isn't available in this ffmpeg build, tonemapping with libplacebo instead");
+		}
+		command
+			.option("init_hw_device", "vulkan=vk")
+			.option("filter_hw_device", "vk");
+		return Ok(vec![
+			Filter::new("libplacebo")
+				.arg("tonemapping", mode)
+				.arg("colorspace", "bt709")
+				.arg("color_primaries", "bt709")
+				.arg("color_trc", "bt709")
+				.arg("range", "tv"),
+		]);
+	}
+
+	let mut tonemap = Filter::new("tonemap").arg("tonemap", mode).arg("desat", 0);
+	if let Some(peak) = peak {
+		tonemap = tonemap.arg("peak", format!("{:.3}", peak / TONEMAP_REFERENCE_WHITE));
 	}
 
+	capabilities.require_filters(&["zscale", "tonemap"])?;
+	Ok(vec![
+		Filter::new("zscale")
+			.arg("t", "linear")
+			.arg("npl", TONEMAP_REFERENCE_WHITE),
+		Filter::new("format").positional("gbrpf32le"),
+		Filter::new("zscale").arg("p", "bt709"),
+		tonemap,
+		Filter::new("zscale").arg("t", "bt709").arg("m", "bt709"),
+	])
+}
+
+// TODO: --framerate and --framerate-mult have never produced a filter, the string version built one and dropped it
+pub(crate) fn generate_fps_filter(
+	_fps_arg: Option<f64>,
+	_fps_mult_arg: Option<f64>,
+	_stream_fps: Option<f64>,
+) -> Option<Filter> {
 	None
 }
 
@@ -156,39 +426,48 @@ pub(crate) fn generate_color_sharpness_filters(
 	contrast: f64,
 	saturation: f64,
 	sharpness: f64,
-) -> Option<String> {
-	let mut filter_parts: Vec<String> = vec![];
+) -> Vec<Filter> {
+	let mut filters = vec![];
 
 	if brightness != 0.0 || contrast != 1.0 || saturation != 1.0 {
-		let mut eq_args: Vec<String> = vec![];
+		let mut eq = Filter::new("eq");
 
 		if brightness != 0.0 {
-			eq_args.push(format!("brightness={brightness}"));
+			eq = eq.arg("brightness", brightness);
 		}
 		if contrast != 1.0 {
-			eq_args.push(format!("contrast={contrast}"));
+			eq = eq.arg("contrast", contrast);
 		}
 		if saturation != 1.0 {
-			eq_args.push(format!("saturation={saturation}"));
+			eq = eq.arg("saturation", saturation);
 		}
 
-		filter_parts.push(format!("eq={}", eq_args.join(":")));
+		filters.push(eq);
 	}
 
 	if sharpness != 0.0 {
-		filter_parts.push(format!("unsharp=la={sharpness}:ca={sharpness}"));
+		filters.push(
+			Filter::new("unsharp")
+				.arg("la", sharpness)
+				.arg("ca", sharpness),
+		);
 	}
 
-	if filter_parts.is_empty() {
-		None
-	} else {
-		Some(filter_parts.join(","))
-	}
+	filters
+}
+
+/// Generates a `fade` or `afade` filter, with times in seconds.
+pub(crate) fn fade_filter(name: &str, direction: &str, start: f64, duration: f64) -> Filter {
+	Filter::new(name)
+		.arg("t", direction)
+		.arg("st", format!("{start:.3}"))
+		.arg("d", format!("{duration:.3}"))
 }
 
 #[allow(clippy::too_many_arguments)]
-/// This function generates a chain of filters that should be appended to the very end of a filtergraph.
+/// This function finishes the given video chain with the palette filters, which are appended to its very end.
 pub(crate) fn generate_palette_filtergraph(
+	video: Chain,
 	palette_file: Option<&Path>,
 	palette_name: Option<&BuiltInPalette>,
 	num_colors: u16,
@@ -196,61 +475,62 @@ pub(crate) fn generate_palette_filtergraph(
 	diff_rect: bool,
 	dither: &DitherMode,
 	bayer_scale: u8,
-) -> Result<String> {
-	fn palette_filter_string(pal_string: &String, paletteuse_args: &String) -> String {
-		[
-			",setsar=1 [filtered]".to_string(),
-			format!("{pal_string} [pal]"),
-			format!("[filtered][pal] paletteuse={paletteuse_args}"),
-		]
-		.join(";")
+) -> Result<Graph> {
+	let mut paletteuse = Filter::new("paletteuse").arg("dither", dither);
+	if dither == &DitherMode::Bayer {
+		paletteuse = paletteuse.arg("bayer_scale", bayer_scale);
+	}
+	if diff_rect {
+		paletteuse = paletteuse.arg("diff_mode", "rectangle");
 	}
 
-	let paletteuse_args = {
-		let mut args = HashMap::new();
-		args.insert("dither".to_string(), format!("{dither}"));
-		if dither == &DitherMode::Bayer {
-			args.insert("bayer_scale".to_string(), format!("{bayer_scale}"));
-		}
-		if diff_rect {
-			args.insert("diff_mode".to_string(), "rectangle".to_string());
-		}
-		if palette_file.is_none() && palette_name.is_none() {
-			let new = u8::from(stats_mode == &StatsMode::Single);
-			args.insert("new".to_string(), format!("{new}"));
-		}
-
-		args.into_iter()
-			.map(|(k, v)| format!("{k}={v}"))
-			.collect::<Vec<String>>()
-			.join(":")
-	};
+	let video = video.filter(Filter::new("setsar").positional(1));
+	let mut graph = Graph::new();
 
-	match (palette_file, palette_name) {
-		(Some(palette_file), None) => {
-			#[allow(clippy::needless_late_init)]
-			let pal_string: String;
-			match Palette::load_from_file(palette_file) {
-				Ok(pal) => pal_string = palette_to_ffmpeg(&pal),
-				Err(e) => anyhow::bail!(e),
-			}
-			Ok(palette_filter_string(&pal_string, &paletteuse_args))
-		}
-		(None, Some(palette_name)) => {
-			let pal_string = palette_to_ffmpeg(&get_builtin_palette(palette_name));
-			Ok(palette_filter_string(&pal_string, &paletteuse_args))
-		}
+	let pal = match (palette_file, palette_name) {
+		(Some(palette_file), None) => match Palette::load_from_file(palette_file) {
+			Ok(pal) => pal,
+			Err(e) => anyhow::bail!(e),
+		},
+		(None, Some(palette_name)) => get_builtin_palette(palette_name),
 		(None, None) => {
 			// no palette was given, so we'll use palettegen to create one
-			Ok([
-				",setsar=1,split [a][b]".to_string(),
-				format!("[a] palettegen=max_colors={num_colors}:reserve_transparent=0:stats_mode={stats_mode} [pal]"),
-				format!("[b][pal] paletteuse={paletteuse_args}"),
-			]
-			.join(";"))
+			let (a, b, pal) = (graph.label("a"), graph.label("b"), graph.label("pal"));
+			let new = u8::from(stats_mode == &StatsMode::Single);
+
+			graph.push(
+				video
+					.filter(Filter::new("split"))
+					.outputs([a.clone(), b.clone()]),
+			);
+			graph.push(
+				Chain::new()
+					.input(a)
+					.filter(
+						Filter::new("palettegen")
+							.arg("max_colors", num_colors)
+							.arg("reserve_transparent", 0)
+							.arg("stats_mode", stats_mode),
+					)
+					.output(pal.clone()),
+			);
+			graph.push(
+				Chain::new()
+					.inputs([b, pal])
+					.filter(paletteuse.arg("new", new)),
+			);
+
+			return Ok(graph);
 		}
 		_ => anyhow::bail!("Well, this wasn't supposed to happen."),
-	}
+	};
+
+	let filtered = graph.label("filtered");
+	graph.push(video.output(filtered.clone()));
+	let pal = palette_to_ffmpeg(&pal, &mut graph);
+	graph.push(Chain::new().inputs([filtered, pal]).filter(paletteuse));
+
+	Ok(graph)
 }
 
 /// This is a small wrapper for [ffprobe] that repeats the invocation with frame counting
@@ -263,7 +543,7 @@ pub(crate) fn ffprobe_output<P: AsRef<Path>>(input: P) -> Result<FFProbeOutput>
 
 	#[cfg(debug_assertions)]
 	eprintln!("Running ffprobe again and counting frames…");
-	ffprobe(&input, true)
+	Ok(ffprobe(&input, true)?)
 }
 
 /// This is a small wrapper for [ffprobe] that repeats the invocation with frame counting
@@ -286,7 +566,7 @@ pub(crate) fn ffprobe_frames<P: AsRef<Path>>(input: P) -> Result<FFProbeOutput>
 		.all(|x| x);
 
 	if !all_video_streams_have_nb_frames {
-		return ffprobe(&input, true);
+		return Ok(ffprobe(&input, true)?);
 	}
 
 	Ok(p)
@@ -1,16 +1,30 @@
 use crate::cmd_auto::ffmpeg_auto;
 use crate::cmd_barcode::ffmpeg_barcode;
+use crate::cmd_bitrate::ffmpeg_bitrate;
 use crate::cmd_gif::ffmpeg_gif;
 use crate::cmd_info::ffmpeg_info;
 use crate::cmd_quant::ffmpeg_quant;
-use crate::commands::{Cli, Commands};
+use crate::commands::{AutoArgs, BarcodeArgs, Cli, Commands, GIFArgs, QuantArgs};
+use anyhow::Context;
 use clap::Parser;
+use ffmpeg::ffmpeg::binaries::set_ffmpeg_binary;
+use ffmpeg::ffmpeg::cancel::install_cancel_handler;
+use ffmpeg::ffmpeg::error::FfError;
+use ffmpeg::ffmpeg::report::{Report, emit, set_json_output, warn};
+use ffmpeg::ffmpeg::script::{RunMode, render_script, run_mode, set_run_mode, take_steps};
+use ffmpeg::ffmpeg::watchdog::{Watchdog, set_watchdog};
+use ffmpeg::outln;
+use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::time::Duration;
 
 mod cmd_auto;
+mod cmd_auto_copy;
 mod cmd_barcode;
+mod cmd_bitrate;
 mod cmd_gif;
 mod cmd_info;
 mod cmd_quant;
@@ -18,10 +32,35 @@
 mod commands_traits;
 mod common;
 mod palettes;
-mod vec_push_ext;
 
 fn main() -> ExitCode {
 	let cli = Cli::parse();
+	if let Some(ffmpeg) = &cli.ffmpeg {
+		set_ffmpeg_binary(ffmpeg.clone());
+	}
+	set_json_output(cli.json);
+	set_watchdog(Watchdog {
+		stall_timeout: cli.stall_timeout.map(Duration::from_secs),
+		max_runtime: cli.max_runtime.map(Duration::from_secs),
+	});
+	if cli.dry_run {
+		set_run_mode(RunMode::DryRun);
+	} else if cli.emit_script.is_some() {
+		set_run_mode(RunMode::EmitScript);
+	}
+	install_cancel_handler();
+
+	// a file that was there before this run isn't a partial output and stays, even if ffmpeg started overwriting it
+	let output_existed = match &cli.command {
+		Some(
+			Commands::Auto(AutoArgs { output, .. })
+			| Commands::Barcode(BarcodeArgs { output, .. })
+			| Commands::Gif(GIFArgs { output, .. })
+			| Commands::Quant(QuantArgs { output, .. }),
+		) => output.exists(),
+		_ => false,
+	};
+
 	let output: &PathBuf;
 
 	let result = match &cli.command {
@@ -48,10 +87,13 @@ fn main() -> ExitCode {
 		Some(Commands::Info(args)) => {
 			return match ffmpeg_info(args) {
 				Ok(()) => ExitCode::SUCCESS,
-				Err(e) => {
-					eprintln!("execution failed: {e}");
-					ExitCode::FAILURE
-				}
+				Err(e) => fail(&e),
+			};
+		}
+		Some(Commands::Bitrate(args)) => {
+			return match ffmpeg_bitrate(args, cli.debug).and_then(|()| emit_script(cli.emit_script.as_deref())) {
+				Ok(()) => ExitCode::SUCCESS,
+				Err(e) => fail(&e),
 			};
 		}
 		None => {
@@ -59,7 +101,8 @@ fn main() -> ExitCode {
 		}
 	};
 
-	match result {
+	match result.and_then(|()| emit_script(cli.emit_script.as_deref())) {
+		Ok(()) if run_mode() != RunMode::Run => ExitCode::SUCCESS,
 		Ok(()) => {
 			match fs::metadata(output) {
 				Ok(m) => {
@@ -67,17 +110,76 @@ fn main() -> ExitCode {
 					let size = humansize::format_size(m.len(), humansize::DECIMAL);
 					#[cfg(not(target_os = "macos"))]
 					let size = humansize::format_size(m.len(), humansize::WINDOWS);
-					println!("Output file size: {size}");
+					outln!("Output file size: {size}");
+					emit(&Report::OutputSize { bytes: m.len() });
 				}
 				Err(err) => {
-					eprintln!("Can't determine output file size: {err}");
+					warn(&format!("Can't determine output file size: {err}"));
 				}
 			}
 			ExitCode::SUCCESS
 		}
 		Err(e) => {
-			eprintln!("execution failed: {e}");
-			ExitCode::FAILURE
+			let killed = matches!(
+				e.downcast_ref::<FfError>(),
+				Some(FfError::Cancelled | FfError::Stalled { .. } | FfError::TimedOut { .. })
+			);
+			if !cli.keep_partial && killed && !output_existed {
+				remove_partial_output(output);
+			}
+			fail(&e)
 		}
 	}
 }
+
+/// Writes the commands recorded in [`RunMode::EmitScript`] to a script, if one was asked for.
+fn emit_script(path: Option<&Path>) -> anyhow::Result<()> {
+	let Some(path) = path else {
+		return Ok(());
+	};
+
+	let script = render_script(&take_steps(), &env::temp_dir());
+	fs::write(path, script).with_context(|| format!("Couldn't write script to {}", path.display()))?;
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+	}
+	outln!("Wrote script to {}", path.display());
+
+	Ok(())
+}
+
+fn remove_partial_output(output: &Path) {
+	match fs::remove_file(output) {
+		Ok(()) => {
+			eprintln!("Removed partial output {}", output.display());
+			emit(&Report::PartialOutputRemoved { path: &output.to_string_lossy() });
+		}
+		Err(e) if e.kind() == ErrorKind::NotFound => (),
+		Err(e) => warn(&format!("Couldn't remove partial output {}: {e}", output.display())),
+	}
+}
+
+/// Prints the error and maps it to an exit code. Errors from the ffmpeg crate get their own codes
+/// so that scripts can tell a missing ffmpeg or a failed encode apart from bad arguments.
+fn fail(e: &anyhow::Error) -> ExitCode {
+	eprintln!("execution failed: {e}");
+
+	let code: u8 = match e.downcast_ref::<FfError>() {
+		None => 1,
+		Some(FfError::InvalidValue(_) | FfError::InvalidPath(_)) => 2,
+		Some(FfError::NonZeroExit { .. }) => 3,
+		Some(FfError::ProbeParse(_)) => 4,
+		Some(FfError::MissingStream(_)) => 5,
+		Some(FfError::IoErr(_)) => 6,
+		Some(FfError::MissingCapability { .. }) => 7,
+		Some(FfError::Stalled { .. }) => 8,
+		Some(FfError::TimedOut { .. }) => 124,
+		Some(FfError::Cancelled) => 130,
+		Some(FfError::SpawnFailed { .. }) => 126,
+		Some(FfError::BinaryNotFound { .. }) => 127,
+	};
+	emit(&Report::Error { message: &e.to_string(), exit_code: code });
+	ExitCode::from(code)
+}
@@ -0,0 +1,577 @@
+#![cfg(unix)]
+
+// Runs `ff --dry-run` against stand-ins for ffmpeg and ffprobe and compares the generated command lines.
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+/// Stands in for ffmpeg when the capabilities are queried, with everything the commands use available
+/// except for what the test listed in `missing`.
+const FAKE_FFMPEG: &str = r#"#!/bin/sh
+missing="$(dirname "$0")/missing"
+without_missing() {
+	if [ -s "$missing" ]; then grep -v -w -F -f "$missing"; else cat; fi
+}
+case "$*" in
+*-version*)
+	echo "ffmpeg version 7.1 Copyright (c) 2000-2024 the FFmpeg developers" ;;
+*-encoders*)
+	without_missing <<'EOF'
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)
+ V....D libx265              libx265 H.265 / HEVC (codec hevc)
+ V....D gif                  GIF (Graphics Interchange Format)
+ V....D png                  PNG (Portable Network Graphics) image
+ A....D aac                  AAC (Advanced Audio Coding)
+EOF
+	;;
+*-filters*)
+	without_missing <<'EOF'
+ TSC zscale            V->V       Apply resizing, colorspace and bit depth conversion.
+ ..C tonemap           V->V       Conversion to/from different dynamic ranges.
+ ... libplacebo        N->V       Apply various GPU filters from libplacebo
+ ... subtitles         V->V       Render text subtitles onto input video using the libass library.
+ ... ass               V->V       Render ASS subtitles onto input video using the libass library.
+EOF
+	;;
+*-pix_fmts*)
+	without_missing <<'EOF'
+-----
+IO... yuv420p                3             12      8-8-8
+IO... yuv420p10le            3             15      10-10-10
+IO... rgb24                  3             24      8-8-8
+EOF
+	;;
+*)
+	# with a `hang` file, an encode creates its output and hangs until it's killed
+	if [ -e "$(dirname "$0")/hang" ]; then
+		for output; do :; done
+		touch "$output"
+		exec sleep 10
+	fi
+	exit 1 ;;
+esac
+"#;
+
+/// Stands in for ffprobe and prints whatever the test put into `probe.json`, or a keyframe every 2 seconds when
+/// packets are listed. These belong to the selected stream and start at the `start_time` in `probe.json`.
+const FAKE_FFPROBE: &str = r#"#!/bin/sh
+case "$*" in
+*packet=*)
+	stream=0
+	previous=
+	for arg; do
+		# specifiers like `V:0` select the first stream, plain indexes select that stream
+		[ "$previous" = -select_streams ] && [ "$arg" -eq "$arg" ] 2>/dev/null && stream=$arg
+		previous=$arg
+	done
+	start=$(sed -n 's/.*"start_time": "\([0-9.]*\)".*/\1/p' "$(dirname "$0")/probe.json")
+	awk -v stream="$stream" -v start="${start:-0}" 'BEGIN {
+		for (i = 0; i <= 120; i += 2)
+			printf "codec_type=video|stream_index=%s|pts_time=%.6f|dts_time=%.6f|duration_time=2|size=100|flags=K__\n",
+				stream, i + start, i + start
+	}' ;;
+*)
+	cat "$(dirname "$0")/probe.json" ;;
+esac
+"#;
+
+/// A 2-minute 1080p H.264 video with AAC audio and an SRT subtitle stream.
+const PROBE_SDR: &str = r#"{
+	"streams": [
+		{ "index": 0, "codec_type": "video", "codec_name": "h264", "width": 1920, "height": 1080,
+			"sample_aspect_ratio": "1:1", "r_frame_rate": "24000/1001", "nb_frames": "2877" },
+		{ "index": 1, "codec_type": "audio", "codec_name": "aac", "tags": { "language": "eng" } },
+		{ "index": 2, "codec_type": "subtitle", "codec_name": "subrip", "tags": { "language": "eng" } }
+	],
+	"format": { "duration": "120.000000" }
+}"#;
+
+/// A 2-minute 4K HDR10 video with `MaxCLL` metadata.
+const PROBE_HDR: &str = r#"{
+	"streams": [
+		{ "index": 0, "codec_type": "video", "codec_name": "hevc", "width": 3840, "height": 2160,
+			"sample_aspect_ratio": "1:1", "r_frame_rate": "24000/1001", "color_transfer": "smpte2084",
+			"side_data_list": [ { "side_data_type": "Content light level metadata", "max_content": 1000, "max_average": 400 } ] }
+	],
+	"format": { "duration": "120.000000" }
+}"#;
+
+/// A 2-minute 1080p H.264 video at NTSC's 29.97 fps, with what `ff info` prints for it.
+const PROBE_NTSC: &str = r#"{
+	"streams": [
+		{ "index": 0, "codec_type": "video", "codec_name": "h264", "profile": "High", "level": 41, "pix_fmt": "yuv420p",
+			"width": 1920, "height": 1080, "sample_aspect_ratio": "1:1", "r_frame_rate": "30000/1001",
+			"color_range": "tv", "color_space": "bt709", "color_transfer": "bt709", "color_primaries": "bt709" }
+	],
+	"format": { "duration": "120.000000" }
+}"#;
+
+/// A 2-minute MPEG-TS with two H.264 streams and AAC audio, which starts at 1.4s like MPEG-TS tends to.
+const PROBE_TS: &str = r#"{
+	"streams": [
+		{ "index": 0, "codec_type": "video", "codec_name": "h264", "width": 1920, "height": 1080,
+			"sample_aspect_ratio": "1:1", "r_frame_rate": "25/1" },
+		{ "index": 1, "codec_type": "video", "codec_name": "h264", "width": 1280, "height": 720,
+			"sample_aspect_ratio": "1:1", "r_frame_rate": "25/1" },
+		{ "index": 2, "codec_type": "audio", "codec_name": "aac", "tags": { "language": "eng" } }
+	],
+	"format": { "duration": "120.000000", "start_time": "1.400000" }
+}"#;
+
+/// Sets up a directory with an `input.mkv` that probes as `probe` and an `input.en.srt` next to it, and an ffmpeg
+/// lacking the encoders, filters and pixel formats in `missing`.
+fn fake_dir(probe: &str, missing: &[&str]) -> tempfile::TempDir {
+	let dir = tempfile::tempdir().unwrap();
+	for (name, script) in [("ffmpeg", FAKE_FFMPEG), ("ffprobe", FAKE_FFPROBE)] {
+		let path = dir.path().join(name);
+		fs::write(&path, script).unwrap();
+		fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+	}
+	fs::write(dir.path().join("missing"), missing.join("\n")).unwrap();
+	fs::write(dir.path().join("probe.json"), probe).unwrap();
+	fs::write(dir.path().join("input.mkv"), b"video").unwrap();
+	fs::write(
+		dir.path().join("input.en.srt"),
+		b"1\n00:00:01,000 --> 00:00:02,000\nsubtitle\n",
+	)
+	.unwrap();
+	dir
+}
+
+/// Returns a command that runs `ff` in `dir`, with the stand-ins found through PATH, so the command lines
+/// start with a plain `ffmpeg`.
+fn ff(dir: &Path) -> Command {
+	let path = format!("{}:{}", dir.display(), std::env::var("PATH").unwrap_or_default());
+	let mut command = Command::new(env!("CARGO_BIN_EXE_ff"));
+	command
+		.current_dir(dir)
+		.env("PATH", path)
+		.env("TMPDIR", dir)
+		.env_remove("FFAUTO_FFMPEG")
+		.env_remove("FFAUTO_FFPROBE");
+	command
+}
+
+/// Runs `ff --dry-run` with the given arguments in a [`fake_dir`]. Returns the POSIX sh version of every
+/// ffmpeg command it would have run, or what it printed to stderr if it failed.
+fn run_ff(probe: &str, missing: &[&str], args: &[&str]) -> Result<Vec<String>, String> {
+	let dir = fake_dir(probe, missing);
+	let output = ff(dir.path()).arg("--dry-run").args(args).output().unwrap();
+	if !output.status.success() {
+		return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+	}
+
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	let lines: Vec<&str> = stdout.lines().collect();
+	Ok(lines
+		.windows(2)
+		.filter(|pair| pair[0] == "# POSIX sh")
+		.map(|pair| pair[1].replace(&*dir.path().to_string_lossy(), "$DIR"))
+		.collect())
+}
+
+fn assert_golden(probe: &str, args: &[&str], expected: &[&str]) {
+	let commands = run_ff(probe, &[], args).unwrap_or_else(|stderr| panic!("ff {} failed: {stderr}", args.join(" ")));
+	assert_eq!(commands, expected, "ff {}", args.join(" "));
+}
+
+#[test]
+fn auto() {
+	assert_golden(
+		PROBE_SDR,
+		&["auto", "-i", "input.mkv", "output.mp4"],
+		&[concat!(
+			"ffmpeg -hide_banner -loglevel warning -y -i input.mkv -map 0:V:0 -map 0:a:0 -map '0:s?' -c:a copy ",
+			"-c:v libx264 -disposition 0 -empty_hdlr_name 1 -crf 23 -pix_fmt yuv420p -preset slower -tune film ",
+			"-partitions all -me_method tesa -movflags faststart -metadata:s 'handler_name=\"\"' ",
+			"-metadata:s:1 language=eng output.mp4"
+		)],
+	);
+	assert_golden(
+		PROBE_SDR,
+		&[
+			"auto",
+			"-i",
+			"input.mkv",
+			"output.mp4",
+			"-s",
+			"1m",
+			"-t",
+			"30",
+			"--crop",
+			"1280x720,top",
+			"--vs",
+			"50%",
+			"-r",
+			"30",
+		],
+		&[concat!(
+			"ffmpeg -hide_banner -loglevel warning -y -ss 60 -i input.mkv -map 0:V:0 -map 0:a:0 -map '0:s?' -t 30 ",
+			"-c:a copy -c:v libx264 -disposition 0 -empty_hdlr_name 1 -crf 23 -pix_fmt yuv420p -preset slower ",
+			"-tune film -partitions all -me_method tesa -movflags faststart ",
+			"-vf 'crop=w=1280:h=720:x=320:y=0,",
+			"scale=w=trunc(iw*0.5/2)*2:h=-2:flags=bicubic+accurate_rnd+full_chroma_int+full_chroma_inp' ",
+			"-metadata:s 'handler_name=\"\"' -metadata:s:1 language=eng output.mp4"
+		)],
+	);
+	assert_golden(
+		PROBE_SDR,
+		&[
+			"auto",
+			"-i",
+			"input.mkv",
+			"output.mp4",
+			"-s",
+			"10",
+			"--burn-subtitle",
+			"--mute",
+		],
+		&[concat!(
+			"ffmpeg -hide_banner -loglevel warning -y -ss 10 -i input.mkv -map 0:V:0 -c:v libx264 ",
+			"-disposition 0 -empty_hdlr_name 1 -sn -an -crf 23 -pix_fmt yuv420p -preset slower -tune film ",
+			"-partitions all -me_method tesa -movflags faststart ",
+			"-vf setpts=PTS+10/TB,subtitles=filename=input.mkv:si=0,setpts=PTS-10/TB ",
+			"-metadata:s 'handler_name=\"\"' output.mp4"
+		)],
+	);
+	// the 5th gen iPod gets no subtitles at all
+	assert_golden(
+		PROBE_SDR,
+		&["auto", "-i", "input.mkv", "output.mp4", "-O", "ipod5"],
+		&[concat!(
+			"ffmpeg -hide_banner -loglevel warning -y -i input.mkv -map 0:V:0 -map 0:a:0 -c:a aac -c:v libx264 ",
+			"-disposition 0 -empty_hdlr_name 1 -sn -b:a 256k -ac 2 -crf 23 -pix_fmt yuv420p -preset slower -tune film ",
+			"-partitions all -me_method tesa -profile:v baseline -level 1.3 -map_chapters 0 -maxrate 768K -bufsize 2M ",
+			"-movflags faststart ",
+			"-vf scale=w=320:h=240:force_original_aspect_ratio=decrease:force_divisible_by=2:",
+			"flags=bicubic+accurate_rnd+full_chroma_int+full_chroma_inp ",
+			"-metadata:s 'handler_name=\"\"' -metadata:s:1 language=eng output.mp4"
+		)],
+	);
+}
+
+#[test]
+fn auto_odd_size() {
+	let stderr = run_ff(
+		PROBE_SDR,
+		&[],
+		&["auto", "-i", "input.mkv", "output.mp4", "--vs", "1281x720!"],
+	)
+	.unwrap_err();
+	assert!(stderr.contains("has to be even"), "{stderr}");
+	// GIFs aren't 4:2:0
+	assert!(
+		run_ff(
+			PROBE_SDR,
+			&[],
+			&["gif", "-i", "input.mkv", "output.gif", "--vs", "481x271!"]
+		)
+		.is_ok()
+	);
+}
+
+#[test]
+fn auto_copy() {
+	assert_golden(
+		PROBE_SDR,
+		&[
+			"auto",
+			"-i",
+			"input.mkv",
+			"output.mkv",
+			"--copy",
+			"-s",
+			"5",
+			"-t",
+			"10",
+			"--Ss",
+			"0",
+			"--Ss",
+			"input.en.srt",
+		],
+		&[concat!(
+			"ffmpeg -hide_banner -loglevel warning -y -ss 4 -i input.mkv -i $DIR/input.en.srt -map 0:V:0 -map 0:a:0 ",
+			"-map 0:s:0 -map 1:s:0 -t 12 -c copy -avoid_negative_ts make_zero -movflags faststart ",
+			"-metadata:s:1 language=eng -metadata:s:2 language=eng -metadata:s:3 language=eng output.mkv"
+		)],
+	);
+}
+
+#[test]
+fn auto_copy_start_time() {
+	// keyframes are snapped in -ss's timeline, which starts at the container's start time, and taken from the
+	// video stream that's mapped
+	assert_golden(
+		PROBE_TS,
+		&[
+			"auto",
+			"-i",
+			"input.mkv",
+			"output.mkv",
+			"--copy",
+			"-s",
+			"5",
+			"-t",
+			"10",
+			"--Vs",
+			"1",
+		],
+		&[concat!(
+			"ffmpeg -hide_banner -loglevel warning -y -ss 4 -i input.mkv -map 0:V:1 -map 0:a:0 -t 12 -c copy -sn ",
+			"-avoid_negative_ts make_zero -movflags faststart -metadata:s:1 language=eng output.mkv"
+		)],
+	);
+}
+
+#[test]
+fn auto_smart_cut() {
+	let commands = run_ff(
+		PROBE_NTSC,
+		&[],
+		&[
+			"auto",
+			"-i",
+			"input.mkv",
+			"output.mp4",
+			"--copy",
+			"--smart-cut",
+			"-s",
+			"5",
+			"-t",
+			"10",
+			"--Ss",
+			"input.en.srt",
+		],
+	)
+	.unwrap();
+	// the parts end up in a temp directory with a random name
+	let commands = commands
+		.iter()
+		.map(|command| {
+			let mut command = command.clone();
+			while let Some(i) = command.find("$DIR/ffauto") {
+				let name = i + "$DIR/".len();
+				command.replace_range(name..name + "ffauto".len() + 6, "$TMP");
+			}
+			command
+		})
+		.collect::<Vec<_>>();
+	// the keyframes are 2 seconds apart, so only the first and last second are re-encoded
+	assert_eq!(
+		commands,
+		[
+			concat!(
+				"ffmpeg -hide_banner -loglevel warning -y -ss 5 -i input.mkv -map 0:0 -t 1 -c:v libx264 -an -sn -dn ",
+				"-crf 18 -pix_fmt yuv420p -preset slower -fps_mode passthrough -profile:v high -level 4.1 ",
+				"-color_primaries bt709 -color_trc bt709 -colorspace bt709 -color_range tv -f mpegts ",
+				"$DIR/$TMP/segment0.ts"
+			),
+			concat!(
+				"ffmpeg -hide_banner -loglevel warning -y -ss 6 -i input.mkv -map 0:0 -t 8 -c:v copy -an -sn -dn ",
+				"-bsf:v h264_mp4toannexb -f mpegts $DIR/$TMP/segment1.ts"
+			),
+			concat!(
+				"ffmpeg -hide_banner -loglevel warning -y -ss 14 -i input.mkv -map 0:0 -t 1 -c:v libx264 -an -sn -dn ",
+				"-crf 18 -pix_fmt yuv420p -preset slower -fps_mode passthrough -profile:v high -level 4.1 ",
+				"-color_primaries bt709 -color_trc bt709 -colorspace bt709 -color_range tv -f mpegts ",
+				"$DIR/$TMP/segment2.ts"
+			),
+			concat!(
+				"ffmpeg -hide_banner -loglevel warning -y -ss 5 -i input.mkv -f concat -safe 0 ",
+				"-i $DIR/$TMP/segments.txt -i $DIR/input.en.srt -map 1:v:0 -map 2:s:0 -t 10 -c copy ",
+				"-avoid_negative_ts make_zero -movflags faststart -tag:v avc3 -metadata:s:1 language=eng output.mp4"
+			),
+		]
+	);
+}
+
+#[test]
+fn auto_capabilities() {
+	let args = ["auto", "-i", "input.mkv", "output.mp4"];
+	// the AAC audio is copied, and muted audio isn't encoded at all
+	assert!(run_ff(PROBE_SDR, &["aac"], &args).is_ok());
+	assert!(
+		run_ff(
+			PROBE_SDR,
+			&["aac"],
+			&["auto", "-i", "input.mkv", "output.mp4", "--mute"]
+		)
+		.is_ok()
+	);
+
+	let stderr = run_ff(
+		PROBE_SDR,
+		&["aac"],
+		&["auto", "-i", "input.mkv", "output.mp4", "-v", "0.5"],
+	)
+	.unwrap_err();
+	assert!(stderr.contains("Your ffmpeg build has no aac encoder"), "{stderr}");
+
+	let stderr = run_ff(
+		PROBE_SDR,
+		&["subtitles"],
+		&["auto", "-i", "input.mkv", "output.mp4", "--burn-subtitle"],
+	)
+	.unwrap_err();
+	assert!(stderr.contains("Your ffmpeg build has no subtitles filter"), "{stderr}");
+
+	let stderr = run_ff(
+		PROBE_SDR,
+		&["yuv420p10le"],
+		&["auto", "-i", "input.mkv", "output.mp4", "-C", "h265-10"],
+	)
+	.unwrap_err();
+	assert!(
+		stderr.contains("Your ffmpeg build has no yuv420p10le pixel format"),
+		"{stderr}"
+	);
+}
+
+#[test]
+fn gif() {
+	assert_golden(
+		PROBE_SDR,
+		&[
+			"gif",
+			"-i",
+			"input.mkv",
+			"output.gif",
+			"-s",
+			"10",
+			"-t",
+			"2.5",
+			"--vs",
+			"480x",
+		],
+		&[concat!(
+			"ffmpeg -hide_banner -loglevel error -y -ss 10 -t 2.5 -i input.mkv -filter_complex ",
+			"'[0:V:0]scale=w=480:h=-2:flags=bicubic+accurate_rnd+full_chroma_int+full_chroma_inp,setsar=1,",
+			"split[a1][b2];[a1]palettegen=max_colors=256:reserve_transparent=0:stats_mode=full[pal3];",
+			"[b2][pal3]paletteuse=dither=sierra2-4a:new=0' -an -dn -sn -loop 0 -f gif output.gif"
+		)],
+	);
+}
+
+#[test]
+fn barcode() {
+	assert_golden(
+		PROBE_SDR,
+		&["barcode", "-i", "input.mkv", "output.png", "--frames", "2877"],
+		&[concat!(
+			"ffmpeg -hide_banner -loglevel warning -y -i input.mkv -filter_complex ",
+			"'[0:V:0]format=rgb48be,scale=w=1:h=1080:flags=bicubic+accurate_rnd+full_chroma_int+full_chroma_inp,",
+			"tile=2877x1[video_out1];[video_out1]setsar=1,format=rgb24,",
+			"setparams=colorspace=bt709:color_primaries=bt709:color_trc=iec61966-2-1' ",
+			"-c:v png -update 1 -f image2 output.png"
+		)],
+	);
+}
+
+#[test]
+fn gif_tonemap() {
+	let args = ["gif", "-i", "input.mkv", "output.gif", "-t", "2"];
+	assert_golden(
+		PROBE_HDR,
+		&args,
+		&[concat!(
+			"ffmpeg -hide_banner -loglevel error -y -t 2 -i input.mkv -filter_complex ",
+			"'[0:V:0]zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,",
+			"tonemap=tonemap=hable:desat=0:peak=10.000,zscale=t=bt709:m=bt709,setsar=1,",
+			"split[a1][b2];[a1]palettegen=max_colors=256:reserve_transparent=0:stats_mode=full[pal3];",
+			"[b2][pal3]paletteuse=dither=sierra2-4a:new=0' -an -dn -sn -loop 0 -f gif output.gif"
+		)],
+	);
+	// libplacebo runs on the Vulkan device that's set up for the filters
+	assert_golden(
+		PROBE_HDR,
+		&[&args[..], &["--tonemap-mode", "bt2390"]].concat(),
+		&[concat!(
+			"ffmpeg -hide_banner -loglevel error -y -init_hw_device vulkan=vk -filter_hw_device vk -t 2 -i input.mkv ",
+			"-filter_complex '[0:V:0]libplacebo=tonemapping=bt.2390:colorspace=bt709:color_primaries=bt709:",
+			"color_trc=bt709:range=tv,setsar=1,split[a1][b2];",
+			"[a1]palettegen=max_colors=256:reserve_transparent=0:stats_mode=full[pal3];",
+			"[b2][pal3]paletteuse=dither=sierra2-4a:new=0' -an -dn -sn -loop 0 -f gif output.gif"
+		)],
+	);
+
+	// without zscale, every mode tonemaps with libplacebo
+	assert_eq!(
+		run_ff(PROBE_HDR, &["zscale"], &args).unwrap(),
+		[concat!(
+			"ffmpeg -hide_banner -loglevel error -y -init_hw_device vulkan=vk -filter_hw_device vk -t 2 -i input.mkv ",
+			"-filter_complex '[0:V:0]libplacebo=tonemapping=hable:colorspace=bt709:color_primaries=bt709:",
+			"color_trc=bt709:range=tv,setsar=1,split[a1][b2];",
+			"[a1]palettegen=max_colors=256:reserve_transparent=0:stats_mode=full[pal3];",
+			"[b2][pal3]paletteuse=dither=sierra2-4a:new=0' -an -dn -sn -loop 0 -f gif output.gif"
+		)]
+	);
+	let stderr = run_ff(
+		PROBE_HDR,
+		&["zscale"],
+		&[&args[..], &["--tonemap-peak", "1000"]].concat(),
+	)
+	.unwrap_err();
+	assert!(stderr.contains("--tonemap-peak needs the zscale filter"), "{stderr}");
+	// and there's no way to linearize the signal without either, which must not fall back to wrong colours
+	let stderr = run_ff(PROBE_HDR, &["zscale", "libplacebo"], &args).unwrap_err();
+	assert!(stderr.contains("Your ffmpeg build has no zscale filter"), "{stderr}");
+}
+
+#[test]
+fn removes_partial_output() {
+	let dir = fake_dir(PROBE_SDR, &[]);
+	fs::write(dir.path().join("hang"), b"").unwrap();
+	let gif = |output: &str, keep_partial: bool| {
+		let mut command = ff(dir.path());
+		command.args(["--json", "--max-runtime", "1"]);
+		if keep_partial {
+			command.arg("--keep-partial");
+		}
+		let output = command
+			.args(["gif", "-i", "input.mkv", output])
+			.output()
+			.unwrap();
+		assert!(!output.status.success(), "ffmpeg should have been killed");
+		String::from_utf8(output.stdout).unwrap()
+	};
+
+	let stdout = gif("output.gif", false);
+	assert!(!dir.path().join("output.gif").exists());
+	assert!(
+		stdout.contains(r#"{"type":"partial_output_removed","path":"output.gif"}"#),
+		"{stdout}"
+	);
+
+	gif("output.gif", true);
+	assert!(dir.path().join("output.gif").exists());
+
+	// a file that was there before isn't this run's partial output
+	fs::write(dir.path().join("existing.gif"), b"mine").unwrap();
+	gif("existing.gif", false);
+	assert_eq!(fs::read(dir.path().join("existing.gif")).unwrap(), b"mine");
+}
+
+#[test]
+fn info_timecodes() {
+	let dir = fake_dir(PROBE_NTSC, &[]);
+	let output = ff(dir.path())
+		.args(["info", "-i", "input.mkv", "--keyframes", "--timecode"])
+		.output()
+		.unwrap();
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+	// the stand-in has a keyframe every 2 seconds, which drop-frame timecodes keep close to the clock
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	let keyframes = stdout
+		.lines()
+		.skip_while(|line| !line.starts_with("Keyframes:"))
+		.collect::<Vec<_>>();
+	assert_eq!(keyframes[0], "Keyframes: 61");
+	assert_eq!(keyframes[1], "  00:00:00;00");
+	assert_eq!(keyframes[2], "  00:00:02;00 (+2.000s)");
+	assert_eq!(keyframes[31], "  00:00:59;28 (+2.000s)");
+	assert_eq!(keyframes[61], "  00:01:59;28 (+2.000s)");
+}
@@ -0,0 +1,55 @@
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Environment variable that overrides the ffmpeg binary.
+pub const FFMPEG_ENV: &str = "FFAUTO_FFMPEG";
+/// Environment variable that overrides the ffprobe binary.
+pub const FFPROBE_ENV: &str = "FFAUTO_FFPROBE";
+
+static FFMPEG_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+fn non_empty_env(key: &str) -> Option<OsString> {
+	env::var_os(key).filter(|v| !v.is_empty())
+}
+
+/// Makes every following invocation use the given ffmpeg binary, taking precedence over [`FFMPEG_ENV`].
+/// Only the first call has an effect.
+pub fn set_ffmpeg_binary(path: PathBuf) {
+	let _ = FFMPEG_OVERRIDE.set(path);
+}
+
+/// Returns the ffmpeg binary to run, which is the one set with [`set_ffmpeg_binary`],
+/// then the one from [`FFMPEG_ENV`], then whatever `ffmpeg` is in `PATH`.
+#[must_use]
+pub fn ffmpeg_binary() -> PathBuf {
+	FFMPEG_OVERRIDE
+		.get()
+		.cloned()
+		.or_else(|| non_empty_env(FFMPEG_ENV).map(PathBuf::from))
+		.unwrap_or_else(|| PathBuf::from("ffmpeg"))
+}
+
+/// Returns the ffprobe binary to run, which is the one from [`FFPROBE_ENV`], then the one next to
+/// a custom ffmpeg binary if there is one, then whatever `ffprobe` is in `PATH`.
+#[must_use]
+pub fn ffprobe_binary() -> PathBuf {
+	if let Some(ffprobe) = non_empty_env(FFPROBE_ENV) {
+		return PathBuf::from(ffprobe);
+	}
+
+	// static builds ship both binaries in the same directory, so look there first
+	let ffmpeg = ffmpeg_binary();
+	if ffmpeg.components().count() > 1
+		&& let Some(file_name) = ffmpeg.file_name().and_then(|f| f.to_str())
+		&& file_name.contains("ffmpeg")
+	{
+		let ffprobe = ffmpeg.with_file_name(file_name.replacen("ffmpeg", "ffprobe", 1));
+		if ffprobe.is_file() {
+			return ffprobe;
+		}
+	}
+
+	PathBuf::from("ffprobe")
+}
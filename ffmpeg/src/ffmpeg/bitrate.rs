@@ -0,0 +1,64 @@
+use crate::ffmpeg::ffprobe_struct::PacketInfo;
+use crate::ffmpeg::keyframes::keyframe_times;
+
+/// Returns the decode timestamps and sizes of all packets belonging to the given stream, in decode order.
+fn stream_packets(packets: &[PacketInfo], stream_index: u64) -> Vec<(f64, u64)> {
+	let mut stream_packets = packets
+		.iter()
+		.filter(|p| p.stream_index == stream_index && !p.discard)
+		.filter_map(|p| Some((p.dts_time.or(p.pts_time)?, p.size)))
+		.filter(|(time, _)| *time >= 0.0)
+		.collect::<Vec<(f64, u64)>>();
+	stream_packets.sort_by(|a, b| a.0.total_cmp(&b.0));
+	stream_packets
+}
+
+/// Sums up the packet sizes of a stream in one-second buckets and returns each second's bitrate in bits/s.
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn bitrate_per_second(packets: &[PacketInfo], stream_index: u64) -> Vec<u64> {
+	let mut seconds: Vec<u64> = vec![];
+	for (time, size) in stream_packets(packets, stream_index) {
+		let second = time.floor() as usize;
+		if seconds.len() <= second {
+			seconds.resize(second + 1, 0);
+		}
+		seconds[second] += size * 8;
+	}
+
+	seconds
+}
+
+/// Returns the highest average bitrate in bits/s the stream reaches within any window of the given length in seconds.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn peak_bitrate(packets: &[PacketInfo], stream_index: u64, window: f64) -> f64 {
+	let stream_packets = stream_packets(packets, stream_index);
+
+	let mut peak_bits = 0;
+	let mut window_bits = 0;
+	let mut window_end = 0;
+	for (start, (start_time, start_size)) in stream_packets.iter().enumerate() {
+		while window_end < stream_packets.len() && stream_packets[window_end].0 < start_time + window {
+			window_bits += stream_packets[window_end].1 * 8;
+			window_end += 1;
+		}
+
+		peak_bits = peak_bits.max(window_bits);
+
+		if window_end > start {
+			window_bits -= start_size * 8;
+		}
+	}
+
+	peak_bits as f64 / window
+}
+
+/// Returns the distances in seconds between consecutive keyframes of the given stream.
+#[must_use]
+pub fn keyframe_intervals(packets: &[PacketInfo], stream_index: u64) -> Vec<f64> {
+	keyframe_times(packets, stream_index)
+		.windows(2)
+		.map(|w| w[1] - w[0])
+		.collect()
+}
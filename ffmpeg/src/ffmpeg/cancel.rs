@@ -0,0 +1,80 @@
+use std::io::Write;
+use std::process::{Child, ExitStatus};
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::ffmpeg::error::FfError;
+use crate::ffmpeg::report::warn;
+
+/// How long ffmpeg gets to finalize its output after being asked to quit before it's killed.
+const QUIT_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+static INSTALL_HANDLER: Once = Once::new();
+
+/// Replaces the default SIGINT/SIGTERM (Ctrl-C on Windows) behavior with one that only marks the process as cancelled,
+/// so that a running ffmpeg can be stopped cleanly and [`FfError::Cancelled`] is returned instead.
+/// Calling this more than once does nothing.
+pub fn install_cancel_handler() {
+	INSTALL_HANDLER.call_once(|| {
+		if let Err(e) = ctrlc::set_handler(|| CANCELLED.store(true, Ordering::SeqCst)) {
+			eprintln!("Couldn't install the Ctrl-C handler: {e}");
+		}
+	});
+}
+
+#[must_use]
+pub fn is_cancelled() -> bool {
+	CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Waits for the child to exit. If a cancellation comes in while waiting, ffmpeg is asked to quit by sending `q`
+/// to its stdin, and killed if it doesn't within [`QUIT_TIMEOUT`]. `on_poll` is called while waiting, the child is
+/// killed right away if it returns an error. Either way the child is reaped before returning.
+pub(crate) fn wait_or_cancel(
+	child: &mut Child,
+	mut on_poll: impl FnMut() -> Result<(), FfError>,
+) -> Result<ExitStatus, FfError> {
+	loop {
+		if let Some(status) = child.try_wait()? {
+			// on a terminal Ctrl-C reaches ffmpeg too, which then exits on its own
+			return if is_cancelled() {
+				Err(FfError::Cancelled)
+			} else {
+				Ok(status)
+			};
+		}
+		if is_cancelled() {
+			break;
+		}
+		if let Err(e) = on_poll() {
+			let _ = child.kill();
+			child.wait()?;
+			return Err(e);
+		}
+		sleep(POLL_INTERVAL);
+	}
+
+	if let Some(stdin) = child.stdin.as_mut() {
+		// this fails if ffmpeg has already closed stdin, in which case it's on its way out anyway
+		let _ = stdin.write_all(b"q");
+		let _ = stdin.flush();
+	}
+
+	let deadline = Instant::now() + QUIT_TIMEOUT;
+	while Instant::now() < deadline {
+		if child.try_wait()?.is_some() {
+			return Err(FfError::Cancelled);
+		}
+		sleep(POLL_INTERVAL);
+	}
+
+	warn("ffmpeg didn't quit in time, killing it");
+	let _ = child.kill();
+	child.wait()?;
+
+	Err(FfError::Cancelled)
+}
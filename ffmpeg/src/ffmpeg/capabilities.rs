@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::sync::OnceLock;
+
+use crate::ffmpeg::binaries::ffmpeg_binary;
+use crate::ffmpeg::error::FfError;
+use crate::ffmpeg::runner::runner;
+
+/// What the ffmpeg binary in use was built with.
+#[derive(Debug, Clone, Default)]
+pub struct Capabilities {
+	pub version: String,
+	pub encoders: HashSet<String>,
+	pub filters: HashSet<String>,
+	pub pix_fmts: HashSet<String>,
+}
+
+static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+
+/// Queries the ffmpeg binary for its version, encoders, filters and pixel formats.
+/// This runs ffmpeg four times, so the result is cached for the lifetime of the process.
+pub fn capabilities() -> Result<&'static Capabilities, FfError> {
+	if let Some(capabilities) = CAPABILITIES.get() {
+		return Ok(capabilities);
+	}
+
+	let capabilities = Capabilities {
+		version: parse_version(&run_ffmpeg(&["-version"])?),
+		encoders: parse_encoders(&run_ffmpeg(&["-hide_banner", "-encoders"])?),
+		filters: parse_filters(&run_ffmpeg(&["-hide_banner", "-filters"])?),
+		pix_fmts: parse_pix_fmts(&run_ffmpeg(&["-hide_banner", "-pix_fmts"])?),
+	};
+
+	Ok(CAPABILITIES.get_or_init(|| capabilities))
+}
+
+fn run_ffmpeg(args: &[&str]) -> Result<String, FfError> {
+	let args = args.iter().map(OsString::from).collect::<Vec<OsString>>();
+	runner().output(&ffmpeg_binary(), &args)
+}
+
+impl Capabilities {
+	#[must_use]
+	pub fn has_encoder(&self, name: &str) -> bool {
+		self.encoders.contains(name)
+	}
+
+	#[must_use]
+	pub fn has_filter(&self, name: &str) -> bool {
+		self.filters.contains(name)
+	}
+
+	#[must_use]
+	pub fn has_pix_fmt(&self, name: &str) -> bool {
+		self.pix_fmts.contains(name)
+	}
+
+	/// Returns [`FfError::MissingCapability`] for the first of the given encoders that isn't available.
+	pub fn require_encoders(&self, names: &[&str]) -> Result<(), FfError> {
+		match names.iter().find(|name| !self.has_encoder(name)) {
+			Some(name) => Err(FfError::MissingCapability { kind: "encoder", name: (*name).to_string() }),
+			None => Ok(()),
+		}
+	}
+
+	/// Returns [`FfError::MissingCapability`] for the first of the given pixel formats that isn't available.
+	pub fn require_pix_fmts(&self, names: &[&str]) -> Result<(), FfError> {
+		match names.iter().find(|name| !self.has_pix_fmt(name)) {
+			Some(name) => Err(FfError::MissingCapability {
+				kind: "pixel format",
+				name: (*name).to_string(),
+			}),
+			None => Ok(()),
+		}
+	}
+
+	/// Returns [`FfError::MissingCapability`] for the first of the given filters that isn't available.
+	pub fn require_filters(&self, names: &[&str]) -> Result<(), FfError> {
+		match names.iter().find(|name| !self.has_filter(name)) {
+			Some(name) => Err(FfError::MissingCapability { kind: "filter", name: (*name).to_string() }),
+			None => Ok(()),
+		}
+	}
+}
+
+/// Returns the `configure` flag that enables the given encoder or filter, for the ones that aren't built by default.
+#[must_use]
+pub fn configure_flag(name: &str) -> Option<&'static str> {
+	match name {
+		"libx264" => Some("--enable-libx264"),
+		"libx265" => Some("--enable-libx265"),
+		"libfdk_aac" => Some("--enable-libfdk-aac"),
+		"libsvtav1" => Some("--enable-libsvtav1"),
+		"zscale" => Some("--enable-libzimg"),
+		"subtitles" | "ass" => Some("--enable-libass"),
+		"drawtext" => Some("--enable-libfreetype"),
+		_ => None,
+	}
+}
+
+/// Parses the output of `ffmpeg -version`, e.g. `ffmpeg version 7.1 Copyright (c) 2000-2024 the FFmpeg developers`.
+#[must_use]
+pub fn parse_version(output: &str) -> String {
+	output
+		.lines()
+		.next()
+		.and_then(|line| line.strip_prefix("ffmpeg version "))
+		.and_then(|rest| rest.split_whitespace().next())
+		.unwrap_or_default()
+		.to_string()
+}
+
+/// Parses the output of `ffmpeg -encoders`. Entries follow a `------` separator and look like
+/// ` V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)`.
+#[must_use]
+pub fn parse_encoders(output: &str) -> HashSet<String> {
+	output
+		.lines()
+		.skip_while(|line| !line.trim_start().starts_with("---"))
+		.skip(1)
+		.filter_map(|line| line.split_whitespace().nth(1))
+		.map(str::to_string)
+		.collect()
+}
+
+/// Parses the output of `ffmpeg -filters`. Entries look like ` TSC zscale            V->V       Apply resizing, …`,
+/// which is what sets them apart from the legend above them.
+#[must_use]
+pub fn parse_filters(output: &str) -> HashSet<String> {
+	output
+		.lines()
+		.filter_map(|line| {
+			let mut fields = line.split_whitespace();
+			let (flags, name, io) = (fields.next()?, fields.next()?, fields.next()?);
+			let is_entry = flags.chars().all(|c| matches!(c, 'T' | 'S' | 'C' | '.')) && io.contains("->");
+			is_entry.then(|| name.to_string())
+		})
+		.collect()
+}
+
+/// Parses the output of `ffmpeg -pix_fmts`. Entries follow a `-----` separator and look like
+/// `IO... yuv420p                3             12      8-8-8`.
+#[must_use]
+pub fn parse_pix_fmts(output: &str) -> HashSet<String> {
+	output
+		.lines()
+		.skip_while(|line| !line.trim_start().starts_with("---"))
+		.skip(1)
+		.filter_map(|line| line.split_whitespace().nth(1))
+		.map(str::to_string)
+		.collect()
+}
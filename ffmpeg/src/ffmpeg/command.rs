@@ -0,0 +1,306 @@
+use std::ffi::{OsStr, OsString};
+use std::time::Duration;
+
+use crate::ffmpeg::filtergraph::{Chain, Graph};
+
+/// Returns `option` for all streams, or `option:spec` for the streams matching the stream specifier.
+fn with_specifier(option: &str, spec: &str) -> String {
+	if spec.is_empty() {
+		option.to_string()
+	} else {
+		format!("{option}:{spec}")
+	}
+}
+
+fn push_options(args: &mut Vec<OsString>, options: &[(String, Option<String>)]) {
+	for (key, value) in options {
+		args.push(format!("-{key}").into());
+		args.extend(value.as_deref().map(OsString::from));
+	}
+}
+
+fn push_pair(args: &mut Vec<OsString>, option: &str, value: impl AsRef<OsStr>) {
+	args.push(option.into());
+	args.push(value.as_ref().to_os_string());
+}
+
+/// An input file along with the options that apply to it, which always end up in front of its `-i`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Input {
+	path: OsString,
+	hwaccel: Option<String>,
+	format: Option<String>,
+	seek: Option<Duration>,
+	duration: Option<Duration>,
+	options: Vec<(String, Option<String>)>,
+}
+
+impl Input {
+	/// Takes the path as an [`OsStr`], so that file names which aren't valid UTF-8 work too.
+	#[must_use]
+	pub fn new(path: impl AsRef<OsStr>) -> Self {
+		Self {
+			path: path.as_ref().to_os_string(),
+			hwaccel: None,
+			format: None,
+			seek: None,
+			duration: None,
+			options: Vec::new(),
+		}
+	}
+
+	/// Decodes the input with the given hardware accelerator (`-hwaccel`).
+	pub fn hwaccel(&mut self, accelerator: impl Into<String>) -> &mut Self {
+		self.hwaccel = Some(accelerator.into());
+		self
+	}
+
+	/// Forces the input format instead of guessing it (`-f`).
+	pub fn format(&mut self, format: impl Into<String>) -> &mut Self {
+		self.format = Some(format.into());
+		self
+	}
+
+	/// Starts reading at the given position (`-ss`).
+	pub fn seek(&mut self, seek: Duration) -> &mut Self {
+		self.seek = Some(seek);
+		self
+	}
+
+	/// Stops reading after the given duration (`-t`).
+	pub fn duration(&mut self, duration: Duration) -> &mut Self {
+		self.duration = Some(duration);
+		self
+	}
+
+	/// Adds any other input option, given without its leading `-`.
+	#[allow(clippy::needless_pass_by_value)]
+	pub fn option(&mut self, key: impl Into<String>, value: impl ToString) -> &mut Self {
+		self.options.push((key.into(), Some(value.to_string())));
+		self
+	}
+
+	fn push_args(&self, args: &mut Vec<OsString>) {
+		if let Some(accelerator) = &self.hwaccel {
+			push_pair(args, "-hwaccel", accelerator);
+		}
+		if let Some(format) = &self.format {
+			push_pair(args, "-f", format);
+		}
+		if let Some(seek) = self.seek {
+			push_pair(args, "-ss", seek.as_secs_f64().to_string());
+		}
+		if let Some(duration) = self.duration {
+			push_pair(args, "-t", duration.as_secs_f64().to_string());
+		}
+		push_options(args, &self.options);
+
+		push_pair(args, "-i", &self.path);
+	}
+}
+
+/// An output file along with the options that apply to it, which always end up in front of its path.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+	path: OsString,
+	maps: Vec<String>,
+	duration: Option<Duration>,
+	codecs: Vec<(String, String)>,
+	options: Vec<(String, Option<String>)>,
+	video_filter: Option<Chain>,
+	audio_filter: Option<Chain>,
+	metadata: Vec<(String, String)>,
+	format: Option<String>,
+}
+
+impl Output {
+	#[must_use]
+	pub fn new(path: impl AsRef<OsStr>) -> Self {
+		Self {
+			path: path.as_ref().to_os_string(),
+			maps: Vec::new(),
+			duration: None,
+			codecs: Vec::new(),
+			options: Vec::new(),
+			video_filter: None,
+			audio_filter: None,
+			metadata: Vec::new(),
+			format: None,
+		}
+	}
+
+	/// Adds the streams matching the specifier to the output (`-map`).
+	pub fn map(&mut self, spec: impl Into<String>) -> &mut Self {
+		self.maps.push(spec.into());
+		self
+	}
+
+	/// Stops writing after the given duration (`-t`).
+	pub fn duration(&mut self, duration: Duration) -> &mut Self {
+		self.duration = Some(duration);
+		self
+	}
+
+	/// Sets the codec for the streams matching the specifier, or for all of them if it's empty (`-c:v libx264`).
+	pub fn codec(&mut self, spec: &str, codec: impl Into<String>) -> &mut Self {
+		self.codecs.push((with_specifier("-c", spec), codec.into()));
+		self
+	}
+
+	/// Adds any other output option, given without its leading `-`.
+	#[allow(clippy::needless_pass_by_value)]
+	pub fn option(&mut self, key: impl Into<String>, value: impl ToString) -> &mut Self {
+		self.options.push((key.into(), Some(value.to_string())));
+		self
+	}
+
+	/// Adds an output option that doesn't take a value, like `an`.
+	pub fn flag(&mut self, key: impl Into<String>) -> &mut Self {
+		self.options.push((key.into(), None));
+		self
+	}
+
+	/// Filters the video stream (`-vf`). Empty chains are left out.
+	pub fn video_filter(&mut self, chain: Chain) -> &mut Self {
+		self.video_filter = Some(chain).filter(|chain| !chain.is_empty());
+		self
+	}
+
+	/// Filters the audio stream (`-af`). Empty chains are left out.
+	pub fn audio_filter(&mut self, chain: Chain) -> &mut Self {
+		self.audio_filter = Some(chain).filter(|chain| !chain.is_empty());
+		self
+	}
+
+	/// Sets a metadata tag of the output file.
+	pub fn metadata(&mut self, key: &str, value: &str) -> &mut Self {
+		self.metadata
+			.push(("-metadata".to_string(), format!("{key}={value}")));
+		self
+	}
+
+	/// Sets a metadata tag of the output streams matching the specifier, or of all of them if it's empty.
+	pub fn stream_metadata(&mut self, spec: &str, key: &str, value: &str) -> &mut Self {
+		self.metadata
+			.push((with_specifier("-metadata:s", spec), format!("{key}={value}")));
+		self
+	}
+
+	/// Forces the output format instead of guessing it from the file extension (`-f`).
+	pub fn format(&mut self, format: impl Into<String>) -> &mut Self {
+		self.format = Some(format.into());
+		self
+	}
+
+	fn push_args(&self, args: &mut Vec<OsString>) {
+		for map in &self.maps {
+			push_pair(args, "-map", map);
+		}
+		if let Some(duration) = self.duration {
+			push_pair(args, "-t", duration.as_secs_f64().to_string());
+		}
+		for (option, codec) in &self.codecs {
+			push_pair(args, option, codec);
+		}
+		push_options(args, &self.options);
+		if let Some(chain) = &self.video_filter {
+			push_pair(args, "-vf", chain.to_string());
+		}
+		if let Some(chain) = &self.audio_filter {
+			push_pair(args, "-af", chain.to_string());
+		}
+		for (option, value) in &self.metadata {
+			push_pair(args, option, value);
+		}
+		if let Some(format) = &self.format {
+			push_pair(args, "-f", format);
+		}
+
+		args.push(self.path.clone());
+	}
+}
+
+/// A complete ffmpeg invocation. Options are kept with the input or output they belong to
+/// and only put in order when the arguments are built, so they can be added in any order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FfmpegCommand {
+	options: Vec<(String, Option<String>)>,
+	inputs: Vec<Input>,
+	filter_complex: Option<Graph>,
+	outputs: Vec<Output>,
+}
+
+impl FfmpegCommand {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn hide_banner(&mut self) -> &mut Self {
+		self.flag("hide_banner")
+	}
+
+	/// Sets how much ffmpeg logs (`-loglevel`).
+	pub fn loglevel(&mut self, level: &str) -> &mut Self {
+		self.option("loglevel", level)
+	}
+
+	/// Overwrites existing output files without asking (`-y`).
+	pub fn overwrite(&mut self) -> &mut Self {
+		self.flag("y")
+	}
+
+	/// Adds any other global option, given without its leading `-`.
+	#[allow(clippy::needless_pass_by_value)]
+	pub fn option(&mut self, key: impl Into<String>, value: impl ToString) -> &mut Self {
+		self.options.push((key.into(), Some(value.to_string())));
+		self
+	}
+
+	/// Adds a global option that doesn't take a value.
+	pub fn flag(&mut self, key: impl Into<String>) -> &mut Self {
+		self.options.push((key.into(), None));
+		self
+	}
+
+	/// Adds an input. Inputs are numbered in the order they're added, starting at 0.
+	pub fn input(&mut self, input: Input) -> &mut Self {
+		self.inputs.push(input);
+		self
+	}
+
+	/// Returns the number of inputs added so far, which is also the index the next input will get.
+	#[must_use]
+	pub fn input_count(&self) -> usize {
+		self.inputs.len()
+	}
+
+	pub fn filter_complex(&mut self, graph: Graph) -> &mut Self {
+		self.filter_complex = Some(graph);
+		self
+	}
+
+	pub fn output(&mut self, output: Output) -> &mut Self {
+		self.outputs.push(output);
+		self
+	}
+
+	/// Builds the arguments to pass to ffmpeg.
+	#[must_use]
+	pub fn args(&self) -> Vec<OsString> {
+		let mut args = Vec::new();
+
+		push_options(&mut args, &self.options);
+		for input in &self.inputs {
+			input.push_args(&mut args);
+		}
+		if let Some(graph) = &self.filter_complex {
+			push_pair(&mut args, "-filter_complex", graph.to_string());
+		}
+		for output in &self.outputs {
+			output.push_args(&mut args);
+		}
+
+		args
+	}
+}
@@ -0,0 +1,135 @@
+use std::path::Path;
+use std::time::Duration;
+
+use crate::ffmpeg::binaries::ffmpeg_binary;
+use crate::ffmpeg::command::{FfmpegCommand, Input, Output};
+use crate::ffmpeg::enums::Crop;
+use crate::ffmpeg::error::FfError;
+use crate::ffmpeg::ffprobe_struct::Stream;
+use crate::ffmpeg::filtergraph::{Chain, Filter};
+use crate::ffmpeg::runner::runner;
+
+/// How many windows spread across the input are sampled.
+const SAMPLE_WINDOWS: u32 = 5;
+/// How long each sampled window is.
+const WINDOW_LENGTH: Duration = Duration::from_secs(2);
+
+/// Parses the frame metadata that `metadata=mode=print` writes for `cropdetect` into one rectangle per frame.
+/// Frames without a complete rectangle, like the ones cropdetect skips at the start, are left out.
+#[must_use]
+pub fn parse_cropdetect(output: &str) -> Vec<Crop> {
+	fn finish(crops: &mut Vec<Crop>, frame: [Option<u64>; 4]) {
+		if let [Some(width), Some(height), Some(x), Some(y)] = frame {
+			crops.push(Crop { width, height, x: Some(x), y: Some(y) });
+		}
+	}
+
+	let mut crops = Vec::new();
+	let mut frame = [None; 4];
+	for line in output.lines() {
+		if line.starts_with("frame:") {
+			finish(&mut crops, frame);
+			frame = [None; 4];
+			continue;
+		}
+
+		let Some((key, value)) = line
+			.trim()
+			.strip_prefix("lavfi.cropdetect.")
+			.and_then(|entry| entry.split_once('='))
+		else {
+			continue;
+		};
+		let slot = match key {
+			"w" => 0,
+			"h" => 1,
+			"x" => 2,
+			"y" => 3,
+			_ => continue,
+		};
+		// cropdetect reports negative sizes for frames that are entirely black
+		frame[slot] = value.parse::<u64>().ok();
+	}
+	finish(&mut crops, frame);
+
+	crops
+}
+
+/// Returns the rectangle that occurs most often. Ties go to the larger rectangle, so that nothing is cut off
+/// because a few windows happened to be dark around the edges.
+#[must_use]
+pub fn most_common_crop(crops: &[Crop]) -> Option<Crop> {
+	let mut counts: Vec<(&Crop, usize)> = Vec::new();
+	for crop in crops {
+		match counts.iter_mut().find(|(c, _)| *c == crop) {
+			Some((_, count)) => *count += 1,
+			None => counts.push((crop, 1)),
+		}
+	}
+
+	counts
+		.into_iter()
+		.max_by_key(|(crop, count)| (*count, crop.width * crop.height))
+		.map(|(crop, _)| crop.clone())
+}
+
+/// Runs `cropdetect` over several windows spread across the input and returns the rectangle most of them agree on.
+/// Every window contributes the rectangle cropdetect settled on by its last frame, which covers the whole window.
+///
+/// Returns [None] if the stream has no black bars, and [`FfError::InvalidValue`] if the detected rectangle
+/// doesn't fit into the stream's frames.
+pub fn detect_crop(input: &Path, stream: &Stream, duration: Duration) -> Result<Option<Crop>, FfError> {
+	let mut windows = Vec::new();
+	for i in 1..=SAMPLE_WINDOWS {
+		// stay clear of the very start and end, which are often black or have credits
+		let start = (duration * i / (SAMPLE_WINDOWS + 1)).saturating_sub(WINDOW_LENGTH / 2);
+
+		let mut input = Input::new(input);
+		input.seek(start).duration(WINDOW_LENGTH);
+
+		let mut output = Output::new("-");
+		output
+			.map(format!("0:{}", stream.index))
+			.video_filter(
+				Chain::new()
+					.filter(Filter::new("cropdetect").arg("round", 2))
+					.filter(
+						Filter::new("metadata")
+							.arg("mode", "print")
+							.arg("file", "pipe:1"),
+					),
+			)
+			.format("null");
+
+		let mut command = FfmpegCommand::new();
+		command
+			.hide_banner()
+			.loglevel("error")
+			.input(input)
+			.output(output);
+
+		let stdout = runner().output(&ffmpeg_binary(), &command.args())?;
+		windows.extend(parse_cropdetect(&stdout).pop());
+	}
+
+	let Some(crop) = most_common_crop(&windows) else {
+		return Ok(None);
+	};
+
+	if let (Some(frame_width), Some(frame_height)) = (stream.width, stream.height) {
+		if crop.width == 0 || crop.height == 0 || !crop.fits(frame_width, frame_height) {
+			return Err(FfError::InvalidValue(format!(
+				"cropdetect found {}x{} at {};{}, which doesn't fit into the {frame_width}x{frame_height} video",
+				crop.width,
+				crop.height,
+				crop.x.unwrap_or_default(),
+				crop.y.unwrap_or_default()
+			)));
+		}
+		if crop.width == frame_width && crop.height == frame_height {
+			return Ok(None);
+		}
+	}
+
+	Ok(Some(crop))
+}
@@ -1,3 +1,5 @@
+use crate::ffmpeg::size::AspectRatio;
+
 #[derive(clap::ValueEnum, Clone, Default, Debug, PartialEq)]
 pub enum ScaleMode {
 	Nearest,
@@ -13,6 +15,13 @@ pub enum ScaleMode {
 	Spline,
 }
 
+#[derive(clap::ValueEnum, Clone, Default, Debug, PartialEq)]
+pub enum AspectMode {
+	#[default]
+	Crop,
+	Pad,
+}
+
 #[derive(clap::ValueEnum, Clone, Default, Debug, PartialEq)]
 pub enum VideoCodec {
 	#[default]
@@ -29,12 +38,68 @@ pub enum OptimizeTarget {
 	PsVita,
 }
 
+/// A crop rectangle in pixels. A width of 0 keeps the input's width, and a missing offset centers the rectangle.
 #[derive(Clone, Default, Debug, PartialEq, Eq)]
 pub struct Crop {
 	pub width: u64,
 	pub height: u64,
-	pub x: u64,
-	pub y: u64,
+	pub x: Option<u64>,
+	pub y: Option<u64>,
+}
+
+/// A crop length, either in pixels or as a percentage of the frame's width or height.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CropLength {
+	Pixels(u64),
+	Percent(f64),
+}
+
+/// Where a crop rectangle sits in the frame.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum Anchor {
+	#[default]
+	Center,
+	Top,
+	Bottom,
+	Left,
+	Right,
+	TopLeft,
+	TopRight,
+	BottomLeft,
+	BottomRight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CropPosition {
+	Offset(CropLength, CropLength),
+	Anchor(Anchor),
+}
+
+/// A crop that's resolved against the stream's dimensions once they're known.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CropRegion {
+	/// `H`, `WxH`, `WxH,X;Y` or `WxH,anchor`. A missing width keeps the input's.
+	Rect {
+		width: Option<CropLength>,
+		height: CropLength,
+		position: CropPosition,
+	},
+	/// The largest rectangle with the display aspect ratio, like `16:9` or `1:1,top`.
+	Aspect { ratio: AspectRatio, anchor: Anchor },
+	/// Trims the edges, like `t=10,b=10,l=4,r=4`.
+	Trim {
+		top: CropLength,
+		bottom: CropLength,
+		left: CropLength,
+		right: CropLength,
+	},
+}
+
+/// What `--crop` was given: a region, or `auto` to detect black bars.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CropSpec {
+	Auto,
+	Region(CropRegion),
 }
 
 #[derive(clap::ValueEnum, Clone, Default, Debug, PartialEq)]
@@ -65,3 +130,13 @@ pub enum BarcodeMode {
 	Frames,
 	Colors,
 }
+
+#[derive(clap::ValueEnum, Clone, Default, Debug, PartialEq)]
+pub enum TonemapMode {
+	#[default]
+	Hable,
+	Mobius,
+	Reinhard,
+	Bt2390,
+	Clip,
+}
@@ -1,7 +1,12 @@
 use crate::ffmpeg::enums::*;
-use anyhow::{Result, anyhow};
+use crate::ffmpeg::error::FfError;
+use crate::ffmpeg::ffprobe_struct::Stream;
+use crate::ffmpeg::filtergraph::Filter;
+use crate::ffmpeg::size::AspectRatio;
 use regex::Regex;
 use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
 
 impl fmt::Display for ScaleMode {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -66,25 +71,312 @@ pub fn crf_with_garbage(&self, garbage: u8) -> u8 {
 	}
 }
 
+impl OptimizeTarget {
+	/// Returns the `-maxrate` and `-bufsize` values the target's video stream has to adhere to.
+	#[must_use]
+	pub fn rate_limits(&self) -> (&str, &str) {
+		match self {
+			OptimizeTarget::Ipod5 => ("768K", "2M"), // apple: 768 kbps, actual level limit
+			OptimizeTarget::Ipod => ("2.5M", "5M"),  // apple: 2.5 mbps
+			OptimizeTarget::Psp => ("3M", "6M"),     // needs verification
+			OptimizeTarget::PsVita => ("10M", "20M"),
+		}
+	}
+}
+
+/// A crop length in pixels or as a percentage, like `1080` or `12.5%`.
+const CROP_LENGTH: &str = r"\d+(?:\.\d+)?%|\d+";
+
+static CROP_LENGTH_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(&format!("^(?:{CROP_LENGTH})$")).unwrap());
+static CROP_SIZE_RE: LazyLock<Regex> =
+	LazyLock::new(|| Regex::new(&format!(r"^(?:(?P<w>{CROP_LENGTH})[x:])?(?P<h>{CROP_LENGTH})$")).unwrap());
+static CROP_OFFSET_RE: LazyLock<Regex> = LazyLock::new(|| {
+	Regex::new(&format!(
+		r"^(?P<w>{CROP_LENGTH})[x:](?P<h>{CROP_LENGTH})[,;:x](?P<x>{CROP_LENGTH})[,;:x](?P<y>{CROP_LENGTH})$"
+	))
+	.unwrap()
+});
+
 impl Crop {
-	pub fn new<S: Into<String>>(crop_str: S) -> Result<Self> {
+	/// Parses a crop in pixels: `H`, `WxH` or `WxH,X;Y`. Other forms need the stream's dimensions,
+	/// so they're parsed into a [`CropRegion`] instead.
+	pub fn new<S: Into<String>>(crop_str: S) -> Result<Self, FfError> {
 		let crop_str = crop_str.into();
-		let re = Regex::new(r"(-?\d+)").unwrap();
+		let invalid = || FfError::InvalidValue(format!("\"{crop_str}\" is not a valid crop value"));
+		let pixels = |length: CropLength| match length {
+			CropLength::Pixels(pixels) => Ok(pixels),
+			CropLength::Percent(_) => Err(invalid()),
+		};
+
+		let CropRegion::Rect { width, height, position } = crop_str.parse::<CropRegion>()? else {
+			return Err(invalid());
+		};
+		let (x, y) = match position {
+			CropPosition::Offset(x, y) => (Some(pixels(x)?), Some(pixels(y)?)),
+			CropPosition::Anchor(Anchor::Center) => (None, None),
+			CropPosition::Anchor(_) => return Err(invalid()),
+		};
+
+		Ok(Crop {
+			width: width.map(pixels).transpose()?.unwrap_or(0),
+			height: pixels(height)?,
+			x,
+			y,
+		})
+	}
+
+	/// Returns whether the rectangle lies within a frame of the given size.
+	#[must_use]
+	pub fn fits(&self, frame_width: u64, frame_height: u64) -> bool {
+		// a zero width or height keeps the input's
+		let width = if self.width > 0 { self.width } else { frame_width };
+		let height = if self.height > 0 { self.height } else { frame_height };
+		self.x.unwrap_or(0) + width <= frame_width && self.y.unwrap_or(0) + height <= frame_height
+	}
+
+	/// Returns the `crop` filter for this crop. Offsets are passed on as they are, 0 included, since a detected
+	/// crop is often off-center; only a crop without an offset is centered by ffmpeg.
+	#[must_use]
+	pub fn filter(&self) -> Filter {
+		let mut filter = Filter::new("crop");
+
+		// a zero width or height keeps the input's
+		if self.width > 0 {
+			filter = filter.arg("w", self.width);
+		}
+		if self.height > 0 {
+			filter = filter.arg("h", self.height);
+		}
+		if let Some(x) = self.x {
+			filter = filter.arg("x", x);
+		}
+		if let Some(y) = self.y {
+			filter = filter.arg("y", y);
+		}
+
+		filter
+	}
+}
+
+impl FromStr for Crop {
+	type Err = FfError;
+
+	fn from_str(s: &str) -> Result<Self, FfError> {
+		Crop::new(s)
+	}
+}
+
+impl CropLength {
+	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+	fn resolve(self, total: u64) -> u64 {
+		match self {
+			CropLength::Pixels(pixels) => pixels,
+			CropLength::Percent(percent) => (total as f64 * percent / 100.0).floor() as u64,
+		}
+	}
+
+	/// Like [`CropLength::resolve`], but rounds percentages down to an even size, which chroma subsampling needs.
+	fn resolve_size(self, total: u64) -> u64 {
+		match self {
+			CropLength::Pixels(pixels) => pixels,
+			CropLength::Percent(_) => self.resolve(total) & !1,
+		}
+	}
+
+	fn is_zero(self) -> bool {
+		match self {
+			CropLength::Pixels(pixels) => pixels == 0,
+			CropLength::Percent(percent) => percent == 0.0,
+		}
+	}
+}
+
+impl FromStr for CropLength {
+	type Err = FfError;
+
+	fn from_str(s: &str) -> Result<Self, FfError> {
+		let invalid = || FfError::InvalidValue(format!("\"{s}\" is not a valid crop length"));
+		if !CROP_LENGTH_RE.is_match(s) {
+			return Err(invalid());
+		}
 
-		let numbers = re
-			.find_iter(crop_str.as_str())
-			.map(|s| {
-				s.as_str()
-					.parse::<u64>()
-					.map_err(|_| anyhow!("\"{crop_str}\" is not a valid crop value"))
-			})
-			.collect::<Result<Vec<u64>, anyhow::Error>>()?;
+		match s.strip_suffix('%') {
+			Some(percent) => percent
+				.parse::<f64>()
+				.ok()
+				.filter(|p| *p <= 100.0)
+				.map(CropLength::Percent)
+				.ok_or_else(invalid),
+			None => s
+				.parse::<u64>()
+				.map(CropLength::Pixels)
+				.map_err(|_| invalid()),
+		}
+	}
+}
+
+impl Anchor {
+	/// Returns the offset of a `width`×`height` rectangle placed at this anchor.
+	fn place(self, frame_width: u64, frame_height: u64, width: u64, height: u64) -> (u64, u64) {
+		let (free_x, free_y) = (frame_width.saturating_sub(width), frame_height.saturating_sub(height));
+		let x = match self {
+			Anchor::Left | Anchor::TopLeft | Anchor::BottomLeft => 0,
+			Anchor::Right | Anchor::TopRight | Anchor::BottomRight => free_x,
+			Anchor::Center | Anchor::Top | Anchor::Bottom => free_x / 2,
+		};
+		let y = match self {
+			Anchor::Top | Anchor::TopLeft | Anchor::TopRight => 0,
+			Anchor::Bottom | Anchor::BottomLeft | Anchor::BottomRight => free_y,
+			Anchor::Center | Anchor::Left | Anchor::Right => free_y / 2,
+		};
+		(x, y)
+	}
+}
+
+impl FromStr for Anchor {
+	type Err = FfError;
+
+	fn from_str(s: &str) -> Result<Self, FfError> {
+		match s {
+			"center" => Ok(Anchor::Center),
+			"top" => Ok(Anchor::Top),
+			"bottom" => Ok(Anchor::Bottom),
+			"left" => Ok(Anchor::Left),
+			"right" => Ok(Anchor::Right),
+			"top-left" => Ok(Anchor::TopLeft),
+			"top-right" => Ok(Anchor::TopRight),
+			"bottom-left" => Ok(Anchor::BottomLeft),
+			"bottom-right" => Ok(Anchor::BottomRight),
+			_ => Err(FfError::InvalidValue(format!("\"{s}\" is not a valid crop anchor"))),
+		}
+	}
+}
+
+impl CropRegion {
+	/// Turns the region into a rectangle in pixels for the given stream and checks that it fits into its frames.
+	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+	pub fn resolve(&self, stream: &Stream) -> Result<Crop, FfError> {
+		let (Some(frame_width), Some(frame_height)) = (stream.width, stream.height) else {
+			return Err(FfError::InvalidValue(
+				"The crop can't be applied because ffprobe didn't report the video's size".to_string(),
+			));
+		};
+
+		let (width, height, position) = match *self {
+			CropRegion::Rect { width, height, position } => (
+				width.map_or(frame_width, |w| w.resolve_size(frame_width)),
+				height.resolve_size(frame_height),
+				position,
+			),
+			CropRegion::Aspect { ratio, anchor } => {
+				let ratio = ratio.width as f64 / ratio.height as f64 / stream.sample_aspect_ratio();
+				let width = (frame_height as f64 * ratio).floor() as u64 & !1;
+				let height = (frame_width as f64 / ratio).floor() as u64 & !1;
+				(
+					width.min(frame_width),
+					height.min(frame_height),
+					CropPosition::Anchor(anchor),
+				)
+			}
+			CropRegion::Trim { top, bottom, left, right } => {
+				let (top, bottom) = (top.resolve(frame_height), bottom.resolve(frame_height));
+				let (left, right) = (left.resolve(frame_width), right.resolve(frame_width));
+				(
+					frame_width.saturating_sub(left + right),
+					frame_height.saturating_sub(top + bottom),
+					CropPosition::Offset(CropLength::Pixels(left), CropLength::Pixels(top)),
+				)
+			}
+		};
+
+		let (x, y) = match position {
+			CropPosition::Offset(x, y) => (x.resolve(frame_width), y.resolve(frame_height)),
+			CropPosition::Anchor(anchor) => anchor.place(frame_width, frame_height, width, height),
+		};
 
-		match numbers.as_slice() {
-			[h] if *h > 0 => Ok(Crop { height: *h, ..Crop::default() }),
-			[w, h] if *w > 0 && *h > 0 => Ok(Crop { width: *w, height: *h, ..Crop::default() }),
-			[w, h, x, y] if *w > 0 && *h > 0 => Ok(Crop { width: *w, height: *h, x: *x, y: *y }),
-			_ => anyhow::bail!("\"{crop_str}\" is not a valid crop value"),
+		let crop = Crop { width, height, x: Some(x), y: Some(y) };
+		if width == 0 || height == 0 || !crop.fits(frame_width, frame_height) {
+			return Err(FfError::InvalidValue(format!(
+				"Cropping {width}x{height} at {x};{y} doesn't fit into the {frame_width}x{frame_height} video"
+			)));
+		}
+
+		Ok(crop)
+	}
+}
+
+impl FromStr for CropRegion {
+	type Err = FfError;
+
+	fn from_str(s: &str) -> Result<Self, FfError> {
+		let invalid = || FfError::InvalidValue(format!("\"{s}\" is not a valid crop value"));
+
+		if s.contains('=') {
+			let mut edges = [None; 4];
+			for edge in s.split(',') {
+				let (key, value) = edge.split_once('=').ok_or_else(invalid)?;
+				let slot = match key {
+					"t" | "top" => 0,
+					"b" | "bottom" => 1,
+					"l" | "left" => 2,
+					"r" | "right" => 3,
+					_ => return Err(invalid()),
+				};
+				if edges[slot].is_some() {
+					return Err(invalid());
+				}
+				edges[slot] = Some(value.parse::<CropLength>()?);
+			}
+
+			let [top, bottom, left, right] = edges.map(|edge| edge.unwrap_or(CropLength::Pixels(0)));
+			return Ok(CropRegion::Trim { top, bottom, left, right });
+		}
+
+		// a trailing anchor like `,top-left`
+		let (rect, anchor) = match s
+			.rsplit_once(',')
+			.and_then(|(rect, anchor)| Some((rect, anchor.parse::<Anchor>().ok()?)))
+		{
+			Some((rect, anchor)) => (rect, Some(anchor)),
+			None => (s, None),
+		};
+
+		// `W:H` is an aspect ratio like `--aspect`, sizes in pixels are written as `WxH`
+		if let Ok(ratio) = rect.parse::<AspectRatio>() {
+			return Ok(CropRegion::Aspect { ratio, anchor: anchor.unwrap_or_default() });
+		}
+
+		let (groups, position) = if let Some(groups) = CROP_SIZE_RE.captures(rect) {
+			(groups, CropPosition::Anchor(anchor.unwrap_or_default()))
+		} else if let Some(groups) = CROP_OFFSET_RE.captures(rect)
+			&& anchor.is_none()
+		{
+			let position = CropPosition::Offset(groups["x"].parse()?, groups["y"].parse()?);
+			(groups, position)
+		} else {
+			return Err(invalid());
+		};
+
+		let width = groups
+			.name("w")
+			.map(|w| w.as_str().parse::<CropLength>())
+			.transpose()?;
+		let height = groups["h"].parse::<CropLength>()?;
+		if height.is_zero() || width.is_some_and(CropLength::is_zero) {
+			return Err(invalid());
+		}
+
+		Ok(CropRegion::Rect { width, height, position })
+	}
+}
+
+impl FromStr for CropSpec {
+	type Err = FfError;
+
+	fn from_str(s: &str) -> Result<Self, FfError> {
+		match s {
+			"auto" => Ok(CropSpec::Auto),
+			_ => s.parse::<CropRegion>().map(CropSpec::Region),
 		}
 	}
 }
@@ -98,11 +390,12 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 			(w, h) => format!("w={w}:h={h}"),
 		};
 
-		if self.x > 0 {
-			crop_str += format!(":x={}", self.x).as_str();
+		// without an offset, ffmpeg centers the rectangle
+		if let Some(x) = self.x {
+			crop_str += format!(":x={x}").as_str();
 		}
-		if self.y > 0 {
-			crop_str += format!(":y={}", self.y).as_str();
+		if let Some(y) = self.y {
+			crop_str += format!(":y={y}").as_str();
 		}
 
 		write!(f, "{crop_str}")
@@ -134,3 +427,15 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		}
 	}
 }
+
+impl fmt::Display for TonemapMode {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			TonemapMode::Hable => write!(f, "hable"),
+			TonemapMode::Mobius => write!(f, "mobius"),
+			TonemapMode::Reinhard => write!(f, "reinhard"),
+			TonemapMode::Bt2390 => write!(f, "bt.2390"),
+			TonemapMode::Clip => write!(f, "clip"),
+		}
+	}
+}
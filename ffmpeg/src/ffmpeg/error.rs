@@ -0,0 +1,114 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::ffmpeg::binaries::{FFMPEG_ENV, FFPROBE_ENV};
+use crate::ffmpeg::capabilities::configure_flag;
+use crate::ffmpeg::failure::FailureKind;
+use crate::ffmpeg::timestamps::{TimestampFormat, format_ffmpeg_timestamp};
+
+#[derive(Debug)]
+pub enum FfError {
+	BinaryNotFound {
+		binary: String,
+	},
+	SpawnFailed {
+		binary: String,
+		source: io::Error,
+	},
+	NonZeroExit {
+		binary: String,
+		code: Option<i32>,
+		stderr: String,
+	},
+	ProbeParse(String),
+	InvalidPath(PathBuf),
+	InvalidValue(String),
+	MissingStream(String),
+	MissingCapability {
+		kind: &'static str,
+		name: String,
+	},
+	Cancelled,
+	Stalled {
+		timeout: Duration,
+		last_position: Option<Duration>,
+	},
+	TimedOut {
+		limit: Duration,
+	},
+	IoErr(io::Error),
+}
+
+impl FfError {
+	/// Turns the error returned by [`std::process::Command::spawn`] into either
+	/// [`FfError::BinaryNotFound`] or [`FfError::SpawnFailed`].
+	pub(crate) fn from_spawn_error(binary: &Path, e: io::Error) -> Self {
+		let binary = binary.display().to_string();
+		if e.kind() == io::ErrorKind::NotFound {
+			FfError::BinaryNotFound { binary }
+		} else {
+			FfError::SpawnFailed { binary, source: e }
+		}
+	}
+
+	/// Classifies why ffmpeg or ffprobe exited with an error, based on what it printed to stderr.
+	#[must_use]
+	pub fn failure_kind(&self) -> Option<FailureKind> {
+		match self {
+			FfError::NonZeroExit { stderr, .. } => FailureKind::classify(stderr),
+			_ => None,
+		}
+	}
+}
+
+#[rustfmt::skip]
+impl Display for FfError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			FfError::BinaryNotFound { binary } => write!(f, "{binary} could not be found, make sure it is installed and in your PATH or set {FFMPEG_ENV}/{FFPROBE_ENV}"),
+			FfError::SpawnFailed { binary, source } => write!(f, "Failed to run {binary}: {source}"),
+			FfError::NonZeroExit { binary, code: Some(code), stderr } if stderr.is_empty() => write!(f, "{binary} exited with status code {code}"),
+			FfError::NonZeroExit { binary, code: None, stderr } if stderr.is_empty() => write!(f, "{binary} was terminated by a signal"),
+			FfError::NonZeroExit { stderr, .. } => match FailureKind::classify(stderr) {
+				Some(kind) => write!(f, "{stderr}\n{kind}: {}", kind.suggestion()),
+				None => write!(f, "{stderr}"),
+			},
+			FfError::ProbeParse(msg) => write!(f, "Couldn't parse ffprobe output: {msg}"),
+			FfError::InvalidPath(path) => write!(f, "\"{}\" is not a valid UTF-8 path", path.display()),
+			FfError::InvalidValue(msg) | FfError::MissingStream(msg) => write!(f, "{msg}"),
+			FfError::MissingCapability { kind, name } => match configure_flag(name) {
+				Some(flag) => write!(f, "Your ffmpeg build has no {name} {kind}, it needs to be built with {flag}"),
+				None => write!(f, "Your ffmpeg build has no {name} {kind}"),
+			},
+			FfError::Cancelled => write!(f, "Cancelled"),
+			FfError::Stalled { timeout, last_position: Some(position) } => write!(f, "ffmpeg made no progress for {}s and was killed, the last known position was {}", timeout.as_secs(), format_ffmpeg_timestamp(*position, &TimestampFormat::TwoDigits)),
+			FfError::Stalled { timeout, last_position: None } => write!(f, "ffmpeg made no progress for {}s and was killed before reporting any", timeout.as_secs()),
+			FfError::TimedOut { limit } => write!(f, "ffmpeg was killed after running for longer than {}s", limit.as_secs()),
+			FfError::IoErr(e) => write!(f, "io error: {e}"),
+		}
+	}
+}
+
+impl std::error::Error for FfError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			FfError::SpawnFailed { source, .. } | FfError::IoErr(source) => Some(source),
+			_ => None,
+		}
+	}
+}
+
+impl From<io::Error> for FfError {
+	fn from(e: io::Error) -> Self {
+		FfError::IoErr(e)
+	}
+}
+
+/// Returns the path as a `&str` for places that need text, like filter options, or [`FfError::InvalidPath`]
+/// if it isn't valid UTF-8. Argument lists take paths as they are.
+pub fn path_to_str(path: &Path) -> Result<&str, FfError> {
+	path.to_str()
+		.ok_or_else(|| FfError::InvalidPath(path.to_path_buf()))
+}
@@ -0,0 +1,135 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+
+/// Keeps the last few lines of a process's stderr, so that they can be shown again once it fails.
+#[derive(Debug)]
+pub struct StderrTail {
+	lines: VecDeque<String>,
+	capacity: usize,
+	partial: Vec<u8>,
+}
+
+impl StderrTail {
+	#[must_use]
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			lines: VecDeque::with_capacity(capacity),
+			capacity,
+			partial: Vec::new(),
+		}
+	}
+
+	/// Adds a chunk of output, which doesn't need to end on a line break.
+	/// Lines redrawn with `\r` count as separate lines, blank ones are skipped.
+	pub fn push(&mut self, chunk: &[u8]) {
+		for &byte in chunk {
+			if byte == b'\n' || byte == b'\r' {
+				let line = String::from_utf8_lossy(&self.partial)
+					.trim_end()
+					.to_string();
+				self.partial.clear();
+				self.push_line(line);
+			} else {
+				self.partial.push(byte);
+			}
+		}
+	}
+
+	fn push_line(&mut self, line: String) {
+		if line.is_empty() || self.capacity == 0 {
+			return;
+		}
+		if self.lines.len() == self.capacity {
+			self.lines.pop_front();
+		}
+		self.lines.push_back(line);
+	}
+
+	/// Returns the kept lines, including one that hasn't been terminated yet.
+	#[must_use]
+	pub fn into_string(mut self) -> String {
+		let partial = String::from_utf8_lossy(&self.partial)
+			.trim_end()
+			.to_string();
+		self.push_line(partial);
+
+		Vec::from(self.lines).join("\n")
+	}
+}
+
+/// Common reasons for ffmpeg to fail, recognized by the messages it prints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+	UnknownEncoder,
+	MissingFilter,
+	InvalidOption,
+	PermissionDenied,
+	DiskFull,
+	InvalidData,
+}
+
+impl FailureKind {
+	/// Looks for a known error message in ffmpeg's stderr. The last matching line wins,
+	/// since earlier ones are more likely to be warnings that ffmpeg recovered from.
+	#[must_use]
+	pub fn classify(stderr: &str) -> Option<Self> {
+		stderr.lines().rev().find_map(Self::classify_line)
+	}
+
+	fn classify_line(line: &str) -> Option<Self> {
+		let kind = if line.contains("Unknown encoder") || line.contains("Encoder not found") {
+			FailureKind::UnknownEncoder
+		} else if line.contains("No such filter") || line.contains("Filter not found") {
+			FailureKind::MissingFilter
+		} else if line.contains("Unrecognized option")
+			|| line.contains("Option not found")
+			|| line.contains("Error setting option")
+		{
+			FailureKind::InvalidOption
+		} else if line.contains("Permission denied") {
+			FailureKind::PermissionDenied
+		} else if line.contains("No space left on device") {
+			FailureKind::DiskFull
+		} else if line.contains("Invalid data found when processing input") || line.contains("moov atom not found") {
+			FailureKind::InvalidData
+		} else {
+			return None;
+		};
+
+		Some(kind)
+	}
+
+	#[must_use]
+	pub fn suggestion(&self) -> &'static str {
+		match self {
+			FailureKind::UnknownEncoder => {
+				"Your ffmpeg build doesn't include this encoder, check `ffmpeg -encoders` or pick a different codec"
+			}
+			FailureKind::MissingFilter => {
+				"Your ffmpeg build doesn't include this filter, check `ffmpeg -filters` or use a full build of ffmpeg"
+			}
+			FailureKind::InvalidOption => {
+				"This ffmpeg version doesn't support one of the options, try updating it or run with --debug to see the full command"
+			}
+			FailureKind::PermissionDenied => "Make sure the input is readable and the output directory is writable",
+			FailureKind::DiskFull => "Free up some space or write the output to a different drive",
+			FailureKind::InvalidData => {
+				"The input is damaged or isn't a format ffmpeg understands, check the path or try remuxing it first"
+			}
+		}
+	}
+}
+
+#[rustfmt::skip]
+impl Display for FailureKind {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			FailureKind::UnknownEncoder => write!(f, "unknown encoder"),
+			FailureKind::MissingFilter => write!(f, "missing filter"),
+			FailureKind::InvalidOption => write!(f, "invalid option"),
+			FailureKind::PermissionDenied => write!(f, "permission denied"),
+			FailureKind::DiskFull => write!(f, "disk full"),
+			FailureKind::InvalidData => write!(f, "invalid data in input"),
+		}
+	}
+}
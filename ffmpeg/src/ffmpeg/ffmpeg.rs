@@ -1,173 +1,181 @@
+use crate::ffmpeg::binaries::ffmpeg_binary;
+use crate::ffmpeg::cancel::is_cancelled;
+use crate::ffmpeg::command::FfmpegCommand;
+use crate::ffmpeg::error::FfError;
+use crate::ffmpeg::progress::{ProgressEvent, ProgressTotal, estimate_remaining, format_eta};
+use crate::ffmpeg::report::{Report, emit, json_output};
+use crate::ffmpeg::runner::runner;
+use crate::ffmpeg::script::{RunMode, ScriptStep, Shell, format_command, record, run_mode};
 use crate::ffmpeg::timestamps::TimestampFormat;
-use crate::ffmpeg::timestamps::{format_ffmpeg_timestamp, parse_ffmpeg_duration};
-use anyhow::{Context, Result};
-use std::fs::File;
+use crate::ffmpeg::timestamps::format_ffmpeg_timestamp;
+use crate::{out, outln};
+use std::ffi::OsString;
 use std::io;
-use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
-use std::process::Command;
-use std::thread::sleep;
-use std::time::{Duration, Instant};
-use tempfile::Builder;
-
-pub fn ffmpeg(in_args: &[String], accelerator: Option<String>, show_progress: bool, debug: bool) -> Result<()> {
-	let temp_file = Builder::new()
-		.prefix("ffmpeg")
-		.suffix(".txt")
-		.tempfile()
-		.context("Couldn't create temp file: {e}")?;
-
-	let mut args = vec!["-progress".to_string(), temp_file.path().to_str().unwrap().to_string()];
-
-	if let Some(accelerator) = accelerator {
-		args.extend(["-hwaccel".to_string(), accelerator]);
+use std::io::{IsTerminal, Stdout, Write};
+use std::time::Instant;
+
+const PROGRESS_BAR_WIDTH: u32 = 20;
+
+pub fn ffmpeg(command: &FfmpegCommand, progress: Option<ProgressTotal>, debug: bool) -> Result<(), FfError> {
+	let args = command.args();
+	let binary = ffmpeg_binary().into_os_string();
+
+	match run_mode() {
+		RunMode::Run => (),
+		RunMode::DryRun => {
+			outln!("# POSIX sh");
+			outln!("{}", format_command(&binary, &args, Shell::Posix));
+			outln!("# PowerShell");
+			outln!("{}", format_command(&binary, &args, Shell::PowerShell));
+			return Ok(());
+		}
+		RunMode::EmitScript => {
+			record(ScriptStep::Run { binary, args });
+			return Ok(());
+		}
 	}
 
-	args.extend(in_args.to_vec());
-
 	if debug {
-		println!("{:#^40}", " DEBUG MODE ");
-
-		let ffmpeg_args = &args
-			.iter()
-			.map(|a| if a.contains(' ') { format!("\"{a}\"") } else { a.clone() })
-			.collect::<Vec<String>>();
-
-		println!("full command: ffmpeg {}", ffmpeg_args.join(" "));
-		let mut stdout = io::stdout();
-		let stdin = io::stdin();
-		write!(stdout, "{:#^40}", " Press Enter to continue… ").unwrap();
-		stdout.flush().unwrap();
-		let _ = stdin.read_line(&mut String::new()).unwrap();
-		writeln!(stdout, "Continuing…").unwrap();
+		outln!("{:#^40}", " DEBUG MODE ");
+		outln!("full command: {}", format_command(&binary, &args, Shell::Posix));
+		out!("{:#^40}", " Press Enter to continue… ");
+		let _ = io::stdout().flush();
+		let _ = io::stdin().read_line(&mut String::new()).unwrap();
+		outln!("Continuing…");
 	}
 
-	let mut ffmpeg = Command::new("ffmpeg");
-	let ffmpeg = ffmpeg.args(args);
-
 	let start = Instant::now();
 
-	#[allow(clippy::zombie_processes)]
-	let mut process = ffmpeg.spawn().expect("failed to run ffmpeg");
-
-	if show_progress {
-		let progress_file = File::open(temp_file.path())?;
-		let mut reader = BufReader::new(progress_file);
-
-		let mut pos = 0;
-		let mut last_progress = Instant::now();
-
-		let mut frames_processed = None;
-		let mut frames_per_second = None;
-		let mut out_time = None;
-		let mut encode_bitrate = None;
-		let mut encode_speed = None;
-		let mut total_size = None;
-
-		loop {
-			let mut line = String::new();
-			let res = reader.read_line(&mut line);
-
-			#[allow(clippy::match_same_arms)]
-			match res {
-				Ok(0) => {
-					// stats_period defaults to 0.5 seconds, but sometimes heavy processing means output gets delayed
-					// within this window, assume that an EOF just means ffmpeg needs more time
-					if last_progress.elapsed() < Duration::from_secs(5) {
-						sleep(Duration::from_millis(200));
-
-						reader
-							.seek(SeekFrom::Start(pos))
-							.context("Failed to seek to resume point")?;
-
-						continue;
-					}
-
-					break;
-				}
-				Ok(len) => {
-					last_progress = Instant::now();
-					pos += len as u64;
-
-					let Some((key, value)) = line.trim().split_once('=') else {
-						break;
-					};
-
-					match (key, value) {
-						("frame", frame) => {
-							frames_processed = frame.trim().parse::<u64>().ok();
-						}
-						("fps", fps) => {
-							frames_per_second = fps.trim().parse::<f64>().ok();
-						}
-						("out_time", time) => {
-							out_time = parse_ffmpeg_duration(time.trim());
-						}
-						("bitrate", bitrate) => {
-							encode_bitrate = Some(bitrate.trim().to_string());
-						}
-						("speed", speed) => {
-							encode_speed = speed.trim().trim_end_matches('x').parse::<f64>().ok();
-						}
-						("total_size", size) => total_size = size.trim().parse::<u64>().ok(),
-						("progress", "continue") => (),
-						("progress", "end") => {
-							#[cfg(debug_assertions)]
-							eprintln!("PROCESSING HAS ENDED");
-							break;
-						}
-						("out_time_us" | "out_time_ms" | "dup_frames" | "drop_frames", _) => (),
-						_ if key.starts_with("stream_") && key.ends_with("_q") => (),
-						_ => {
-							#[cfg(debug_assertions)]
-							eprintln!("Unknown progress value: {key} = {value}");
-						}
-					}
-				}
-				Err(e) => {
-					eprintln!("failed to read progress file: {e:?}");
-					break;
-				}
-			}
-
-			// values for keys "bitrate", "out_time", and "speed" can be N/A for a while after starting an encode
-			// so we process those values separately
-			// there's probably a better way of doing this but I'm lazy and this works
-			if let (Some(frame), Some(fps), Some(size)) = (frames_processed, frames_per_second, total_size) {
-				let timestamp = out_time.map_or("N/A".to_string(), |time| {
-					format_ffmpeg_timestamp(time, &TimestampFormat::TwoDigits)
-				});
-				let bitrate = encode_bitrate.unwrap_or("N/A".to_string());
-				let speed = encode_speed.map_or("N/A".to_string(), |speed| format!("{speed:.3}x"));
-
-				let formatted_size = {
-					if cfg!(target_os = "macos") {
-						humansize::format_size(size, humansize::DECIMAL)
-					} else {
-						humansize::format_size(size, humansize::WINDOWS)
-					}
-				};
-
-				println!(
-					"frame: {frame} - fps: {fps:.2} - time: {timestamp} - size: {formatted_size} - bitrate: {bitrate} - speed: {speed}"
-				);
-
-				frames_processed = None;
-				frames_per_second = None;
-				out_time = None;
-				encode_bitrate = None;
-				encode_speed = None;
-				total_size = None;
-			}
+	match progress {
+		Some(total) => {
+			let mut printer = ProgressPrinter::new(total);
+			let result = run_ffmpeg(&args, &mut |event| printer.print(&event));
+			printer.finish();
+			result?;
 		}
+		None => run_ffmpeg(&args, &mut |_| ())?,
+	}
+
+	let execution_time = start.elapsed().as_secs_f64();
+	outln!("Encoding took {execution_time:.2}s!");
+	emit(&Report::EncodingTook { seconds: execution_time });
+
+	Ok(())
+}
+
+/// Runs ffmpeg with the given arguments and calls `on_progress` for every block of its `-progress` output,
+/// leaving it up to the caller how progress gets displayed. Nothing is printed to stdout.
+pub fn ffmpeg_with_progress(in_args: &[OsString], mut on_progress: impl FnMut(ProgressEvent)) -> Result<(), FfError> {
+	run_ffmpeg(in_args, &mut on_progress)
+}
+
+fn run_ffmpeg(args: &[OsString], on_progress: &mut dyn FnMut(ProgressEvent)) -> Result<(), FfError> {
+	if is_cancelled() {
+		return Err(FfError::Cancelled);
 	}
 
-	let exit_status = process.wait().expect("failed to wait for ffmpeg");
-	if !exit_status.success() {
-		anyhow::bail!("ffmpeg exited with status code {}", exit_status.code().unwrap_or(-1))
+	runner().encode(&ffmpeg_binary(), args, on_progress)
+}
+
+/// Prints a status line for every progress event. On a terminal the line is redrawn in place,
+/// otherwise every update gets its own line.
+struct ProgressPrinter {
+	total: ProgressTotal,
+	start: Instant,
+	stdout: Stdout,
+	redraw: bool,
+	redrawn: bool,
+}
+
+impl ProgressPrinter {
+	fn new(total: ProgressTotal) -> Self {
+		let stdout = io::stdout();
+		let redraw = stdout.is_terminal();
+
+		Self {
+			total,
+			start: Instant::now(),
+			stdout,
+			redraw,
+			redrawn: false,
+		}
 	}
 
-	let execution_time = start.elapsed();
-	println!("Encoding took {:.2}s!", execution_time.as_secs_f64());
+	fn print(&mut self, event: &ProgressEvent) {
+		let fraction = self.total.fraction(event.out_time, event.frame);
+		let remaining = fraction.and_then(|fraction| estimate_remaining(fraction, self.start.elapsed()));
+
+		if json_output() {
+			emit(&Report::Progress {
+				frame: event.frame,
+				fps: event.fps,
+				out_time: event.out_time.map(|time| time.as_secs_f64()),
+				bitrate: event.bitrate,
+				total_size: event.total_size,
+				speed: event.speed,
+				dup_frames: event.dup_frames,
+				drop_frames: event.drop_frames,
+				stream_q: &event.stream_q,
+				percent: fraction.map(|fraction| fraction * 100.0),
+				eta: remaining.map(|remaining| remaining.as_secs_f64()),
+			});
+			return;
+		}
+
+		let (Some(frame), Some(fps), Some(size)) = (event.frame, event.fps, event.total_size) else {
+			return;
+		};
+
+		let timestamp = event.out_time.map_or("N/A".to_string(), |time| {
+			format_ffmpeg_timestamp(time, &TimestampFormat::TwoDigits)
+		});
+		let bitrate = event
+			.bitrate
+			.map_or("N/A".to_string(), |bitrate| format!("{bitrate:.1}kbits/s"));
+		let speed = event
+			.speed
+			.map_or("N/A".to_string(), |speed| format!("{speed:.3}x"));
+
+		let formatted_size = {
+			if cfg!(target_os = "macos") {
+				humansize::format_size(size, humansize::DECIMAL)
+			} else {
+				humansize::format_size(size, humansize::WINDOWS)
+			}
+		};
+
+		let eta = remaining.map_or("N/A".to_string(), format_eta);
+
+		if self.redraw {
+			// kept short so that it fits on one line, a wrapped line can't be redrawn with \r
+			let percent = fraction.map_or(String::new(), |fraction| {
+				#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+				let filled = (fraction * f64::from(PROGRESS_BAR_WIDTH)).round() as u32;
+				format!(
+					"[{}{}] {:5.1}% - ",
+					"#".repeat(filled as usize),
+					"-".repeat((PROGRESS_BAR_WIDTH - filled) as usize),
+					fraction * 100.0
+				)
+			});
+			let _ = write!(
+				self.stdout,
+				"\r{percent}{timestamp} - {fps:.2} fps - {speed} - {formatted_size} - ETA: {eta}\x1b[K"
+			);
+			let _ = self.stdout.flush();
+			self.redrawn = true;
+		} else {
+			let percent = fraction.map_or(String::new(), |fraction| format!("{:.1}% - ", fraction * 100.0));
+			println!(
+				"{percent}frame: {frame} - fps: {fps:.2} - time: {timestamp} - size: {formatted_size} - bitrate: {bitrate} - speed: {speed} - ETA: {eta}"
+			);
+		}
+	}
 
-	Ok(())
+	/// Moves past the redrawn line so that whatever gets printed next doesn't overwrite it.
+	fn finish(&self) {
+		if self.redrawn {
+			println!();
+		}
+	}
 }
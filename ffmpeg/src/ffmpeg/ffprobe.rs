@@ -1,12 +1,14 @@
+use std::ffi::OsString;
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::str::FromStr;
 
-use anyhow::{Result, anyhow};
-
-use crate::ffmpeg::ffprobe_struct::FFProbeOutput;
+use crate::ffmpeg::binaries::ffprobe_binary;
+use crate::ffmpeg::error::FfError;
+use crate::ffmpeg::ffprobe_struct::{FFProbeOutput, FrameInfo, PacketInfo};
+use crate::ffmpeg::runner::runner;
 
 // ffprobe -hide_banner -loglevel error -print_format json -show_streams -show_format Exclusion\ Zone/mariomovie.mkv
-pub fn ffprobe<P: AsRef<Path>>(input: P, count_frames: bool) -> Result<FFProbeOutput> {
+pub fn ffprobe<P: AsRef<Path>>(input: P, count_frames: bool) -> Result<FFProbeOutput, FfError> {
 	let mut ffprobe_args = vec![
 		"-hide_banner",
 		"-loglevel",
@@ -15,35 +17,87 @@ pub fn ffprobe<P: AsRef<Path>>(input: P, count_frames: bool) -> Result<FFProbeOu
 		"json",
 		"-show_streams",
 		"-show_format",
-		"-i",
-		input.as_ref().to_str().unwrap(),
 	];
 	if count_frames {
 		ffprobe_args.push("-count_frames");
 	}
 
-	let ffprobe = Command::new("ffprobe")
-		.args(ffprobe_args)
-		.stdout(Stdio::piped())
-		.stderr(Stdio::piped())
-		.spawn()
-		.expect("failed to run ffprobe");
-
-	let child_output = ffprobe
-		.wait_with_output()
-		.expect("failed to wait for ffprobe");
-	if !child_output.status.success() {
-		let stderr = String::from_utf8(child_output.stderr).expect("stderr contained corrupted data");
-		anyhow::bail!(stderr.trim().to_string())
-	}
+	let args = with_input(&ffprobe_args, input.as_ref());
+	let output = runner().output(&ffprobe_binary(), &args)?;
 
-	let stdout = String::from_utf8(child_output.stdout).expect("stdout contained corrupted data");
+	serde_json::from_str::<FFProbeOutput>(&output).map_err(|e| FfError::ProbeParse(e.to_string()))
+}
 
-	match serde_json::from_str::<FFProbeOutput>(stdout.as_str()) {
-		Ok(output) => Ok(output),
-		Err(e) => {
-			// eprintln!("stdout:\n{}", stdout);
-			Err(anyhow!(e))
+/// Appends the input to the arguments. The path is passed on as it is, so it doesn't need to be valid UTF-8.
+fn with_input(args: &[&str], input: &Path) -> Vec<OsString> {
+	let mut args = args.iter().map(OsString::from).collect::<Vec<OsString>>();
+	args.extend([OsString::from("-i"), input.as_os_str().to_os_string()]);
+	args
+}
+
+/// Runs ffprobe with `compact` output and parses its stdout line by line as it arrives,
+/// so that even hours of frame or packet data never have to be held in memory as text.
+fn ffprobe_compact<T: FromStr>(args: &[OsString]) -> Result<Vec<T>, FfError> {
+	let mut records = Vec::new();
+	runner().read_output(&ffprobe_binary(), args, &mut |line| {
+		if let Ok(record) = line.parse::<T>() {
+			records.push(record);
 		}
+	})?;
+
+	Ok(records)
+}
+
+/// Lists every frame of the selected stream. This decodes the stream and can take a while.
+///
+/// `read_intervals` takes ffprobe's `-read_intervals` syntax, e.g. `10%+20` to read 20 seconds starting at 10s.
+// ffprobe -hide_banner -loglevel error -select_streams V:0 -show_frames -print_format compact=p=0 input.mkv
+pub fn ffprobe_frames_detail<P: AsRef<Path>>(
+	input: P,
+	stream_specifier: &str,
+	read_intervals: Option<&str>,
+) -> Result<Vec<FrameInfo>, FfError> {
+	let mut ffprobe_args = vec![
+		"-hide_banner",
+		"-loglevel",
+		"error",
+		"-select_streams",
+		stream_specifier,
+		"-show_entries",
+		"frame=key_frame,pts_time,duration_time,pkt_size,pict_type,interlaced_frame,top_field_first",
+		"-print_format",
+		"compact=p=0",
+	];
+	if let Some(read_intervals) = read_intervals {
+		ffprobe_args.extend(["-read_intervals", read_intervals]);
 	}
+
+	ffprobe_compact(&with_input(&ffprobe_args, input.as_ref()))
+}
+
+/// Lists every packet of the selected stream, or of all streams if no specifier is given.
+/// Unlike [`ffprobe_frames_detail`], this only demuxes the input and is therefore fast.
+// ffprobe -hide_banner -loglevel error -show_packets -print_format compact=p=0 input.mkv
+pub fn ffprobe_packets<P: AsRef<Path>>(
+	input: P,
+	stream_specifier: Option<&str>,
+	read_intervals: Option<&str>,
+) -> Result<Vec<PacketInfo>, FfError> {
+	let mut ffprobe_args = vec![
+		"-hide_banner",
+		"-loglevel",
+		"error",
+		"-show_entries",
+		"packet=stream_index,codec_type,pts_time,dts_time,duration_time,size,flags",
+		"-print_format",
+		"compact=p=0",
+	];
+	if let Some(stream_specifier) = stream_specifier {
+		ffprobe_args.extend(["-select_streams", stream_specifier]);
+	}
+	if let Some(read_intervals) = read_intervals {
+		ffprobe_args.extend(["-read_intervals", read_intervals]);
+	}
+
+	ffprobe_compact(&with_input(&ffprobe_args, input.as_ref()))
 }
@@ -1,9 +1,11 @@
-use crate::ffmpeg::deserialize_bool_from_int;
+use crate::ffmpeg::error::FfError;
 use crate::ffmpeg::timestamps::parse_ffmpeg_duration;
-use anyhow::{Context, Result, anyhow};
+use crate::ffmpeg::{deserialize_bool_from_int, deserialize_optional_rational};
 use colored::Color;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 use std::time::Duration;
 
 #[derive(Debug, Clone, Deserialize)]
@@ -13,23 +15,20 @@ pub struct FFProbeOutput {
 }
 
 impl FFProbeOutput {
-	pub fn duration(&self) -> Result<Duration> {
-		// intentionally not dealing with FloatParseErrors here.
-		// if ffprobe ever feeds us bad data we've got bigger problems anyway
-
+	pub fn duration(&self) -> Result<Duration, FfError> {
 		let video_stream = self
 			.streams
 			.iter()
 			.find(|s| s.codec_type == StreamType::Video)
-			.ok_or_else(|| anyhow!("The input file needs to contain a usable video stream"))?;
+			.ok_or_else(|| {
+				FfError::MissingStream("The input file needs to contain a usable video stream".to_string())
+			})?;
 
 		if let Some(stream_duration) = video_stream.duration.clone() {
 			// return first video stream duration
-			return Ok(Duration::from_secs_f64(
-				stream_duration
-					.parse()
-					.map_err(|e| anyhow!("{e}: stream duration \"{stream_duration}\""))?,
-			));
+			return Ok(Duration::from_secs_f64(stream_duration.parse().map_err(|e| {
+				FfError::ProbeParse(format!("{e}: stream duration \"{stream_duration}\""))
+			})?));
 		}
 
 		if let Some(tags_duration) = video_stream
@@ -44,17 +43,35 @@ pub fn duration(&self) -> Result<Duration> {
 
 		if let Some(format_duration) = &self.format.duration {
 			// return format duration
-			return Ok(Duration::from_secs_f64(format_duration.parse()?));
+			return Ok(Duration::from_secs_f64(format_duration.parse().map_err(|e| {
+				FfError::ProbeParse(format!("{e}: format duration \"{format_duration}\""))
+			})?));
 		}
 
 		if let (Some(read_frames), Some(frame_rate)) = (&video_stream.nb_read_frames, video_stream.frame_rate()) {
 			// divide number of frames by frame rate and return the result
 
-			let read_frames = read_frames.parse::<f64>()?;
+			let read_frames = read_frames
+				.parse::<f64>()
+				.map_err(|e| FfError::ProbeParse(format!("{e}: number of read frames \"{read_frames}\"")))?;
 			return Ok(Duration::from_secs_f64(read_frames / frame_rate));
 		}
 
-		anyhow::bail!("ffprobe could not find a duration for the input file")
+		Err(FfError::ProbeParse(
+			"ffprobe could not find a duration for the input file".to_string(),
+		))
+	}
+
+	/// Returns the container's start time in seconds, which `-ss` counts from. It's usually 0, but MPEG-TS
+	/// for example tends to start at 1.4s.
+	#[must_use]
+	pub fn start_time(&self) -> f64 {
+		self.format
+			.start_time
+			.as_deref()
+			.and_then(|s| s.parse::<f64>().ok())
+			.filter(|s| s.is_finite())
+			.unwrap_or(0.0)
 	}
 
 	#[must_use]
@@ -104,24 +121,32 @@ pub fn checked_get_video_stream_by_index_or_language(
 		&self,
 		lang: &Option<String>,
 		index: usize,
-	) -> Result<(Stream, String)> {
+	) -> Result<(Stream, String), FfError> {
 		let (video_stream, video_stream_id) = if let Some(language) = lang {
 			let stream = self
 				.get_video_stream_by_language(language)
-				.context(format!("No stream with language \"{language}\" found"))?
+				.ok_or_else(|| FfError::MissingStream(format!("No stream with language \"{language}\" found")))?
 				.clone();
 			(stream, format!("0:V:m:language:{language}"))
 		} else {
 			let stream = self
 				.get_video_stream(index)
-				.context(format!("No stream with index {index} found"))?
+				.ok_or_else(|| FfError::MissingStream(format!("No stream with index {index} found")))?
 				.clone();
 			(stream, format!("0:V:{index}"))
 		};
 
 		match video_stream.height {
-			None => anyhow::bail!("The selected video stream contains no height information"),
-			Some(0) => anyhow::bail!("The selected video stream contains invalid height information"),
+			None => {
+				return Err(FfError::ProbeParse(
+					"The selected video stream contains no height information".to_string(),
+				));
+			}
+			Some(0) => {
+				return Err(FfError::ProbeParse(
+					"The selected video stream contains invalid height information".to_string(),
+				));
+			}
 			_ => (),
 		}
 
@@ -207,6 +232,21 @@ pub fn color(&self) -> Color {
 	}
 }
 
+impl FromStr for StreamType {
+	type Err = FfError;
+
+	fn from_str(s: &str) -> Result<Self, FfError> {
+		match s {
+			"audio" => Ok(StreamType::Audio),
+			"video" => Ok(StreamType::Video),
+			"subtitle" => Ok(StreamType::Subtitle),
+			"data" => Ok(StreamType::Data),
+			"attachment" => Ok(StreamType::Attachment),
+			_ => Err(FfError::ProbeParse(format!("Unknown stream type \"{s}\""))),
+		}
+	}
+}
+
 impl Display for StreamType {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		match self {
@@ -412,6 +452,7 @@ fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct Format {
 	pub duration: Option<String>,
+	pub start_time: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize)]
@@ -420,6 +461,8 @@ pub struct Stream {
 	pub index: u64,
 	pub codec_name: Option<String>,
 	pub profile: Option<String>,
+	/// The codec level as ffprobe reports it, e.g. 41 for H.264 level 4.1 and 123 for H.265 level 4.1.
+	pub level: Option<i64>,
 	pub codec_type: StreamType,
 	pub codec_tag_string: Option<String>,
 	pub width: Option<u64>,
@@ -447,9 +490,21 @@ pub struct Stream {
 	pub nb_read_frames: Option<String>,
 	pub tags: Option<Tags>,
 	pub disposition: Option<Disposition>,
+	pub side_data_list: Option<Vec<SideData>>,
 }
 
 impl Stream {
+	/// Returns the sample aspect ratio, or 1 if it's unknown.
+	#[must_use]
+	pub fn sample_aspect_ratio(&self) -> f64 {
+		self.sar
+			.as_deref()
+			.and_then(|sar| sar.split_once(':'))
+			.and_then(|(num, den)| Some(num.parse::<f64>().ok()? / den.parse::<f64>().ok()?))
+			.filter(|sar| sar.is_finite() && *sar > 0.0)
+			.unwrap_or(1.0)
+	}
+
 	#[must_use]
 	pub fn frame_rate(&self) -> Option<f64> {
 		match &self.r_frame_rate {
@@ -459,8 +514,8 @@ pub fn frame_rate(&self) -> Option<f64> {
 			Some(fps) => {
 				if fps.contains('/') {
 					if let Some(split) = fps.split_once('/') {
-						let left = split.0.parse::<f64>().unwrap();
-						let right = split.1.parse::<f64>().unwrap();
+						let left = split.0.parse::<f64>().ok()?;
+						let right = split.1.parse::<f64>().ok()?;
 						return Some(left / right);
 					}
 				} else {
@@ -472,13 +527,77 @@ pub fn frame_rate(&self) -> Option<f64> {
 		None
 	}
 
+	fn side_data(&self) -> impl Iterator<Item = &SideData> {
+		self.side_data_list.iter().flatten()
+	}
+
+	#[must_use]
+	pub fn mastering_display(&self) -> Option<&MasteringDisplayMetadata> {
+		self.side_data().find_map(|sd| match sd {
+			SideData::MasteringDisplay(m) => Some(m),
+			_ => None,
+		})
+	}
+
+	#[must_use]
+	pub fn content_light_level(&self) -> Option<&ContentLightLevel> {
+		self.side_data().find_map(|sd| match sd {
+			SideData::ContentLightLevel(c) => Some(c),
+			_ => None,
+		})
+	}
+
+	#[must_use]
+	pub fn dovi_configuration(&self) -> Option<&DoviConfiguration> {
+		self.side_data().find_map(|sd| match sd {
+			SideData::DoviConfiguration(d) => Some(d),
+			_ => None,
+		})
+	}
+
+	/// Classifies the stream's HDR format. Dolby Vision takes precedence over the transfer characteristics
+	/// because profile 8 streams also carry the transfer of their backwards-compatible base layer.
+	#[must_use]
+	pub fn hdr_format(&self) -> Option<HdrFormat> {
+		if let Some(dovi) = self.dovi_configuration() {
+			return Some(HdrFormat::DolbyVision {
+				profile: dovi.dv_profile,
+				compatibility: dovi.compatibility(),
+			});
+		}
+
+		match self.color_transfer.as_deref() {
+			Some(t) if t.contains("smpte2084") => Some(HdrFormat::Hdr10),
+			Some(t) if t.contains("arib-std-b67") => Some(HdrFormat::Hlg),
+			_ => None,
+		}
+	}
+
 	#[must_use]
 	pub fn is_hdr(&self) -> bool {
-		if let Some(color_transfer) = &self.color_transfer {
-			return color_transfer.contains("smpte2084") || color_transfer.contains("arib-std-b67");
+		match self.hdr_format() {
+			Some(HdrFormat::DolbyVision { compatibility, .. }) => compatibility != DoviCompatibility::Sdr,
+			Some(_) => true,
+			None => false,
 		}
+	}
 
-		false
+	/// Returns the brightest luminance in cd/m² the content is known to reach, preferring `MaxCLL` over
+	/// the mastering display's peak luminance.
+	#[must_use]
+	pub fn peak_luminance(&self) -> Option<f64> {
+		#[allow(clippy::cast_precision_loss)]
+		if let Some(max_cll) = self
+			.content_light_level()
+			.map(|c| c.max_content)
+			.filter(|c| *c > 0)
+		{
+			return Some(max_cll as f64);
+		}
+
+		self.mastering_display()
+			.and_then(|m| m.max_luminance)
+			.filter(|l| *l > 0.0)
 	}
 
 	#[must_use]
@@ -492,3 +611,262 @@ pub fn total_frames(&self) -> Option<u64> {
 		None
 	}
 }
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "side_data_type")]
+pub enum SideData {
+	#[serde(rename = "Mastering display metadata")]
+	MasteringDisplay(MasteringDisplayMetadata),
+	#[serde(rename = "Content light level metadata")]
+	ContentLightLevel(ContentLightLevel),
+	#[serde(rename = "DOVI configuration record")]
+	DoviConfiguration(DoviConfiguration),
+	#[serde(other)]
+	Other,
+}
+
+/// SMPTE ST 2086 mastering display color volume. Chromaticities are CIE 1931 xy coordinates,
+/// luminances are in cd/m².
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MasteringDisplayMetadata {
+	#[serde(default, deserialize_with = "deserialize_optional_rational")]
+	pub red_x: Option<f64>,
+	#[serde(default, deserialize_with = "deserialize_optional_rational")]
+	pub red_y: Option<f64>,
+	#[serde(default, deserialize_with = "deserialize_optional_rational")]
+	pub green_x: Option<f64>,
+	#[serde(default, deserialize_with = "deserialize_optional_rational")]
+	pub green_y: Option<f64>,
+	#[serde(default, deserialize_with = "deserialize_optional_rational")]
+	pub blue_x: Option<f64>,
+	#[serde(default, deserialize_with = "deserialize_optional_rational")]
+	pub blue_y: Option<f64>,
+	#[serde(default, deserialize_with = "deserialize_optional_rational")]
+	pub white_point_x: Option<f64>,
+	#[serde(default, deserialize_with = "deserialize_optional_rational")]
+	pub white_point_y: Option<f64>,
+	#[serde(default, deserialize_with = "deserialize_optional_rational")]
+	pub min_luminance: Option<f64>,
+	#[serde(default, deserialize_with = "deserialize_optional_rational")]
+	pub max_luminance: Option<f64>,
+}
+
+/// CTA-861.3 content light level information, in cd/m².
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ContentLightLevel {
+	/// `MaxCLL`
+	pub max_content: u64,
+	/// `MaxFALL`
+	pub max_average: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DoviConfiguration {
+	pub dv_version_major: u8,
+	pub dv_version_minor: u8,
+	pub dv_profile: u8,
+	pub dv_level: u8,
+	#[serde(deserialize_with = "deserialize_bool_from_int")]
+	pub rpu_present_flag: bool,
+	#[serde(deserialize_with = "deserialize_bool_from_int")]
+	pub el_present_flag: bool,
+	#[serde(deserialize_with = "deserialize_bool_from_int")]
+	pub bl_present_flag: bool,
+	pub dv_bl_signal_compatibility_id: u8,
+}
+
+impl DoviConfiguration {
+	#[must_use]
+	pub fn compatibility(&self) -> DoviCompatibility {
+		match self.dv_bl_signal_compatibility_id {
+			0 => DoviCompatibility::None,
+			1 | 6 => DoviCompatibility::Hdr10,
+			2 => DoviCompatibility::Sdr,
+			4 => DoviCompatibility::Hlg,
+			id => DoviCompatibility::Unknown(id),
+		}
+	}
+}
+
+/// What a decoder without Dolby Vision support will see when it only decodes the base layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoviCompatibility {
+	/// The base layer is not usable on its own (e.g. profile 5's `IPTPQc2`).
+	None,
+	Hdr10,
+	Sdr,
+	Hlg,
+	Unknown(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdrFormat {
+	Hdr10,
+	Hlg,
+	DolbyVision {
+		profile: u8,
+		compatibility: DoviCompatibility,
+	},
+}
+
+impl Display for HdrFormat {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			HdrFormat::Hdr10 => write!(f, "HDR10"),
+			HdrFormat::Hlg => write!(f, "HLG"),
+			HdrFormat::DolbyVision { profile, compatibility } => {
+				write!(f, "Dolby Vision profile {profile}")?;
+				match compatibility {
+					DoviCompatibility::None => Ok(()),
+					DoviCompatibility::Hdr10 => write!(f, " (HDR10 compatible)"),
+					DoviCompatibility::Sdr => write!(f, " (SDR compatible)"),
+					DoviCompatibility::Hlg => write!(f, " (HLG compatible)"),
+					DoviCompatibility::Unknown(id) => write!(f, " (compatibility ID {id})"),
+				}
+			}
+		}
+	}
+}
+
+/// Splits a line of ffprobe's `compact` output (`key=value|key=value|…`) into its entries.
+/// `N/A` values are left out.
+fn compact_entries(line: &str) -> HashMap<&str, &str> {
+	line.trim()
+		.split('|')
+		.filter_map(|entry| entry.split_once('='))
+		.filter(|(_, value)| *value != "N/A")
+		.collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PictType {
+	I,
+	P,
+	B,
+	S,
+	SI,
+	SP,
+	BI,
+	Unknown,
+}
+
+impl FromStr for PictType {
+	type Err = FfError;
+
+	fn from_str(s: &str) -> Result<Self, FfError> {
+		Ok(match s {
+			"I" => PictType::I,
+			"P" => PictType::P,
+			"B" => PictType::B,
+			"S" => PictType::S,
+			"i" => PictType::SI,
+			"p" => PictType::SP,
+			"b" => PictType::BI,
+			_ => PictType::Unknown,
+		})
+	}
+}
+
+impl Display for PictType {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			PictType::I => write!(f, "I"),
+			PictType::P => write!(f, "P"),
+			PictType::B => write!(f, "B"),
+			PictType::S => write!(f, "S"),
+			PictType::SI => write!(f, "i"),
+			PictType::SP => write!(f, "p"),
+			PictType::BI => write!(f, "b"),
+			PictType::Unknown => write!(f, "?"),
+		}
+	}
+}
+
+/// A single decoded frame as reported by `ffprobe -show_frames`. Times are in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameInfo {
+	pub pts_time: Option<f64>,
+	pub duration_time: Option<f64>,
+	pub key_frame: bool,
+	pub pict_type: PictType,
+	pub pkt_size: Option<u64>,
+	pub interlaced_frame: bool,
+	pub top_field_first: bool,
+}
+
+impl FromStr for FrameInfo {
+	type Err = FfError;
+
+	/// Parses a line of `ffprobe -show_frames -print_format compact` output.
+	fn from_str(s: &str) -> Result<Self, FfError> {
+		let entries = compact_entries(s);
+		let flag = |key: &str| entries.get(key).is_some_and(|v| *v == "1");
+
+		if !entries.contains_key("key_frame") {
+			return Err(FfError::ProbeParse(format!("\"{s}\" is not a frame entry")));
+		}
+
+		Ok(FrameInfo {
+			pts_time: entries.get("pts_time").and_then(|v| v.parse().ok()),
+			duration_time: entries.get("duration_time").and_then(|v| v.parse().ok()),
+			key_frame: flag("key_frame"),
+			pict_type: entries
+				.get("pict_type")
+				.map_or(Ok(PictType::Unknown), |v| v.parse())?,
+			pkt_size: entries.get("pkt_size").and_then(|v| v.parse().ok()),
+			interlaced_frame: flag("interlaced_frame"),
+			top_field_first: flag("top_field_first"),
+		})
+	}
+}
+
+/// A single demuxed packet as reported by `ffprobe -show_packets`. Times are in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketInfo {
+	pub stream_index: u64,
+	pub codec_type: Option<StreamType>,
+	pub pts_time: Option<f64>,
+	pub dts_time: Option<f64>,
+	pub duration_time: Option<f64>,
+	pub size: u64,
+	pub key: bool,
+	pub discard: bool,
+}
+
+impl PacketInfo {
+	/// Returns the best available timestamp for this packet, which is the pts unless it's missing.
+	#[must_use]
+	pub fn time(&self) -> Option<f64> {
+		self.pts_time.or(self.dts_time)
+	}
+}
+
+impl FromStr for PacketInfo {
+	type Err = FfError;
+
+	/// Parses a line of `ffprobe -show_packets -print_format compact` output.
+	fn from_str(s: &str) -> Result<Self, FfError> {
+		let entries = compact_entries(s);
+
+		let stream_index = entries
+			.get("stream_index")
+			.ok_or_else(|| FfError::ProbeParse(format!("\"{s}\" is not a packet entry")))?
+			.parse()
+			.map_err(|e| FfError::ProbeParse(format!("{e}: stream index in \"{s}\"")))?;
+		let flags = entries.get("flags").copied().unwrap_or_default();
+
+		Ok(PacketInfo {
+			stream_index,
+			codec_type: entries.get("codec_type").and_then(|v| v.parse().ok()),
+			pts_time: entries.get("pts_time").and_then(|v| v.parse().ok()),
+			dts_time: entries.get("dts_time").and_then(|v| v.parse().ok()),
+			duration_time: entries.get("duration_time").and_then(|v| v.parse().ok()),
+			size: entries
+				.get("size")
+				.and_then(|v| v.parse().ok())
+				.unwrap_or_default(),
+			key: flags.contains('K'),
+			discard: flags.contains('D'),
+		})
+	}
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// Characters the option parser splits a filter's arguments on, or treats as quoting.
+const OPTION_SPECIAL: &[char] = &['\\', '\'', ':', '='];
+/// Characters the filtergraph parser splits filters, chains and pad labels on, or treats as quoting.
+const GRAPH_SPECIAL: &[char] = &['\\', '\'', '[', ']', ',', ';'];
+
+fn backslash_escape(value: &str, special: &[char]) -> String {
+	let mut escaped = String::with_capacity(value.len());
+	for c in value.chars() {
+		if special.contains(&c) {
+			escaped.push('\\');
+		}
+		escaped.push(c);
+	}
+	escaped
+}
+
+/// Escapes a filter option value so that it arrives at the filter unchanged. ffmpeg unescapes values twice,
+/// first when parsing the filtergraph and then when parsing the filter's options, so both levels are applied.
+#[must_use]
+pub fn escape_value(value: &str) -> String {
+	backslash_escape(&backslash_escape(value, OPTION_SPECIAL), GRAPH_SPECIAL)
+}
+
+/// A filter pad label like `[v]`, or a stream specifier like `[0:v:0]` when used as a chain's input.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Label(String);
+
+impl Label {
+	#[must_use]
+	pub fn new(name: impl Into<String>) -> Self {
+		Self(name.into())
+	}
+}
+
+impl Display for Label {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "[{}]", self.0)
+	}
+}
+
+/// A single filter like `scale=w=1280:h=-2`, with its option values escaped when it's formatted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+	name: String,
+	options: Vec<(Option<String>, String)>,
+}
+
+impl Filter {
+	#[must_use]
+	pub fn new(name: impl Into<String>) -> Self {
+		Self { name: name.into(), options: Vec::new() }
+	}
+
+	/// Adds a named option, e.g. `w=1280`.
+	#[must_use]
+	#[allow(clippy::needless_pass_by_value)] // taking values lets numbers be passed as they are
+	pub fn arg(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+		self.options.push((Some(key.into()), value.to_string()));
+		self
+	}
+
+	/// Adds an option by position, e.g. the `2` of `split=2`.
+	#[must_use]
+	#[allow(clippy::needless_pass_by_value)]
+	pub fn positional(mut self, value: impl ToString) -> Self {
+		self.options.push((None, value.to_string()));
+		self
+	}
+
+	#[must_use]
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+}
+
+impl Display for Filter {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.name)?;
+
+		for (i, (key, value)) in self.options.iter().enumerate() {
+			let separator = if i == 0 { '=' } else { ':' };
+			match key {
+				Some(key) => write!(f, "{separator}{key}={}", escape_value(value))?,
+				None => write!(f, "{separator}{}", escape_value(value))?,
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Filters that are applied one after another, with optional input and output pads.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chain {
+	inputs: Vec<Label>,
+	filters: Vec<Filter>,
+	outputs: Vec<Label>,
+}
+
+impl Chain {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	#[must_use]
+	pub fn input(mut self, label: Label) -> Self {
+		self.inputs.push(label);
+		self
+	}
+
+	#[must_use]
+	pub fn inputs(mut self, labels: impl IntoIterator<Item = Label>) -> Self {
+		self.inputs.extend(labels);
+		self
+	}
+
+	#[must_use]
+	pub fn output(mut self, label: Label) -> Self {
+		self.outputs.push(label);
+		self
+	}
+
+	#[must_use]
+	pub fn outputs(mut self, labels: impl IntoIterator<Item = Label>) -> Self {
+		self.outputs.extend(labels);
+		self
+	}
+
+	#[must_use]
+	pub fn filter(mut self, filter: Filter) -> Self {
+		self.filters.push(filter);
+		self
+	}
+
+	pub fn push(&mut self, filter: Filter) {
+		self.filters.push(filter);
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.filters.is_empty()
+	}
+}
+
+impl Extend<Filter> for Chain {
+	fn extend<T: IntoIterator<Item = Filter>>(&mut self, iter: T) {
+		self.filters.extend(iter);
+	}
+}
+
+impl FromIterator<Filter> for Chain {
+	fn from_iter<T: IntoIterator<Item = Filter>>(iter: T) -> Self {
+		Self {
+			filters: iter.into_iter().collect(),
+			..Self::default()
+		}
+	}
+}
+
+impl Display for Chain {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		for input in &self.inputs {
+			write!(f, "{input}")?;
+		}
+
+		for (i, filter) in self.filters.iter().enumerate() {
+			if i > 0 {
+				write!(f, ",")?;
+			}
+			write!(f, "{filter}")?;
+		}
+
+		for output in &self.outputs {
+			write!(f, "{output}")?;
+		}
+
+		Ok(())
+	}
+}
+
+/// A complete filtergraph for `-filter_complex`, made up of chains that are connected through their pads.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Graph {
+	chains: Vec<Chain>,
+	next_label: usize,
+}
+
+impl Graph {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Allocates a label that's unique within this graph, e.g. `[pal3]` for `pal`.
+	pub fn label(&mut self, name: &str) -> Label {
+		self.next_label += 1;
+		Label(format!("{name}{}", self.next_label))
+	}
+
+	pub fn push(&mut self, chain: Chain) {
+		self.chains.push(chain);
+	}
+}
+
+impl Display for Graph {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		for (i, chain) in self.chains.iter().enumerate() {
+			if i > 0 {
+				write!(f, ";")?;
+			}
+			write!(f, "{chain}")?;
+		}
+
+		Ok(())
+	}
+}
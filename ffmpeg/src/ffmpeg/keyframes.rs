@@ -0,0 +1,95 @@
+use crate::ffmpeg::ffprobe_struct::PacketInfo;
+
+/// A part of a cut that's either stream-copied or re-encoded. Times are in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CutSegment {
+	Copy { start: f64, end: f64 },
+	Encode { start: f64, end: f64 },
+}
+
+impl CutSegment {
+	#[must_use]
+	pub fn start(&self) -> f64 {
+		match self {
+			CutSegment::Copy { start, .. } | CutSegment::Encode { start, .. } => *start,
+		}
+	}
+
+	#[must_use]
+	pub fn end(&self) -> f64 {
+		match self {
+			CutSegment::Copy { end, .. } | CutSegment::Encode { end, .. } => *end,
+		}
+	}
+
+	#[must_use]
+	pub fn duration(&self) -> f64 {
+		self.end() - self.start()
+	}
+}
+
+/// Returns the sorted presentation timestamps of all keyframes of the given stream.
+#[must_use]
+pub fn keyframe_times(packets: &[PacketInfo], stream_index: u64) -> Vec<f64> {
+	let mut keyframe_times = packets
+		.iter()
+		.filter(|p| p.stream_index == stream_index && p.key)
+		.filter_map(PacketInfo::time)
+		.collect::<Vec<f64>>();
+	keyframe_times.sort_by(f64::total_cmp);
+	keyframe_times.dedup();
+	keyframe_times
+}
+
+/// Moves keyframe times from the packets' timeline to the one `-ss` and `-t` use, which starts at the container's
+/// start time. They're rounded to microseconds, which is what ffmpeg parses `-ss` into, so that seeking to a keyframe
+/// doesn't land just before it.
+#[must_use]
+pub fn keyframes_from_start(keyframes: &[f64], start_time: f64) -> Vec<f64> {
+	keyframes
+		.iter()
+		.map(|k| ((k - start_time) * 1_000_000.0).round() / 1_000_000.0)
+		.collect()
+}
+
+/// Returns the last keyframe at or before the given time.
+#[must_use]
+pub fn keyframe_at_or_before(keyframes: &[f64], time: f64) -> Option<f64> {
+	keyframes.iter().rev().find(|k| **k <= time).copied()
+}
+
+/// Returns the first keyframe at or after the given time.
+#[must_use]
+pub fn keyframe_at_or_after(keyframes: &[f64], time: f64) -> Option<f64> {
+	keyframes.iter().find(|k| **k >= time).copied()
+}
+
+/// Splits the range between `start` and `end` into a re-encoded head up to the first keyframe,
+/// a stream-copied middle between the first and last keyframe, and a re-encoded tail after the last keyframe.
+/// Parts that would be empty are left out.
+#[must_use]
+pub fn smart_cut_segments(keyframes: &[f64], start: f64, end: f64) -> Vec<CutSegment> {
+	let inner_keyframes = keyframes
+		.iter()
+		.copied()
+		.filter(|k| *k >= start && *k <= end)
+		.collect::<Vec<f64>>();
+
+	let (Some(first), Some(last)) = (inner_keyframes.first().copied(), inner_keyframes.last().copied()) else {
+		// no keyframe in range, so everything has to be re-encoded
+		return vec![CutSegment::Encode { start, end }];
+	};
+
+	let mut segments = vec![];
+	if first > start {
+		segments.push(CutSegment::Encode { start, end: first });
+	}
+	if last > first {
+		segments.push(CutSegment::Copy { start: first, end: last });
+	}
+	if end > last {
+		segments.push(CutSegment::Encode { start: last, end });
+	}
+
+	segments
+}
@@ -1,13 +1,29 @@
 use serde::de;
 
+pub mod binaries;
+pub mod bitrate;
+pub mod cancel;
+pub mod capabilities;
+pub mod command;
+pub mod cropdetect;
 pub mod enums;
 pub mod enums_impl;
+pub mod error;
+pub mod failure;
 #[allow(clippy::module_inception)]
 pub mod ffmpeg;
 pub mod ffprobe;
 pub mod ffprobe_struct;
+pub mod filtergraph;
+pub mod keyframes;
+pub mod progress;
+pub mod report;
+pub mod runner;
+pub mod script;
 pub mod size;
+pub mod subtitles;
 pub mod timestamps;
+pub mod watchdog;
 
 fn deserialize_bool_from_int<'de, D>(deserializer: D) -> Result<bool, D::Error>
 where
@@ -20,3 +36,24 @@ fn deserialize_bool_from_int<'de, D>(deserializer: D) -> Result<bool, D::Error>
 		_ => Ok(true),
 	}
 }
+
+/// Deserializes ffprobe's rational strings (like `"35400/50000"`) into floats, for fields that ffprobe may leave out.
+fn deserialize_optional_rational<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+	D: de::Deserializer<'de>,
+{
+	let Some(s): Option<String> = de::Deserialize::deserialize(deserializer)? else {
+		return Ok(None);
+	};
+
+	let parsed = match s.split_once('/') {
+		Some((num, den)) => num
+			.parse::<f64>()
+			.and_then(|num| den.parse::<f64>().map(|den| num / den)),
+		None => s.parse::<f64>(),
+	};
+
+	parsed
+		.map(Some)
+		.map_err(|_| de::Error::custom(format!("\"{s}\" is not a valid rational")))
+}
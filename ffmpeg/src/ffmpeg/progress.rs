@@ -0,0 +1,143 @@
+use crate::ffmpeg::timestamps::parse_ffmpeg_duration;
+use serde::Serialize;
+use std::time::Duration;
+
+/// What an encode's progress is measured against to calculate its percentage and ETA.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProgressTotal {
+	/// Neither the output duration nor its frame count are known, so only the raw values are shown.
+	Unknown,
+	/// The expected output duration, compared against ffmpeg's `out_time`.
+	Duration(Duration),
+	/// The expected number of output frames, compared against ffmpeg's `frame`.
+	Frames(u64),
+}
+
+impl ProgressTotal {
+	/// Returns how much of the encode is done, from 0.0 to 1.0.
+	#[must_use]
+	#[allow(clippy::cast_precision_loss)]
+	pub fn fraction(&self, out_time: Option<Duration>, frame: Option<u64>) -> Option<f64> {
+		let fraction = match (self, out_time, frame) {
+			(ProgressTotal::Duration(total), Some(out_time), _) if !total.is_zero() => {
+				out_time.as_secs_f64() / total.as_secs_f64()
+			}
+			(ProgressTotal::Frames(total), _, Some(frame)) if *total > 0 => frame as f64 / *total as f64,
+			_ => return None,
+		};
+
+		Some(fraction.clamp(0.0, 1.0))
+	}
+}
+
+/// Estimates the time left by assuming the rest of the encode runs as fast as it did so far.
+#[must_use]
+pub fn estimate_remaining(fraction: f64, elapsed: Duration) -> Option<Duration> {
+	if fraction <= 0.0 {
+		return None;
+	}
+
+	Some(elapsed.mul_f64((1.0 - fraction) / fraction))
+}
+
+/// Formats an ETA as `M:SS`, or `H:MM:SS` once it's an hour or longer.
+#[must_use]
+pub fn format_eta(eta: Duration) -> String {
+	let secs = eta.as_secs();
+	let (hours, minutes, seconds) = (secs / 3600, secs % 3600 / 60, secs % 60);
+
+	if hours > 0 {
+		format!("{hours}:{minutes:02}:{seconds:02}")
+	} else {
+		format!("{minutes}:{seconds:02}")
+	}
+}
+
+/// The encoding quality of one output stream, from the `stream_<file>_<stream>_q` progress keys.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct StreamQuality {
+	pub file_index: usize,
+	pub stream_index: usize,
+	pub q: f64,
+}
+
+/// One block of ffmpeg's `-progress` output. Values that ffmpeg reports as `N/A`, which `bitrate`, `out_time` and
+/// `speed` are for a while after an encode starts, are `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProgressEvent {
+	pub frame: Option<u64>,
+	pub fps: Option<f64>,
+	pub out_time: Option<Duration>,
+	/// In kbit/s.
+	pub bitrate: Option<f64>,
+	/// In bytes.
+	pub total_size: Option<u64>,
+	pub speed: Option<f64>,
+	pub dup_frames: Option<u64>,
+	pub drop_frames: Option<u64>,
+	pub stream_q: Vec<StreamQuality>,
+	/// Whether this is the last block, which ffmpeg writes once it's done encoding.
+	pub end: bool,
+}
+
+/// Turns the lines of ffmpeg's `-progress` output into [`ProgressEvent`]s.
+#[derive(Debug, Default)]
+pub struct ProgressParser {
+	event: ProgressEvent,
+}
+
+impl ProgressParser {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Feeds one line of progress output to the parser, returning the finished event once the line that ends a
+	/// block (`progress=continue` or `progress=end`) is reached.
+	pub fn push_line(&mut self, line: &str) -> Option<ProgressEvent> {
+		let (key, value) = line.trim().split_once('=')?;
+		let value = value.trim();
+		let event = &mut self.event;
+
+		match key {
+			"frame" => event.frame = value.parse().ok(),
+			"fps" => event.fps = value.parse().ok(),
+			"out_time" => event.out_time = parse_ffmpeg_duration(value),
+			"bitrate" => event.bitrate = value.trim_end_matches("kbits/s").parse().ok(),
+			"total_size" => event.total_size = value.parse().ok(),
+			"speed" => event.speed = value.trim_end_matches('x').parse().ok(),
+			"dup_frames" => event.dup_frames = value.parse().ok(),
+			"drop_frames" => event.drop_frames = value.parse().ok(),
+			"progress" => {
+				let mut event = std::mem::take(&mut self.event);
+				event.end = value == "end";
+				return Some(event);
+			}
+			"out_time_us" | "out_time_ms" => (),
+			_ => {
+				if let Some(quality) = parse_stream_quality(key, value) {
+					event.stream_q.push(quality);
+				} else {
+					#[cfg(debug_assertions)]
+					eprintln!("Unknown progress value: {key} = {value}");
+				}
+			}
+		}
+
+		None
+	}
+}
+
+/// Parses a `stream_0_1_q=28.0` progress value.
+fn parse_stream_quality(key: &str, value: &str) -> Option<StreamQuality> {
+	let (file_index, stream_index) = key
+		.strip_prefix("stream_")?
+		.strip_suffix("_q")?
+		.split_once('_')?;
+
+	Some(StreamQuality {
+		file_index: file_index.parse().ok()?,
+		stream_index: stream_index.parse().ok()?,
+		q: value.parse().ok()?,
+	})
+}
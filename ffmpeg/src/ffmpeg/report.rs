@@ -0,0 +1,101 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+
+use crate::ffmpeg::progress::StreamQuality;
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Switches to machine-readable output: every [`Report`] is written to stdout as one JSON object per line,
+/// and the human-readable text printed with [`out!`](crate::out) and [`outln!`](crate::outln) goes to stderr instead.
+pub fn set_json_output(enabled: bool) {
+	JSON_OUTPUT.store(enabled, Ordering::SeqCst);
+}
+
+#[must_use]
+pub fn json_output() -> bool {
+	JSON_OUTPUT.load(Ordering::SeqCst)
+}
+
+/// Something worth telling a script about, tagged with its kind in the `type` field.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Report<'a> {
+	Progress {
+		frame: Option<u64>,
+		fps: Option<f64>,
+		/// In seconds.
+		out_time: Option<f64>,
+		/// In kbit/s.
+		bitrate: Option<f64>,
+		/// In bytes.
+		total_size: Option<u64>,
+		speed: Option<f64>,
+		dup_frames: Option<u64>,
+		drop_frames: Option<u64>,
+		stream_q: &'a [StreamQuality],
+		/// From 0 to 100, only known when the expected output duration or frame count is.
+		percent: Option<f64>,
+		/// In seconds.
+		eta: Option<f64>,
+	},
+	Warning {
+		message: &'a str,
+	},
+	OutputSize {
+		bytes: u64,
+	},
+	EncodingTook {
+		seconds: f64,
+	},
+	/// The output of a killed run was deleted, see `--keep-partial`.
+	PartialOutputRemoved {
+		path: &'a str,
+	},
+	Error {
+		message: &'a str,
+		exit_code: u8,
+	},
+}
+
+/// Writes the report as a JSON line to stdout, but only in JSON mode.
+pub fn emit(report: &Report) {
+	if !json_output() {
+		return;
+	}
+
+	match serde_json::to_string(report) {
+		Ok(line) => println!("{line}"),
+		Err(e) => eprintln!("Couldn't serialize {report:?}: {e}"),
+	}
+}
+
+/// Prints a warning to stderr, and emits it as a [`Report::Warning`] in JSON mode.
+pub fn warn(message: &str) {
+	eprintln!("{message}");
+	emit(&Report::Warning { message });
+}
+
+/// Like [`print!`], but writes to stderr in JSON mode so that stdout only contains JSON.
+#[macro_export]
+macro_rules! out {
+	($($arg:tt)*) => {
+		if $crate::ffmpeg::report::json_output() {
+			eprint!($($arg)*);
+		} else {
+			print!($($arg)*);
+		}
+	};
+}
+
+/// Like [`println!`], but writes to stderr in JSON mode so that stdout only contains JSON.
+#[macro_export]
+macro_rules! outln {
+	($($arg:tt)*) => {
+		if $crate::ffmpeg::report::json_output() {
+			eprintln!($($arg)*);
+		} else {
+			println!($($arg)*);
+		}
+	};
+}
@@ -0,0 +1,349 @@
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{ChildStderr, Command, Stdio};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use tempfile::Builder;
+
+use crate::ffmpeg::cancel::wait_or_cancel;
+use crate::ffmpeg::error::FfError;
+use crate::ffmpeg::failure::StderrTail;
+use crate::ffmpeg::progress::{ProgressEvent, ProgressParser};
+use crate::ffmpeg::watchdog::watchdog;
+
+/// How many lines of ffmpeg's stderr are repeated in the error when it fails.
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Runs ffmpeg and ffprobe. Every invocation goes through the runner set with [`set_runner`], which makes it
+/// possible to record the generated command lines, replay canned ffprobe output or run ffmpeg somewhere else.
+pub trait Runner: Send + Sync {
+	/// Runs a command like ffprobe to completion and passes every line of its stdout on to `on_line`.
+	/// Returns [`FfError::NonZeroExit`] with its stderr if it fails.
+	fn read_output(&self, binary: &Path, args: &[OsString], on_line: &mut dyn FnMut(&str)) -> Result<(), FfError>;
+
+	/// Runs an ffmpeg encode and passes every block of its `-progress` output on to `on_progress`.
+	fn encode(
+		&self,
+		binary: &Path,
+		args: &[OsString],
+		on_progress: &mut dyn FnMut(ProgressEvent),
+	) -> Result<(), FfError>;
+
+	/// Like [`Runner::read_output`], but collects stdout into a string.
+	fn output(&self, binary: &Path, args: &[OsString]) -> Result<String, FfError> {
+		let mut output = String::new();
+		self.read_output(binary, args, &mut |line| {
+			output.push_str(line);
+			output.push('\n');
+		})?;
+
+		Ok(output)
+	}
+}
+
+static RUNNER: Mutex<Option<Arc<dyn Runner>>> = Mutex::new(None);
+
+/// Makes every following ffmpeg and ffprobe invocation go through the given runner.
+pub fn set_runner(runner: Arc<dyn Runner>) {
+	*RUNNER.lock().unwrap_or_else(PoisonError::into_inner) = Some(runner);
+}
+
+/// Returns the runner set with [`set_runner`], or [`LocalRunner`] if there is none.
+#[must_use]
+pub fn runner() -> Arc<dyn Runner> {
+	RUNNER
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.clone()
+		.unwrap_or_else(|| Arc::new(LocalRunner))
+}
+
+/// Runs the binaries as child processes of this one.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LocalRunner;
+
+impl Runner for LocalRunner {
+	fn read_output(&self, binary: &Path, args: &[OsString], on_line: &mut dyn FnMut(&str)) -> Result<(), FfError> {
+		let mut child = Command::new(binary)
+			.args(args)
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped())
+			.spawn()
+			.map_err(|e| FfError::from_spawn_error(binary, e))?;
+
+		// stderr is drained on its own thread, or a child that fills up the stderr pipe would block
+		// while this one is still waiting for the rest of its stdout
+		let stderr_reader = child.stderr.take().map(|mut stderr_pipe| {
+			thread::spawn(move || {
+				let mut stderr = Vec::new();
+				let _ = stderr_pipe.read_to_end(&mut stderr);
+				stderr
+			})
+		});
+
+		// stdout is handed out as it arrives, so that even hours of frame data never have to be held in memory.
+		// file names in it don't have to be valid UTF-8, so lines are split as bytes and converted lossily
+		if let Some(stdout) = child.stdout.take() {
+			for line in BufReader::new(stdout).split(b'\n').map_while(Result::ok) {
+				let line = String::from_utf8_lossy(&line);
+				on_line(line.strip_suffix('\r').unwrap_or(&line));
+			}
+		}
+
+		let stderr = stderr_reader
+			.and_then(|reader| reader.join().ok())
+			.unwrap_or_default();
+		let stderr = String::from_utf8_lossy(&stderr);
+
+		let exit_status = child.wait()?;
+		if !exit_status.success() {
+			return Err(FfError::NonZeroExit {
+				binary: binary.display().to_string(),
+				code: exit_status.code(),
+				stderr: stderr.trim().to_string(),
+			});
+		}
+
+		Ok(())
+	}
+
+	fn encode(
+		&self,
+		binary: &Path,
+		args: &[OsString],
+		on_progress: &mut dyn FnMut(ProgressEvent),
+	) -> Result<(), FfError> {
+		let temp_file = Builder::new().prefix("ffmpeg").suffix(".txt").tempfile()?;
+
+		// progress is followed even if nobody is interested in it, it's how a stalled ffmpeg is noticed
+		let mut ffmpeg = Command::new(binary);
+		ffmpeg.arg("-progress").arg(temp_file.path());
+		ffmpeg.args(args);
+
+		let mut process = ffmpeg
+			.stdin(Stdio::piped())
+			.stderr(Stdio::piped())
+			.spawn()
+			.map_err(|e| FfError::from_spawn_error(binary, e))?;
+
+		let stderr_tail = process.stderr.take().map(tee_stderr);
+
+		let mut follower = ProgressFollower::new(temp_file.path())?;
+		let watchdog = watchdog();
+		let started = Instant::now();
+
+		let exit_status = wait_or_cancel(&mut process, || {
+			follower.poll(on_progress)?;
+
+			// finishing up, like moving the moov atom to the front, doesn't write any progress
+			let last_progress = if follower.ended {
+				Instant::now()
+			} else {
+				follower.last_progress
+			};
+			watchdog.check(started, last_progress, follower.last_position)
+		})?;
+
+		// pick up whatever was written between the last poll and ffmpeg exiting
+		follower.poll(on_progress)?;
+
+		if !exit_status.success() {
+			let stderr = stderr_tail
+				.and_then(|tail| tail.join().ok())
+				.map(StderrTail::into_string)
+				.unwrap_or_default();
+
+			return Err(FfError::NonZeroExit {
+				binary: binary.display().to_string(),
+				code: exit_status.code(),
+				stderr,
+			});
+		}
+
+		Ok(())
+	}
+}
+
+/// Passes ffmpeg's stderr through as it comes in, while keeping its last lines around for the error message.
+fn tee_stderr(mut stderr: ChildStderr) -> JoinHandle<StderrTail> {
+	thread::spawn(move || {
+		let mut tail = StderrTail::new(STDERR_TAIL_LINES);
+		let mut buf = [0; 4096];
+
+		while let Ok(len) = stderr.read(&mut buf) {
+			if len == 0 {
+				break;
+			}
+			let _ = io::stderr().write_all(&buf[..len]);
+			tail.push(&buf[..len]);
+		}
+
+		tail
+	})
+}
+
+/// Follows the file ffmpeg writes its `-progress` output to.
+struct ProgressFollower {
+	reader: BufReader<File>,
+	parser: ProgressParser,
+	pos: u64,
+	last_progress: Instant,
+	last_position: Option<Duration>,
+	ended: bool,
+}
+
+impl ProgressFollower {
+	fn new(progress_path: &Path) -> Result<Self, FfError> {
+		Ok(Self {
+			reader: BufReader::new(File::open(progress_path)?),
+			parser: ProgressParser::new(),
+			pos: 0,
+			last_progress: Instant::now(),
+			last_position: None,
+			ended: false,
+		})
+	}
+
+	/// Reads everything written since the last call and passes every complete block on to `on_progress`.
+	fn poll(&mut self, on_progress: &mut dyn FnMut(ProgressEvent)) -> Result<(), FfError> {
+		loop {
+			let mut line = String::new();
+			let len = self.reader.read_line(&mut line)?;
+
+			// a partially written line is read again once the rest of it is there
+			if len == 0 || !line.ends_with('\n') {
+				self.reader.seek(SeekFrom::Start(self.pos))?;
+				return Ok(());
+			}
+
+			self.pos += len as u64;
+			self.last_progress = Instant::now();
+
+			if let Some(event) = self.parser.push_line(&line) {
+				if event.out_time.is_some() {
+					self.last_position = event.out_time;
+				}
+				self.ended = event.end;
+				on_progress(event);
+			}
+		}
+	}
+}
+
+/// One invocation captured by a [`RecordingRunner`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedCall {
+	pub binary: PathBuf,
+	pub args: Vec<OsString>,
+}
+
+/// Captures the arguments of every invocation and passes it on to another runner, if there is one.
+/// Without one, encodes succeed right away and commands print nothing.
+#[derive(Default)]
+pub struct RecordingRunner {
+	inner: Option<Arc<dyn Runner>>,
+	calls: Mutex<Vec<RecordedCall>>,
+}
+
+impl RecordingRunner {
+	#[must_use]
+	pub fn new(inner: Arc<dyn Runner>) -> Self {
+		Self { inner: Some(inner), calls: Mutex::default() }
+	}
+
+	/// Returns the invocations recorded so far.
+	#[must_use]
+	pub fn calls(&self) -> Vec<RecordedCall> {
+		self.calls
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.clone()
+	}
+
+	fn record(&self, binary: &Path, args: &[OsString]) {
+		self.calls
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.push(RecordedCall {
+				binary: binary.to_path_buf(),
+				args: args.to_vec(),
+			});
+	}
+}
+
+impl Runner for RecordingRunner {
+	fn read_output(&self, binary: &Path, args: &[OsString], on_line: &mut dyn FnMut(&str)) -> Result<(), FfError> {
+		self.record(binary, args);
+		match &self.inner {
+			Some(inner) => inner.read_output(binary, args, on_line),
+			None => Ok(()),
+		}
+	}
+
+	fn encode(
+		&self,
+		binary: &Path,
+		args: &[OsString],
+		on_progress: &mut dyn FnMut(ProgressEvent),
+	) -> Result<(), FfError> {
+		self.record(binary, args);
+		match &self.inner {
+			Some(inner) => inner.encode(binary, args, on_progress),
+			None => Ok(()),
+		}
+	}
+}
+
+/// Serves canned output instead of running anything, e.g. ffprobe JSON for an input that doesn't exist.
+/// Encodes succeed right away with a single finished progress event.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayRunner {
+	outputs: Vec<(OsString, String)>,
+}
+
+impl ReplayRunner {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Serves `stdout` to every command that has `arg` as one of its arguments, usually the input file.
+	/// Outputs added first take precedence.
+	#[must_use]
+	pub fn with_output(mut self, arg: impl AsRef<OsStr>, stdout: impl Into<String>) -> Self {
+		self.outputs
+			.push((arg.as_ref().to_os_string(), stdout.into()));
+		self
+	}
+}
+
+impl Runner for ReplayRunner {
+	fn read_output(&self, binary: &Path, args: &[OsString], on_line: &mut dyn FnMut(&str)) -> Result<(), FfError> {
+		let Some((_, stdout)) = self.outputs.iter().find(|(arg, _)| args.contains(arg)) else {
+			return Err(FfError::NonZeroExit {
+				binary: binary.display().to_string(),
+				code: Some(1),
+				stderr: format!("No canned output for {}", args.join(OsStr::new(" ")).display()),
+			});
+		};
+
+		stdout.lines().for_each(on_line);
+		Ok(())
+	}
+
+	fn encode(
+		&self,
+		_binary: &Path,
+		_args: &[OsString],
+		on_progress: &mut dyn FnMut(ProgressEvent),
+	) -> Result<(), FfError> {
+		on_progress(ProgressEvent { end: true, ..ProgressEvent::default() });
+		Ok(())
+	}
+}
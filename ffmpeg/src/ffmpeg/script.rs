@@ -0,0 +1,242 @@
+use std::collections::BTreeSet;
+use std::ffi::{OsStr, OsString};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+/// The shells a command can be quoted for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shell {
+	Posix,
+	PowerShell,
+}
+
+/// Quotes the argument so that the shell passes it on unchanged. Arguments that don't need quoting are left alone.
+#[must_use]
+pub fn quote(arg: impl AsRef<OsStr>, shell: Shell) -> String {
+	let Some(arg) = arg.as_ref().to_str() else {
+		return quote_non_utf8(arg.as_ref(), shell);
+	};
+
+	let is_safe = |c: char| match shell {
+		Shell::Posix => c.is_ascii_alphanumeric() || "_-+=:,./@%".contains(c),
+		// `,` builds arrays and `@` splats in PowerShell
+		Shell::PowerShell => c.is_ascii_alphanumeric() || "_-+=:./".contains(c),
+	};
+
+	if !arg.is_empty() && arg.chars().all(is_safe) {
+		return arg.to_string();
+	}
+
+	match shell {
+		Shell::Posix => format!("'{}'", arg.replace('\'', r"'\''")),
+		Shell::PowerShell => format!("'{}'", arg.replace('\'', "''")),
+	}
+}
+
+/// Quotes an argument that isn't valid UTF-8, like a file name from an old Shift-JIS rip.
+fn quote_non_utf8(arg: &OsStr, shell: Shell) -> String {
+	match shell {
+		// printf recreates the exact bytes, although the command substitution drops trailing line breaks
+		Shell::Posix => format!("\"$(printf '{}')\"", octal_escape(arg.as_encoded_bytes())),
+		// paths are UTF-16 on Windows, so there's nothing better to do than replacing what can't be shown
+		Shell::PowerShell => quote(arg.to_string_lossy().as_ref(), shell),
+	}
+}
+
+/// Escapes every byte for printf, which understands octal escapes in every implementation.
+fn octal_escape(bytes: &[u8]) -> String {
+	bytes.iter().fold(String::new(), |mut s, byte| {
+		let _ = write!(s, "\\{byte:03o}");
+		s
+	})
+}
+
+/// Formats a complete command line, ready to be pasted into the given shell.
+#[must_use]
+pub fn format_command(binary: impl AsRef<OsStr>, args: &[impl AsRef<OsStr>], shell: Shell) -> String {
+	let mut command = quote(binary, shell);
+	if shell == Shell::PowerShell && command.starts_with('\'') {
+		// a quoted string on its own is just a value in PowerShell, the call operator runs it
+		command.insert_str(0, "& ");
+	}
+
+	for arg in args {
+		command.push(' ');
+		command.push_str(&quote(arg, shell));
+	}
+
+	command
+}
+
+/// Whether ffmpeg actually gets run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RunMode {
+	#[default]
+	Run,
+	/// Only print the commands that would be run.
+	DryRun,
+	/// Only record the commands and the files written for them, see [`take_steps`] and [`render_script`].
+	EmitScript,
+}
+
+static RUN_MODE: Mutex<RunMode> = Mutex::new(RunMode::Run);
+static STEPS: Mutex<Vec<ScriptStep>> = Mutex::new(Vec::new());
+
+pub fn set_run_mode(mode: RunMode) {
+	*RUN_MODE.lock().unwrap_or_else(PoisonError::into_inner) = mode;
+}
+
+#[must_use]
+pub fn run_mode() -> RunMode {
+	*RUN_MODE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Something that happened while [`RunMode::EmitScript`] was active.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScriptStep {
+	WriteFile { path: PathBuf, contents: Vec<u8> },
+	Run { binary: OsString, args: Vec<OsString> },
+}
+
+pub(crate) fn record(step: ScriptStep) {
+	STEPS
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.push(step);
+}
+
+/// Writes a file that a following ffmpeg run needs, recording it in [`RunMode::EmitScript`]
+/// so that the script can recreate it.
+pub fn write_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+	if run_mode() == RunMode::EmitScript {
+		record(ScriptStep::WriteFile {
+			path: path.to_path_buf(),
+			contents: contents.to_vec(),
+		});
+	}
+
+	fs::write(path, contents)
+}
+
+/// Returns everything recorded so far and starts over.
+#[must_use]
+pub fn take_steps() -> Vec<ScriptStep> {
+	std::mem::take(&mut *STEPS.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+const HEREDOC_DELIMITER: &str = "FFAUTO_EOF";
+
+/// Renders the steps as a POSIX sh script. Paths inside `temp_dir` are moved into a temp directory
+/// that the script creates itself and removes once it's done.
+#[must_use]
+pub fn render_script(steps: &[ScriptStep], temp_dir: &Path) -> String {
+	let temp_dir = temp_dir.to_string_lossy();
+	let temp_dir = temp_dir.trim_end_matches('/');
+
+	let mut dirs = BTreeSet::new();
+	let mut body = String::new();
+
+	for step in steps {
+		if !body.is_empty() {
+			body.push('\n');
+		}
+
+		match step {
+			ScriptStep::WriteFile { path, contents } => {
+				let target = script_word(path.as_os_str(), temp_dir, &mut dirs);
+
+				if let Some(text) = text_contents(contents) {
+					let text = heredoc_text(text, temp_dir);
+					let _ = write!(
+						body,
+						"cat > {target} <<{HEREDOC_DELIMITER}\n{text}{HEREDOC_DELIMITER}\n"
+					);
+				} else {
+					let _ = writeln!(body, ": > {target}");
+					for chunk in contents.chunks(64) {
+						let _ = writeln!(body, "printf '{}' >> {target}", octal_escape(chunk));
+					}
+				}
+			}
+			ScriptStep::Run { binary, args } => {
+				let mut command = quote(binary, Shell::Posix);
+				for arg in args {
+					command.push(' ');
+					command.push_str(&script_word(arg, temp_dir, &mut dirs));
+				}
+				let _ = writeln!(body, "{command}");
+			}
+		}
+	}
+
+	let mut script = String::from("#!/bin/sh\nset -e\n\n");
+	script.push_str("tmpdir=$(mktemp -d)\ntrap 'rm -rf \"$tmpdir\"' EXIT\n");
+	for dir in dirs {
+		let _ = writeln!(script, "mkdir -p \"$tmpdir\"{}", quote(&dir, Shell::Posix));
+	}
+	script.push('\n');
+	script.push_str(&body);
+
+	script
+}
+
+/// Quotes an argument for the script, pointing it to the script's temp directory if it's inside `temp_dir`.
+/// The directories such paths are in get added to `dirs`, since they need to exist.
+fn script_word(arg: &OsStr, temp_dir: &str, dirs: &mut BTreeSet<String>) -> String {
+	let Some(rest) = arg
+		.to_str()
+		.and_then(|arg| arg.strip_prefix(temp_dir))
+		.filter(|rest| rest.starts_with('/'))
+	else {
+		return quote(arg, Shell::Posix);
+	};
+
+	if let Some((dir, _)) = rest.rsplit_once('/')
+		&& !dir.is_empty()
+	{
+		dirs.insert(dir.to_string());
+	}
+
+	format!("\"$tmpdir\"{}", quote(rest, Shell::Posix))
+}
+
+/// Escapes text for an unquoted heredoc and points the paths in it that start with `temp_dir` to the script's
+/// temp directory. Only whole paths count, like `'/tmp/segment0.mkv'`, but not `/tmpfoo` or `/data/tmp/a`.
+fn heredoc_text(text: &str, temp_dir: &str) -> String {
+	let escape = |text: &str| {
+		text.replace('\\', r"\\")
+			.replace('$', r"\$")
+			.replace('`', r"\`")
+	};
+
+	let mut escaped = String::new();
+	let mut copied = 0;
+	for (start, _) in text.match_indices(temp_dir) {
+		let end = start + temp_dir.len();
+		let starts_path = text[..start]
+			.chars()
+			.next_back()
+			.is_none_or(|c| c.is_whitespace() || matches!(c, '\'' | '"' | '='));
+		if !starts_path || !text[end..].starts_with('/') {
+			continue;
+		}
+
+		escaped.push_str(&escape(&text[copied..start]));
+		escaped.push_str("${tmpdir}");
+		copied = end;
+	}
+	escaped.push_str(&escape(&text[copied..]));
+
+	escaped
+}
+
+/// Returns the contents if they can be put into a heredoc as they are.
+fn text_contents(contents: &[u8]) -> Option<&str> {
+	let text = std::str::from_utf8(contents).ok()?;
+	let is_text = text.ends_with('\n') && !text.contains(['\0', '\r']) && !text.lines().any(|l| l == HEREDOC_DELIMITER);
+
+	is_text.then_some(text)
+}
@@ -1,6 +1,7 @@
-use anyhow::Result;
+use crate::ffmpeg::error::FfError;
 use regex::{Captures, Regex};
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 #[derive(Clone, Default, Debug, PartialEq, Eq)]
 pub struct Size {
@@ -9,26 +10,35 @@ pub struct Size {
 }
 
 impl Size {
-	pub(crate) fn new(width: u64, height: u64) -> Self {
+	#[must_use]
+	pub fn new(width: u64, height: u64) -> Self {
 		Size { width, height }
 	}
 }
 
 impl TryFrom<String> for Size {
-	type Error = anyhow::Error;
+	type Error = FfError;
 
 	fn try_from(value: String) -> Result<Self, Self::Error> {
 		parse_ffmpeg_size(value)
 	}
 }
 
+impl FromStr for Size {
+	type Err = FfError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_ffmpeg_size(s)
+	}
+}
+
 impl Display for Size {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		write!(f, "{}x{}", self.width, self.height)
 	}
 }
 
-pub fn parse_ffmpeg_size<S: Into<String>>(size: S) -> Result<Size> {
+pub fn parse_ffmpeg_size<S: Into<String>>(size: S) -> Result<Size, FfError> {
 	let size: String = size.into();
 
 	// https://github.com/FFmpeg/FFmpeg/blob/00f5a34c9a5f0adee28aca11971918d6aca48745/libavutil/parseutils.c#L76
@@ -94,7 +104,9 @@ pub fn parse_ffmpeg_size<S: Into<String>>(size: S) -> Result<Size> {
 			let re = Regex::new(r"^(?P<W>\d+)x(?P<H>\d+)$").unwrap();
 			let groups: Captures = match re.captures(&size) {
 				None => {
-					anyhow::bail!("Invalid size string \"{size}\" provided")
+					return Err(FfError::InvalidValue(format!(
+						"Invalid size string \"{size}\" provided"
+					)));
 				}
 				Some(captures) => captures,
 			};
@@ -105,7 +117,113 @@ pub fn parse_ffmpeg_size<S: Into<String>>(size: S) -> Result<Size> {
 				return Ok(Size::new(width, height));
 			}
 
-			anyhow::bail!("Couldn't parse provided size string")
+			Err(FfError::InvalidValue("Couldn't parse provided size string".to_string()))
+		}
+	}
+}
+
+/// How the output video gets resized, as given to `--vs`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Resize {
+	/// Fits into the size while keeping the aspect ratio, e.g. `1280x720` or `hd720`.
+	Fit(Size),
+	/// Scales to exactly the size, stretching the video if the aspect ratio differs, e.g. `1280x720!`.
+	Exact(Size),
+	/// Fills the size while keeping the aspect ratio and crops whatever sticks out, e.g. `1080x1080^`.
+	Fill(Size),
+	/// Scales to the width while keeping the aspect ratio, e.g. `1280x`.
+	Width(u64),
+	/// Scales to the height while keeping the aspect ratio, e.g. `x720`.
+	Height(u64),
+	/// Scales both dimensions by a percentage, e.g. `50%`.
+	Percent(f64),
+}
+
+impl Resize {
+	/// Checks that an exact or filled size can be encoded as 4:2:0 video, whose chroma planes have half the
+	/// resolution and need even dimensions. The other kinds leave the rounding to the `scale` filter.
+	///
+	/// # Errors
+	///
+	/// Returns [`FfError::InvalidValue`] if the width or height of an exact or filled size is odd.
+	pub fn require_even(&self) -> Result<(), FfError> {
+		match self {
+			Resize::Exact(size) | Resize::Fill(size) if size.width % 2 != 0 || size.height % 2 != 0 => {
+				Err(FfError::InvalidValue(format!(
+					"The size {}x{} has to be even in both dimensions to be encoded as 4:2:0 video",
+					size.width, size.height
+				)))
+			}
+			_ => Ok(()),
+		}
+	}
+}
+
+impl FromStr for Resize {
+	type Err = FfError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || FfError::InvalidValue(format!("Invalid size string \"{s}\" provided"));
+		let positive = |value: &str| {
+			value
+				.parse::<u64>()
+				.ok()
+				.filter(|v| *v > 0)
+				.ok_or_else(invalid)
+		};
+
+		if let Some(percent) = s.strip_suffix('%') {
+			return percent
+				.parse::<f64>()
+				.ok()
+				.filter(|p| p.is_finite() && *p > 0.0)
+				.map(Resize::Percent)
+				.ok_or_else(invalid);
 		}
+		if let Some(height) = s.strip_prefix('x') {
+			return positive(height).map(Resize::Height);
+		}
+		if let Some(width) = s.strip_suffix('x') {
+			return positive(width).map(Resize::Width);
+		}
+
+		let (size, resize): (&str, fn(Size) -> Resize) = if let Some(size) = s.strip_suffix('!') {
+			(size, Resize::Exact)
+		} else if let Some(size) = s.strip_suffix('^') {
+			(size, Resize::Fill)
+		} else {
+			(s, Resize::Fit)
+		};
+
+		let size = parse_ffmpeg_size(size)?;
+		if size.width == 0 || size.height == 0 {
+			return Err(invalid());
+		}
+		Ok(resize(size))
+	}
+}
+
+/// A display aspect ratio like `16:9`, as given to `--aspect`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AspectRatio {
+	pub width: u64,
+	pub height: u64,
+}
+
+impl FromStr for AspectRatio {
+	type Err = FfError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		s.split_once(':')
+			.and_then(|(w, h)| Some((w.parse::<u64>().ok()?, h.parse::<u64>().ok()?)))
+			.filter(|(w, h)| *w > 0 && *h > 0)
+			.map(|(width, height)| AspectRatio { width, height })
+			.ok_or_else(|| FfError::InvalidValue(format!("Invalid aspect ratio \"{s}\" provided, expected W:H")))
+	}
+}
+
+impl Display for AspectRatio {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}:{}", self.width, self.height)
 	}
 }
@@ -0,0 +1,128 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::ffmpeg::error::{FfError, path_to_str};
+use crate::ffmpeg::ffprobe_struct::{FFProbeOutput, Stream, StreamType};
+use crate::ffmpeg::filtergraph::Filter;
+
+/// How a subtitle codec stores its cues, which decides how it can be burned into the video.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubtitleKind {
+	/// Text cues like SRT, ASS or `WebVTT`, rendered by libass with the `subtitles` or `ass` filter.
+	Text,
+	/// Pre-rendered images like PGS or DVD subtitles, which are put on top of the video with `overlay`.
+	Bitmap,
+}
+
+impl SubtitleKind {
+	/// Returns the kind of the given subtitle codec, or [None] if it's neither a known text nor bitmap codec.
+	#[must_use]
+	pub fn from_codec(codec_name: &str) -> Option<Self> {
+		match codec_name {
+			"subrip" | "srt" | "ass" | "ssa" | "webvtt" | "mov_text" | "text" | "microdvd" | "subviewer" => {
+				Some(SubtitleKind::Text)
+			}
+			"hdmv_pgs_subtitle" | "dvd_subtitle" | "dvb_subtitle" | "xsub" => Some(SubtitleKind::Bitmap),
+			_ => None,
+		}
+	}
+
+	/// Returns the kind of the given stream's codec.
+	#[must_use]
+	pub fn of_stream(stream: &Stream) -> Option<Self> {
+		stream
+			.codec_name
+			.as_deref()
+			.and_then(SubtitleKind::from_codec)
+	}
+}
+
+/// Returns the attachments that are fonts, like the ones MKV files with styled ASS subtitles carry.
+#[must_use]
+pub fn font_attachments(probe: &FFProbeOutput) -> Vec<&Stream> {
+	probe
+		.streams
+		.iter()
+		.filter(|stream| stream.codec_type == StreamType::Attachment)
+		.filter(|stream| {
+			let tags = stream.tags.as_ref();
+			let mimetype = tags.and_then(|t| t.mimetype.as_deref()).unwrap_or_default();
+			let extension = tags
+				.and_then(|t| t.filename.as_deref())
+				.and_then(|filename| Path::new(filename).extension())
+				.and_then(|ext| ext.to_str())
+				.map(str::to_ascii_lowercase);
+
+			mimetype.starts_with("font/")
+				|| mimetype.contains("truetype")
+				|| mimetype.contains("opentype")
+				|| matches!(extension.as_deref(), Some("ttf" | "otf" | "ttc"))
+		})
+		.collect()
+}
+
+/// Returns the path a font attachment is dumped to within `fonts_dir`. Only the file name of the attachment is
+/// used, so it can't point outside the directory, and the stream index keeps attachments with the same name apart.
+#[must_use]
+pub fn font_attachment_path(fonts_dir: &Path, attachment: &Stream) -> PathBuf {
+	let filename = attachment
+		.tags
+		.as_ref()
+		.and_then(|t| t.filename.as_deref())
+		.and_then(|filename| Path::new(filename).file_name())
+		.map_or_else(|| "font.ttf".to_string(), |name| name.to_string_lossy().into_owned());
+
+	fonts_dir.join(format!("{}-{filename}", attachment.index))
+}
+
+/// Returns the filter that renders the given text subtitles.
+///
+/// `stream_index` selects a subtitle stream by its index among the file's subtitle streams, for subtitles
+/// that are embedded in a media file. Without it, `filename` is a subtitle file, and ASS files are rendered
+/// with the `ass` filter, which keeps their styling exactly as authored.
+#[must_use]
+pub fn text_subtitle_filter(filename: &Path, stream_index: Option<usize>) -> &'static str {
+	let is_ass = stream_index.is_none()
+		&& filename
+			.extension()
+			.and_then(|ext| ext.to_str())
+			.is_some_and(|ext| ext.eq_ignore_ascii_case("ass") || ext.eq_ignore_ascii_case("ssa"));
+
+	if is_ass { "ass" } else { "subtitles" }
+}
+
+/// Builds the filters that render text subtitles onto the video with [`text_subtitle_filter`].
+///
+/// The filters read `filename` on their own from its start, while a seeked input starts its timestamps at 0.
+/// With a `seek`, the frames are moved to their original time while the subtitles are rendered and back afterwards.
+///
+/// # Errors
+///
+/// Returns [`FfError::InvalidPath`] if `filename` or `fonts_dir` isn't valid UTF-8, which filter options need.
+pub fn text_subtitle_filters(
+	filename: &Path,
+	stream_index: Option<usize>,
+	fonts_dir: Option<&Path>,
+	seek: Option<Duration>,
+) -> Result<Vec<Filter>, FfError> {
+	let mut subtitles =
+		Filter::new(text_subtitle_filter(filename, stream_index)).arg("filename", path_to_str(filename)?);
+	if let Some(index) = stream_index {
+		subtitles = subtitles.arg("si", index);
+	}
+	if let Some(fonts_dir) = fonts_dir {
+		subtitles = subtitles.arg("fontsdir", path_to_str(fonts_dir)?);
+	}
+
+	Ok(match seek.filter(|seek| !seek.is_zero()) {
+		None => vec![subtitles],
+		Some(seek) => {
+			let seek = seek.as_secs_f64();
+			vec![
+				Filter::new("setpts").positional(format!("PTS+{seek}/TB")),
+				subtitles,
+				Filter::new("setpts").positional(format!("PTS-{seek}/TB")),
+			]
+		}
+	})
+}
@@ -1,7 +1,21 @@
+use std::sync::LazyLock;
 use std::time::Duration;
 
 use regex::{Captures, Regex};
 
+static FFMPEG_DURATION_RE: LazyLock<Regex> = LazyLock::new(|| {
+	Regex::new(r"^(?:(?:(?P<hours>\d+):)?(?P<minutes>\d+):)?(?P<seconds>\d+)(?:\.?(?P<millis>\d+))?$").unwrap()
+});
+static UNIT_DURATION_RE: LazyLock<Regex> = LazyLock::new(|| {
+	Regex::new(
+		r"^(?:(?P<h>\d+(?:\.\d+)?)h)?(?:(?P<m>\d+(?:\.\d+)?)m)?(?:(?P<s>\d+(?:\.\d+)?)s)?(?:(?P<ms>\d+(?:\.\d+)?)ms)?(?:(?P<us>\d+(?:\.\d+)?)us)?$",
+	)
+	.unwrap()
+});
+static TIMECODE_RE: LazyLock<Regex> = LazyLock::new(|| {
+	Regex::new(r"^(?P<hours>\d+):(?P<minutes>\d{2}):(?P<seconds>\d{2})(?P<separator>[:;])(?P<frames>\d+)$").unwrap()
+});
+
 /// Takes an ffmpeg-esque duration string and parses it into a [Duration].
 /// Invalid input will return [None].
 pub fn parse_ffmpeg_duration<S: Into<String>>(timestamp: S) -> Option<Duration> {
@@ -12,13 +26,11 @@ pub fn parse_ffmpeg_duration<S: Into<String>>(timestamp: S) -> Option<Duration>
 	}
 
 	if let Ok(f) = timestamp.parse::<f64>() {
-		return Some(Duration::from_secs_f64(f));
+		// negative, infinite and NaN values don't fit a Duration
+		return Duration::try_from_secs_f64(f).ok();
 	}
 
-	let re =
-		Regex::new(r"^(?:(?:(?P<hours>\d+):)?(?P<minutes>\d+):)?(?P<seconds>\d+)(?:\.?(?P<millis>\d+))?$").unwrap();
-
-	let groups: Captures = match re.captures(&timestamp) {
+	let groups: Captures = match FFMPEG_DURATION_RE.captures(&timestamp) {
 		None => {
 			#[cfg(debug_assertions)]
 			eprintln!("invalid duration string: {timestamp}");
@@ -58,10 +70,159 @@ pub fn parse_ffmpeg_duration<S: Into<String>>(timestamp: S) -> Option<Duration>
 	Some(duration)
 }
 
+/// Returns the number of frames a timecode counts per second at the given frame rate, e.g. 30 for 29.97 fps.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn timecode_rate(frame_rate: f64) -> u64 {
+	frame_rate.round() as u64
+}
+
+/// Returns how many frame numbers drop-frame timecodes skip at the start of each minute, or [None] if
+/// the frame rate has no drop-frame timecodes. Only the NTSC rates 29.97 and 59.94 fps (and their multiples) do.
+#[allow(clippy::cast_precision_loss)]
+fn dropped_frames(frame_rate: f64) -> Option<u64> {
+	let rate = timecode_rate(frame_rate);
+	let ntsc = rate as f64 * 1000.0 / 1001.0;
+	if rate.is_multiple_of(30) && (frame_rate - ntsc).abs() < 0.01 {
+		Some(rate / 15)
+	} else {
+		None
+	}
+}
+
+/// Parses a duration with unit suffixes, like `500ms`, `250000us`, `1.5s`, `2m` or `1h30m`.
+/// Units have to go from hours down to microseconds, and each one can only appear once.
+fn parse_unit_duration(timestamp: &str) -> Option<Duration> {
+	let groups = UNIT_DURATION_RE.captures(timestamp)?;
+
+	let mut seconds = None;
+	for (unit, factor) in [("h", 3600.0), ("m", 60.0), ("s", 1.0), ("ms", 1e-3), ("us", 1e-6)] {
+		if let Some(value) = groups.name(unit) {
+			seconds = Some(seconds.unwrap_or(0.0) + value.as_str().parse::<f64>().ok()? * factor);
+		}
+	}
+
+	Duration::try_from_secs_f64(seconds?).ok()
+}
+
+/// Takes a timestamp and parses it into a [Duration]. On top of everything [`parse_ffmpeg_duration`] accepts,
+/// this understands unit suffixes (`1h30m`, `500ms`), SMPTE timecodes (`HH:MM:SS:FF`, or `HH:MM:SS;FF` for drop-frame) and frame numbers (`f1234`),
+/// which are resolved against the given frame rate.
+///
+/// Those return [None] if the frame rate is unknown, or if the timecode doesn't exist at that frame rate.
+#[allow(clippy::cast_precision_loss)]
+pub fn parse_timestamp(timestamp: &str, frame_rate: Option<f64>) -> Option<Duration> {
+	let frame_number = timestamp.strip_prefix('f').map(str::parse::<u64>);
+	let frame_rate = frame_rate.filter(|fps| *fps > 0.0);
+
+	let frame = match (TIMECODE_RE.captures(timestamp), frame_number) {
+		(Some(groups), _) => {
+			let frame_rate = frame_rate?;
+			let field = |name: &str| groups[name].parse::<u64>().ok();
+			let (hours, minutes, seconds, frames) =
+				(field("hours")?, field("minutes")?, field("seconds")?, field("frames")?);
+
+			let rate = timecode_rate(frame_rate);
+			if minutes >= 60 || seconds >= 60 || frames >= rate {
+				return None;
+			}
+
+			let total_minutes = hours * 60 + minutes;
+			let frame = (total_minutes * 60 + seconds) * rate + frames;
+			if &groups["separator"] == ";" {
+				let dropped = dropped_frames(frame_rate)?;
+				// the first frame numbers of every minute except each tenth don't exist
+				if seconds == 0 && frames < dropped && !minutes.is_multiple_of(10) {
+					return None;
+				}
+				frame - dropped * (total_minutes - total_minutes / 10)
+			} else {
+				frame
+			}
+		}
+		(None, Some(Ok(frame))) => frame,
+		(None, _) => return parse_unit_duration(timestamp).or_else(|| parse_ffmpeg_duration(timestamp)),
+	};
+
+	Some(Duration::from_secs_f64(frame as f64 / frame_rate?))
+}
+
+/// A timestamp that may be relative to the end of the input, which is only known once it has been probed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timestamp {
+	/// An offset from the start, like `90` or `00:01:30`.
+	Start(Duration),
+	/// An offset from the end, like `-10` or `-5s`.
+	End(Duration),
+	/// A percentage of the whole duration, like `50%`.
+	Percent(f64),
+}
+
+impl Timestamp {
+	/// Resolves the timestamp against the input's total duration.
+	/// Returns [None] if it lies before the start or, for relative values, after the end.
+	#[must_use]
+	pub fn resolve(self, total: Duration) -> Option<Duration> {
+		match self {
+			Timestamp::Start(offset) => Some(offset),
+			Timestamp::End(offset) => total.checked_sub(offset),
+			Timestamp::Percent(percent) if (0.0..=100.0).contains(&percent) => Some(total.mul_f64(percent / 100.0)),
+			Timestamp::Percent(_) => None,
+		}
+	}
+}
+
+/// Takes a timestamp like [`parse_timestamp`], but also accepts values relative to the end,
+/// either negative (`-10`, `-5s`, `-00:00:05:00`) or as a percentage of the whole duration (`50%`).
+#[must_use]
+pub fn parse_relative_timestamp(timestamp: &str, frame_rate: Option<f64>) -> Option<Timestamp> {
+	if let Some(percent) = timestamp.strip_suffix('%') {
+		return percent
+			.parse::<f64>()
+			.ok()
+			.filter(|p| p.is_finite())
+			.map(Timestamp::Percent);
+	}
+
+	match timestamp.strip_prefix('-') {
+		Some(offset) => parse_timestamp(offset, frame_rate).map(Timestamp::End),
+		None => parse_timestamp(timestamp, frame_rate).map(Timestamp::Start),
+	}
+}
+
 pub enum TimestampFormat {
 	Auto,
 	Full,
 	TwoDigits,
+	Smpte { frame_rate: f64, drop_frame: bool },
+}
+
+/// Formats the frame closest to the given [Duration] as a SMPTE timecode. Drop-frame timecodes are only
+/// used if the frame rate has them, otherwise frames are counted at the rounded frame rate.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn format_smpte_timecode(duration: Duration, frame_rate: f64, drop_frame: bool) -> String {
+	let rate = timecode_rate(frame_rate).max(1);
+	let mut frame = (duration.as_secs_f64() * frame_rate).round() as u64;
+
+	let dropped = dropped_frames(frame_rate).filter(|_| drop_frame);
+	if let Some(dropped) = dropped {
+		// add back the frame numbers that were skipped up to this frame
+		let frames_per_minute = rate * 60 - dropped;
+		let frames_per_ten_minutes = frames_per_minute * 10 + dropped;
+		let (tens, rest) = (frame / frames_per_ten_minutes, frame % frames_per_ten_minutes);
+		frame += dropped * 9 * tens;
+		if rest > dropped {
+			frame += dropped * ((rest - dropped) / frames_per_minute);
+		}
+	}
+
+	let separator = if dropped.is_some() { ';' } else { ':' };
+	let (seconds_total, frames) = (frame / rate, frame % rate);
+	format!(
+		"{:0>2}:{:0>2}:{:0>2}{separator}{frames:0>2}",
+		seconds_total / 3600,
+		seconds_total % 3600 / 60,
+		seconds_total % 60
+	)
 }
 
 /// Takes a [Duration] and formats it like a timestamp ffmpeg would use. Mainly for display purposes.
@@ -71,6 +232,9 @@ pub enum TimestampFormat {
 /// Specifying `TimestampFormat::Full` will make this function always return a timestamp of format `HH:MM:SS.ffffff`.
 ///
 /// Specifying `TimestampFormat::TwoDigits` will make this function always return a timestamp of format `HH:MM:SS.ff`.
+///
+/// Specifying `TimestampFormat::Smpte` will make this function return the closest frame as a timecode of format
+/// `HH:MM:SS:FF`, or `HH:MM:SS;FF` if `drop_frame` is set and the frame rate has drop-frame timecodes.
 #[must_use]
 #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
 pub fn format_ffmpeg_timestamp(duration: Duration, format: &TimestampFormat) -> String {
@@ -103,5 +267,6 @@ pub fn format_ffmpeg_timestamp(duration: Duration, format: &TimestampFormat) ->
 				hours as u64, minutes as u64
 			)
 		}
+		TimestampFormat::Smpte { frame_rate, drop_frame } => format_smpte_timecode(duration, *frame_rate, *drop_frame),
 	}
 }
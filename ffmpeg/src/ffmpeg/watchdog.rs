@@ -0,0 +1,51 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::ffmpeg::error::FfError;
+
+/// Limits after which a running ffmpeg is killed. Both are off by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Watchdog {
+	/// How long ffmpeg may go without writing any progress.
+	pub stall_timeout: Option<Duration>,
+	/// How long a single ffmpeg run may take in total.
+	pub max_runtime: Option<Duration>,
+}
+
+static WATCHDOG: Mutex<Watchdog> = Mutex::new(Watchdog { stall_timeout: None, max_runtime: None });
+
+/// Applies the given limits to every following ffmpeg run.
+pub fn set_watchdog(watchdog: Watchdog) {
+	*WATCHDOG.lock().unwrap_or_else(PoisonError::into_inner) = watchdog;
+}
+
+#[must_use]
+pub fn watchdog() -> Watchdog {
+	*WATCHDOG.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Watchdog {
+	/// Returns [`FfError::TimedOut`] once the run has taken longer than [`Watchdog::max_runtime`], or
+	/// [`FfError::Stalled`] once there hasn't been any progress for [`Watchdog::stall_timeout`].
+	/// `last_position` is the output time of the last progress update, which ends up in the error.
+	pub fn check(
+		&self,
+		started: Instant,
+		last_progress: Instant,
+		last_position: Option<Duration>,
+	) -> Result<(), FfError> {
+		if let Some(limit) = self.max_runtime
+			&& started.elapsed() > limit
+		{
+			return Err(FfError::TimedOut { limit });
+		}
+
+		if let Some(timeout) = self.stall_timeout
+			&& last_progress.elapsed() > timeout
+		{
+			return Err(FfError::Stalled { timeout, last_position });
+		}
+
+		Ok(())
+	}
+}
@@ -80,7 +80,7 @@ pub fn is_empty(&self) -> bool {
 
 	fn guess_format<P: AsRef<Path>>(path: P) -> Option<PaletteFormat> {
 		let p = path.as_ref();
-		let ext = p.extension()?.to_str().unwrap().to_lowercase();
+		let ext = p.extension()?.to_str()?.to_lowercase();
 
 		match ext.as_str() {
 			"act" => Some(PaletteFormat::AdobeAct),
@@ -0,0 +1,51 @@
+use ffmpeg::ffmpeg::bitrate::{bitrate_per_second, keyframe_intervals, peak_bitrate};
+use ffmpeg::ffmpeg::ffprobe_struct::PacketInfo;
+
+fn packet(stream_index: u64, time: f64, size: u64, key: bool) -> PacketInfo {
+	PacketInfo {
+		stream_index,
+		codec_type: None,
+		pts_time: Some(time),
+		dts_time: Some(time),
+		duration_time: None,
+		size,
+		key,
+		discard: false,
+	}
+}
+
+fn packets() -> Vec<PacketInfo> {
+	vec![
+		packet(0, 0.0, 1000, true),
+		packet(1, 0.0, 100, true),
+		packet(0, 0.5, 500, false),
+		packet(0, 1.0, 2000, true),
+		packet(1, 1.2, 100, true),
+		packet(0, 1.5, 4000, false),
+		packet(0, 3.5, 250, true),
+	]
+}
+
+#[test]
+fn per_second_buckets() {
+	let packets = packets();
+	assert_eq!(bitrate_per_second(&packets, 0), vec![12000, 48000, 0, 2000]);
+	assert_eq!(bitrate_per_second(&packets, 1), vec![800, 800]);
+	assert!(bitrate_per_second(&packets, 2).is_empty());
+}
+
+#[test]
+fn sliding_window_peak() {
+	let packets = packets();
+
+	// the window starting at 1.0s contains 2000 + 4000 bytes
+	assert!((peak_bitrate(&packets, 0, 1.0) - 48000.0).abs() < f64::EPSILON);
+	// the window starting at 0.0s contains 1000 + 500 + 2000 + 4000 bytes
+	assert!((peak_bitrate(&packets, 0, 2.0) - 30000.0).abs() < f64::EPSILON);
+	assert!(peak_bitrate(&packets, 2, 1.0).abs() < f64::EPSILON);
+}
+
+#[test]
+fn keyframe_distances() {
+	assert_eq!(keyframe_intervals(&packets(), 0), vec![1.0, 2.5]);
+}
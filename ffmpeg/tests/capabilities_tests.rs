@@ -0,0 +1,94 @@
+use ffmpeg::ffmpeg::capabilities::{Capabilities, parse_encoders, parse_filters, parse_pix_fmts, parse_version};
+use ffmpeg::ffmpeg::error::FfError;
+
+const VERSION: &str = "ffmpeg version 7.1 Copyright (c) 2000-2024 the FFmpeg developers
+built with Apple clang version 16.0.0 (clang-1600.0.26.4)
+configuration: --prefix=/opt/homebrew/Cellar/ffmpeg/7.1 --enable-libx264 --enable-libx265";
+
+const ENCODERS: &str = "Encoders:
+ V..... = Video
+ A..... = Audio
+ S..... = Subtitle
+ .F.... = Frame-level multithreading
+ ..S... = Slice-level multithreading
+ ...X.. = Codec is experimental
+ ....B. = Supports draw_horiz_band
+ .....D = Supports direct rendering method 1
+ ------
+ V....D gif                  GIF (Graphics Interchange Format)
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)
+ A....D aac                  AAC (Advanced Audio Coding)";
+
+const FILTERS: &str = "Filters:
+  T.. = Timeline support
+  .S. = Slice threading
+  ..C = Command support
+  A = Audio input/output
+  V = Video input/output
+  N = Dynamic number and/or type of input/output
+  | = Source or sink filter
+ ... afade             A->A       Fade in/out input audio.
+ TSC colorspace        V->V       Convert between colorspaces.
+ ..C tonemap           V->V       Conversion to/from different dynamic ranges.
+ ... amix              N->A       Audio mixing.";
+
+const PIX_FMTS: &str = "Pixel formats:
+I.... = Supported Input  format for conversion
+.O... = Supported Output format for conversion
+..H.. = Hardware accelerated format
+...P. = Paletted format
+....B = Bitstream format
+FLAGS NAME            NB_COMPONENTS BITS_PER_PIXEL BIT_DEPTHS
+-----
+IO... yuv420p                3             12      8-8-8
+IO... yuv420p10le            3             15      10-10-10";
+
+#[test]
+fn parses_ffmpeg_output() {
+	assert_eq!(parse_version(VERSION), "7.1");
+
+	let encoders = parse_encoders(ENCODERS);
+	assert_eq!(encoders.len(), 3);
+	assert!(encoders.contains("libx264"));
+	assert!(!encoders.contains("V....."));
+
+	let filters = parse_filters(FILTERS);
+	assert_eq!(filters.len(), 4);
+	assert!(filters.contains("tonemap"));
+	assert!(filters.contains("amix"));
+	assert!(!filters.contains("="));
+
+	let pix_fmts = parse_pix_fmts(PIX_FMTS);
+	assert_eq!(pix_fmts.len(), 2);
+	assert!(pix_fmts.contains("yuv420p10le"));
+}
+
+#[test]
+fn reports_missing_capabilities() {
+	let capabilities = Capabilities {
+		version: parse_version(VERSION),
+		encoders: parse_encoders(ENCODERS),
+		filters: parse_filters(FILTERS),
+		pix_fmts: parse_pix_fmts(PIX_FMTS),
+	};
+
+	assert!(capabilities.require_encoders(&["libx264", "aac"]).is_ok());
+
+	let missing = capabilities
+		.require_filters(&["tonemap", "zscale"])
+		.unwrap_err();
+	assert!(matches!(&missing, FfError::MissingCapability { kind: "filter", name } if name == "zscale"));
+	assert_eq!(
+		missing.to_string(),
+		"Your ffmpeg build has no zscale filter, it needs to be built with --enable-libzimg"
+	);
+
+	assert!(capabilities.require_pix_fmts(&["yuv420p"]).is_ok());
+	assert_eq!(
+		capabilities
+			.require_pix_fmts(&["yuv420p12le"])
+			.unwrap_err()
+			.to_string(),
+		"Your ffmpeg build has no yuv420p12le pixel format"
+	);
+}
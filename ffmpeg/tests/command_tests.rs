@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use ffmpeg::ffmpeg::command::{FfmpegCommand, Input, Output};
+use ffmpeg::ffmpeg::filtergraph::{Chain, Filter, Graph, Label};
+
+#[test]
+fn input_options_come_before_their_input() {
+	let mut input = Input::new("in.mkv");
+	input.option("thread_queue_size", 512);
+	input.duration(Duration::from_secs(10));
+	input.seek(Duration::from_millis(1500));
+	input.hwaccel("videotoolbox");
+
+	let mut output = Output::new("out.mp4");
+	output
+		.codec("v", "libx264")
+		.duration(Duration::from_secs(10));
+
+	let mut command = FfmpegCommand::new();
+	command
+		.output(output)
+		.input(input)
+		.hide_banner()
+		.overwrite();
+
+	assert_eq!(
+		command.args(),
+		[
+			"-hide_banner",
+			"-y",
+			"-hwaccel",
+			"videotoolbox",
+			"-ss",
+			"1.5",
+			"-t",
+			"10",
+			"-thread_queue_size",
+			"512",
+			"-i",
+			"in.mkv",
+			"-t",
+			"10",
+			"-c:v",
+			"libx264",
+			"out.mp4",
+		]
+	);
+}
+
+#[test]
+fn serializes_outputs() {
+	let mut output = Output::new("out.mkv");
+	output
+		.stream_metadata("1", "language", "jpn")
+		.flag("sn")
+		.video_filter(
+			[Filter::new("scale").arg("w", 1280).arg("h", -2)]
+				.into_iter()
+				.collect(),
+		)
+		.audio_filter(Chain::new())
+		.codec("", "copy")
+		.map("0:v:0")
+		.map("0:a:m:language:jpn")
+		.metadata("title", "a=b")
+		.stream_metadata("", "handler_name", "")
+		.format("matroska");
+
+	let mut command = FfmpegCommand::new();
+	command.input(Input::new("in.mkv")).output(output);
+
+	assert_eq!(
+		command.args(),
+		[
+			"-i",
+			"in.mkv",
+			"-map",
+			"0:v:0",
+			"-map",
+			"0:a:m:language:jpn",
+			"-c",
+			"copy",
+			"-sn",
+			"-vf",
+			"scale=w=1280:h=-2",
+			"-metadata:s:1",
+			"language=jpn",
+			"-metadata",
+			"title=a=b",
+			"-metadata:s",
+			"handler_name=",
+			"-f",
+			"matroska",
+			"out.mkv",
+		]
+	);
+}
+
+#[test]
+fn serializes_multiple_inputs_and_a_filtergraph() {
+	let mut concat = Input::new("list.txt");
+	concat.format("concat").option("safe", 0);
+
+	let mut graph = Graph::new();
+	let video = graph.label("v");
+	graph.push(
+		Chain::new()
+			.inputs([Label::new("0:v:0"), Label::new("1:s:0")])
+			.filter(Filter::new("overlay"))
+			.output(video.clone()),
+	);
+
+	let mut output = Output::new("out.mp4");
+	output.map(video.to_string()).map("0:a?");
+
+	let mut command = FfmpegCommand::new();
+	command.loglevel("error").input(Input::new("in.mkv"));
+	assert_eq!(command.input_count(), 1);
+	command.input(concat).filter_complex(graph).output(output);
+
+	assert_eq!(
+		command.args(),
+		[
+			"-loglevel",
+			"error",
+			"-i",
+			"in.mkv",
+			"-f",
+			"concat",
+			"-safe",
+			"0",
+			"-i",
+			"list.txt",
+			"-filter_complex",
+			"[0:v:0][1:s:0]overlay[v1]",
+			"-map",
+			"[v1]",
+			"-map",
+			"0:a?",
+			"out.mp4",
+		]
+	);
+}
@@ -4,7 +4,20 @@
 //
 // 1234x5678,9;0
 
-use ffmpeg::ffmpeg::enums::Crop;
+use ffmpeg::ffmpeg::enums::{Anchor, Crop, CropLength, CropPosition, CropRegion, CropSpec};
+use ffmpeg::ffmpeg::ffprobe_struct::Stream;
+use ffmpeg::ffmpeg::size::AspectRatio;
+
+fn stream(width: u64, height: u64, sar: &str) -> Stream {
+	serde_json::from_str(&format!(
+		r#"{{"index": 0, "codec_type": "video", "width": {width}, "height": {height}, "sample_aspect_ratio": "{sar}"}}"#
+	))
+	.unwrap()
+}
+
+fn crop(width: u64, height: u64, x: u64, y: u64) -> Crop {
+	Crop { width, height, x: Some(x), y: Some(y) }
+}
 
 #[rustfmt::skip]
 fn crop_valid() -> Vec<(String, Crop)> {
@@ -19,15 +32,19 @@ fn crop_valid() -> Vec<(String, Crop)> {
 		),
 		(
 			String::from("3840x1608;32x64"),
-			Crop { width: 3840, height: 1608, x: 32, y: 64 },
+			Crop { width: 3840, height: 1608, x: Some(32), y: Some(64) },
 		),
 		(
 			String::from("3840x1608x32x64"),
-			Crop { width: 3840, height: 1608, x: 32, y: 64 },
+			Crop { width: 3840, height: 1608, x: Some(32), y: Some(64) },
+		),
+		(
+			String::from("1234x5678,0;0"),
+			Crop { width: 1234, height: 5678, x: Some(0), y: Some(0) },
 		),
 		(
 			String::from("3840:1608:32:64"),
-			Crop { width: 3840, height: 1608, x: 32, y: 64 },
+			Crop { width: 3840, height: 1608, x: Some(32), y: Some(64) },
 		),
 	]
 }
@@ -41,6 +58,10 @@ fn crop_invalid() -> Vec<String> {
 		String::from("-9000"),
 		String::from("3840x1608;32"),
 		String::from("3840x-1608;32x64"),
+		String::from("16:9"),
+		String::from("50%"),
+		String::from("1280x720,top"),
+		String::from("t=10"),
 	]
 }
 
@@ -55,3 +76,123 @@ fn crop_parsing() {
 		let _ = Crop::new(crop_str).unwrap_err();
 	}
 }
+
+#[test]
+fn crop_display() {
+	assert_eq!(Crop::new("1280x720").unwrap().to_string(), "w=1280:h=720");
+	assert_eq!(Crop::new("1280x720,0;0").unwrap().to_string(), "w=1280:h=720:x=0:y=0");
+	assert_eq!(Crop::new("720").unwrap().to_string(), "h=720");
+}
+
+#[test]
+fn crop_filter() {
+	// detected crops keep their offsets, also at the edges, or letterboxes that aren't centered get re-centered
+	assert_eq!(
+		crop(1920, 800, 0, 140).filter().to_string(),
+		"crop=w=1920:h=800:x=0:y=140"
+	);
+	assert_eq!(crop(1900, 800, 0, 0).filter().to_string(), "crop=w=1900:h=800:x=0:y=0");
+	assert_eq!(
+		crop(1600, 700, 160, 190).filter().to_string(),
+		"crop=w=1600:h=700:x=160:y=190"
+	);
+	// without offsets, ffmpeg centers the crop
+	assert_eq!(Crop::new("1280x720").unwrap().filter().to_string(), "crop=w=1280:h=720");
+	assert_eq!(Crop::new("720").unwrap().filter().to_string(), "crop=h=720");
+}
+
+#[test]
+fn crop_region_parsing() {
+	assert_eq!(
+		"16:9".parse::<CropRegion>().unwrap(),
+		CropRegion::Aspect {
+			ratio: AspectRatio { width: 16, height: 9 },
+			anchor: Anchor::Center
+		}
+	);
+	assert_eq!(
+		"1:1,top-left".parse::<CropRegion>().unwrap(),
+		CropRegion::Aspect {
+			ratio: AspectRatio { width: 1, height: 1 },
+			anchor: Anchor::TopLeft
+		}
+	);
+	// `W:H` is always a ratio like `--aspect`, even with numbers that look like a size
+	assert_eq!(
+		"720:400".parse::<CropRegion>().unwrap(),
+		CropRegion::Aspect {
+			ratio: AspectRatio { width: 720, height: 400 },
+			anchor: Anchor::Center
+		}
+	);
+	assert_eq!(
+		"720x400".parse::<CropRegion>().unwrap(),
+		CropRegion::Rect {
+			width: Some(CropLength::Pixels(720)),
+			height: CropLength::Pixels(400),
+			position: CropPosition::Anchor(Anchor::Center),
+		}
+	);
+	assert_eq!(
+		"50%x720,bottom".parse::<CropRegion>().unwrap(),
+		CropRegion::Rect {
+			width: Some(CropLength::Percent(50.0)),
+			height: CropLength::Pixels(720),
+			position: CropPosition::Anchor(Anchor::Bottom),
+		}
+	);
+	assert_eq!(
+		"t=10,bottom=5%".parse::<CropRegion>().unwrap(),
+		CropRegion::Trim {
+			top: CropLength::Pixels(10),
+			bottom: CropLength::Percent(5.0),
+			left: CropLength::Pixels(0),
+			right: CropLength::Pixels(0),
+		}
+	);
+	assert_eq!("auto".parse::<CropSpec>().unwrap(), CropSpec::Auto);
+
+	for invalid in [
+		"t=10,t=20",
+		"x=10",
+		"t=",
+		"t=10,",
+		"1280x720,10;10,top",
+		"0%",
+		"101%",
+		"1280x720,middle",
+		"16:0",
+		"16/9",
+		"16:9:1",
+	] {
+		let _ = invalid.parse::<CropRegion>().unwrap_err();
+	}
+}
+
+#[test]
+fn crop_region_resolving() {
+	let hd = stream(1920, 1080, "1:1");
+	let resolve = |region: &str| region.parse::<CropRegion>().unwrap().resolve(&hd);
+
+	assert_eq!(resolve("1280x720").unwrap(), crop(1280, 720, 320, 180));
+	assert_eq!(resolve("1280x720,10;20").unwrap(), crop(1280, 720, 10, 20));
+	assert_eq!(resolve("1280x720,bottom-right").unwrap(), crop(1280, 720, 640, 360));
+	assert_eq!(resolve("800,top").unwrap(), crop(1920, 800, 0, 0));
+	assert_eq!(resolve("50%x50%,right").unwrap(), crop(960, 540, 960, 270));
+	assert_eq!(resolve("1:1").unwrap(), crop(1080, 1080, 420, 0));
+	assert_eq!(resolve("9:16,left").unwrap(), crop(606, 1080, 0, 0));
+	assert_eq!(resolve("21:9").unwrap(), crop(1920, 822, 0, 129));
+	assert_eq!(resolve("t=10,b=10,l=4,r=4").unwrap(), crop(1912, 1060, 4, 10));
+	assert_eq!(resolve("l=10%").unwrap(), crop(1728, 1080, 192, 0));
+
+	// anamorphic DVD, 720x480 displayed at 16:9
+	let dvd = stream(720, 480, "32:27");
+	assert_eq!(
+		"4:3".parse::<CropRegion>().unwrap().resolve(&dvd).unwrap(),
+		crop(540, 480, 90, 0)
+	);
+
+	for invalid in ["2000x720", "1280x720,700;10", "t=540,b=540", "l=60%,r=40%"] {
+		let _ = resolve(invalid).unwrap_err();
+	}
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use ffmpeg::ffmpeg::cropdetect::{detect_crop, most_common_crop, parse_cropdetect};
+use ffmpeg::ffmpeg::enums::Crop;
+use ffmpeg::ffmpeg::error::FfError;
+use ffmpeg::ffmpeg::ffprobe_struct::Stream;
+use ffmpeg::ffmpeg::runner::{RecordingRunner, ReplayRunner, set_runner};
+
+const METADATA: &str = "frame:2    pts:2002    pts_time:0.0834167
+lavfi.cropdetect.x1=0
+lavfi.cropdetect.x2=1919
+lavfi.cropdetect.y1=142
+lavfi.cropdetect.y2=937
+lavfi.cropdetect.w=1920
+lavfi.cropdetect.h=794
+lavfi.cropdetect.x=0
+lavfi.cropdetect.y=144
+frame:3    pts:3003    pts_time:0.125125
+lavfi.cropdetect.x1=0
+lavfi.cropdetect.x2=1919
+lavfi.cropdetect.y1=138
+lavfi.cropdetect.y2=941
+lavfi.cropdetect.w=1920
+lavfi.cropdetect.h=800
+lavfi.cropdetect.x=0
+lavfi.cropdetect.y=140
+frame:4    pts:4004    pts_time:0.166833
+lavfi.cropdetect.w=-1918
+lavfi.cropdetect.h=800
+lavfi.cropdetect.x=0
+lavfi.cropdetect.y=140
+";
+
+fn crop(width: u64, height: u64, x: u64, y: u64) -> Crop {
+	Crop { width, height, x: Some(x), y: Some(y) }
+}
+
+fn stream(width: u64, height: u64) -> Stream {
+	serde_json::from_str(&format!(
+		r#"{{ "index": 0, "codec_type": "video", "width": {width}, "height": {height} }}"#
+	))
+	.unwrap()
+}
+
+#[test]
+fn parses_cropdetect_metadata() {
+	assert_eq!(
+		parse_cropdetect(METADATA),
+		[crop(1920, 794, 0, 144), crop(1920, 800, 0, 140)]
+	);
+	assert!(parse_cropdetect("").is_empty());
+}
+
+#[test]
+fn picks_the_most_common_crop() {
+	let letterbox = crop(1920, 800, 0, 140);
+	let dark_scene = crop(1600, 700, 160, 190);
+
+	assert_eq!(
+		most_common_crop(&[letterbox.clone(), dark_scene.clone(), letterbox.clone()]),
+		Some(letterbox.clone())
+	);
+	// ties go to the larger rectangle
+	assert_eq!(most_common_crop(&[dark_scene, letterbox.clone()]), Some(letterbox));
+	assert_eq!(most_common_crop(&[]), None);
+}
+
+// the runner is process-wide, so everything that uses it is in one test
+#[test]
+fn detects_crop_over_sample_windows() {
+	let recorder = Arc::new(RecordingRunner::new(Arc::new(
+		ReplayRunner::new()
+			.with_output("input.mkv", METADATA)
+			.with_output(
+				"full.mkv",
+				"frame:2\nlavfi.cropdetect.w=1920\nlavfi.cropdetect.h=1080\nlavfi.cropdetect.x=0\nlavfi.cropdetect.y=0\n",
+			),
+	)));
+	set_runner(recorder.clone());
+
+	let detected = detect_crop("input.mkv".as_ref(), &stream(1920, 1080), Duration::from_mins(1)).unwrap();
+	assert_eq!(detected, Some(crop(1920, 800, 0, 140)));
+
+	let calls = recorder.calls();
+	assert_eq!(calls.len(), 5);
+	assert_eq!(calls[0].args[3..7], ["-ss", "9", "-t", "2"]);
+	assert_eq!(calls[4].args[3..7], ["-ss", "49", "-t", "2"]);
+	assert!(
+		calls[0]
+			.args
+			.contains(&"cropdetect=round=2,metadata=mode=print:file=pipe\\\\:1".into())
+	);
+
+	assert_eq!(
+		detect_crop("full.mkv".as_ref(), &stream(1920, 1080), Duration::from_mins(1)).unwrap(),
+		None
+	);
+	assert!(matches!(
+		detect_crop("input.mkv".as_ref(), &stream(1280, 720), Duration::from_mins(1)),
+		Err(FfError::InvalidValue(_))
+	));
+}
@@ -0,0 +1,49 @@
+use ffmpeg::ffmpeg::enums::Crop;
+use ffmpeg::ffmpeg::error::{FfError, path_to_str};
+use ffmpeg::ffmpeg::ffprobe_struct::PacketInfo;
+use ffmpeg::ffmpeg::size::{Size, parse_ffmpeg_size};
+
+#[test]
+fn invalid_values() {
+	assert!(matches!(Crop::new("0x0"), Err(FfError::InvalidValue(_))));
+	assert!(matches!(parse_ffmpeg_size("huge"), Err(FfError::InvalidValue(_))));
+	assert!(matches!("1:2x:3".parse::<Crop>(), Err(FfError::InvalidValue(_))));
+	assert_eq!("hd720".parse::<Size>().unwrap(), Size::new(1280, 720));
+}
+
+#[test]
+fn probe_parse_errors() {
+	assert!(matches!(
+		"frame|key_frame=1".parse::<PacketInfo>(),
+		Err(FfError::ProbeParse(_))
+	));
+}
+
+#[test]
+fn non_zero_exit_message() {
+	let with_stderr = FfError::NonZeroExit {
+		binary: "ffprobe".to_string(),
+		code: Some(1),
+		stderr: "input.mkv: No such file or directory".to_string(),
+	};
+	assert_eq!(with_stderr.to_string(), "input.mkv: No such file or directory");
+
+	let without_stderr = FfError::NonZeroExit {
+		binary: "ffmpeg".to_string(),
+		code: Some(187),
+		stderr: String::new(),
+	};
+	assert_eq!(without_stderr.to_string(), "ffmpeg exited with status code 187");
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_path() {
+	use std::ffi::OsStr;
+	use std::os::unix::ffi::OsStrExt;
+	use std::path::Path;
+
+	let path = Path::new(OsStr::from_bytes(b"caf\xe9.mkv"));
+	assert!(matches!(path_to_str(path), Err(FfError::InvalidPath(_))));
+	assert_eq!(path_to_str(Path::new("café.mkv")).unwrap(), "café.mkv");
+}
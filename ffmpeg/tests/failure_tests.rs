@@ -0,0 +1,57 @@
+use ffmpeg::ffmpeg::error::FfError;
+use ffmpeg::ffmpeg::failure::{FailureKind, StderrTail};
+
+#[test]
+fn keeps_the_last_lines() {
+	let mut tail = StderrTail::new(3);
+	tail.push(b"line 1\nline 2\r\nli");
+	tail.push(b"ne 3\nframe=  10\rframe=  20\r\n\nline 4");
+	assert_eq!(tail.into_string(), "frame=  10\nframe=  20\nline 4");
+}
+
+#[test]
+fn classifies_failures() {
+	let cases = [
+		(
+			"[vost#0:0 @ 0x1] Unknown encoder 'libsvtav1'",
+			FailureKind::UnknownEncoder,
+		),
+		(
+			"[AVFilterGraph @ 0x1] No such filter: 'zscale'",
+			FailureKind::MissingFilter,
+		),
+		("Unrecognized option 'fps_mode'.", FailureKind::InvalidOption),
+		("out.mkv: Permission denied", FailureKind::PermissionDenied),
+		(
+			"av_interleaved_write_frame(): No space left on device",
+			FailureKind::DiskFull,
+		),
+		(
+			"input.mp4: Invalid data found when processing input",
+			FailureKind::InvalidData,
+		),
+	];
+
+	for (stderr, kind) in cases {
+		assert_eq!(FailureKind::classify(stderr), Some(kind), "{stderr}");
+	}
+	assert_eq!(FailureKind::classify("Conversion failed!"), None);
+}
+
+#[test]
+fn error_includes_suggestion() {
+	let error = FfError::NonZeroExit {
+		binary: "ffmpeg".to_string(),
+		code: Some(1),
+		stderr: "No such filter: 'zscale'\nError opening output files: Filter not found".to_string(),
+	};
+
+	assert_eq!(error.failure_kind(), Some(FailureKind::MissingFilter));
+	assert_eq!(
+		error.to_string(),
+		format!(
+			"No such filter: 'zscale'\nError opening output files: Filter not found\nmissing filter: {}",
+			FailureKind::MissingFilter.suggestion()
+		)
+	);
+}
@@ -0,0 +1,89 @@
+use ffmpeg::ffmpeg::filtergraph::{Chain, Filter, Graph, Label, escape_value};
+
+#[test]
+fn escapes_values() {
+	assert_eq!(escape_value("bt709"), "bt709");
+	assert_eq!(escape_value("eq(n,0)"), r"eq(n\,0)");
+	assert_eq!(escape_value("a=b"), r"a\\=b");
+	assert_eq!(
+		escape_value(r"C:\subs\a,b's [1].srt"),
+		r"C\\:\\\\subs\\\\a\,b\\\'s \[1\].srt"
+	);
+}
+
+#[test]
+fn formats_filters() {
+	assert_eq!(Filter::new("split").to_string(), "split");
+	assert_eq!(Filter::new("format").positional("rgb24").to_string(), "format=rgb24");
+	assert_eq!(
+		Filter::new("scale").arg("w", 1280).arg("h", -2).to_string(),
+		"scale=w=1280:h=-2"
+	);
+	assert_eq!(
+		Filter::new("crop")
+			.positional(1)
+			.positional(1)
+			.positional(0)
+			.positional(0)
+			.to_string(),
+		"crop=1:1:0:0"
+	);
+	assert_eq!(
+		Filter::new("subtitles")
+			.arg("filename", "a:b.srt")
+			.to_string(),
+		r"subtitles=filename=a\\:b.srt"
+	);
+}
+
+#[test]
+fn formats_chains() {
+	let chain = Chain::new()
+		.input(Label::new("0:v:0"))
+		.filter(Filter::new("fps").arg("fps", 10))
+		.filter(Filter::new("split"))
+		.outputs([Label::new("a"), Label::new("b")]);
+	assert_eq!(chain.to_string(), "[0:v:0]fps=fps=10,split[a][b]");
+
+	let mut unlabeled: Chain = [Filter::new("volume").positional("0.5")]
+		.into_iter()
+		.collect();
+	unlabeled.extend(None);
+	unlabeled.push(Filter::new("afade").arg("t", "in"));
+	assert_eq!(unlabeled.to_string(), "volume=0.5,afade=t=in");
+
+	assert!(Chain::new().input(Label::new("v")).is_empty());
+}
+
+#[test]
+fn allocates_unique_labels() {
+	let mut graph = Graph::new();
+	let a = graph.label("v");
+	let b = graph.label("v");
+	let pal = graph.label("pal");
+	assert_ne!(a, b);
+	assert_eq!(pal.to_string(), "[pal3]");
+
+	graph.push(
+		Chain::new()
+			.input(Label::new("0:v"))
+			.filter(Filter::new("split"))
+			.outputs([a.clone(), b.clone()]),
+	);
+	graph.push(
+		Chain::new()
+			.input(a)
+			.filter(Filter::new("palettegen"))
+			.output(pal.clone()),
+	);
+	graph.push(
+		Chain::new()
+			.inputs([b, pal])
+			.filter(Filter::new("paletteuse")),
+	);
+
+	assert_eq!(
+		graph.to_string(),
+		"[0:v]split[v1][v2];[v1]palettegen[pal3];[v2][pal3]paletteuse"
+	);
+}
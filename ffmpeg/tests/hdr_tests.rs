@@ -0,0 +1,160 @@
+use ffmpeg::ffmpeg::ffprobe_struct::{DoviCompatibility, FFProbeOutput, HdrFormat};
+
+fn probe_with_stream(stream_json: &str) -> FFProbeOutput {
+	let json = format!(r#"{{"streams": [{stream_json}], "format": {{"duration": "10.000000"}}}}"#);
+	serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn hdr10_side_data() {
+	let probe = probe_with_stream(
+		r#"{
+			"index": 0,
+			"codec_type": "video",
+			"color_transfer": "smpte2084",
+			"side_data_list": [
+				{
+					"side_data_type": "Mastering display metadata",
+					"red_x": "34000/50000",
+					"red_y": "16000/50000",
+					"green_x": "13250/50000",
+					"green_y": "34500/50000",
+					"blue_x": "7500/50000",
+					"blue_y": "3000/50000",
+					"white_point_x": "15635/50000",
+					"white_point_y": "16450/50000",
+					"min_luminance": "50/10000",
+					"max_luminance": "40000000/10000"
+				},
+				{
+					"side_data_type": "Content light level metadata",
+					"max_content": 1200,
+					"max_average": 400
+				},
+				{
+					"side_data_type": "Display Matrix",
+					"displaymatrix": "",
+					"rotation": -90
+				}
+			]
+		}"#,
+	);
+
+	let stream = probe.get_first_video_stream().unwrap();
+	assert_eq!(stream.hdr_format(), Some(HdrFormat::Hdr10));
+	assert!(stream.is_hdr());
+
+	let mastering = stream.mastering_display().unwrap();
+	assert!((mastering.red_x.unwrap() - 0.68).abs() < f64::EPSILON);
+	assert!((mastering.min_luminance.unwrap() - 0.005).abs() < f64::EPSILON);
+	assert!((mastering.max_luminance.unwrap() - 4000.0).abs() < f64::EPSILON);
+
+	let light_level = stream.content_light_level().unwrap();
+	assert_eq!(light_level.max_content, 1200);
+	assert_eq!(light_level.max_average, 400);
+
+	// MaxCLL wins over the mastering display peak
+	assert_eq!(stream.peak_luminance(), Some(1200.0));
+}
+
+#[test]
+fn luminance_only_mastering_display() {
+	// some muxers only write the luminance range, without the primaries and white point
+	let probe = probe_with_stream(
+		r#"{
+			"index": 0,
+			"codec_type": "video",
+			"color_transfer": "smpte2084",
+			"side_data_list": [
+				{
+					"side_data_type": "Mastering display metadata",
+					"max_luminance": "10000000/10000"
+				}
+			]
+		}"#,
+	);
+
+	let stream = probe.get_first_video_stream().unwrap();
+	let mastering = stream.mastering_display().unwrap();
+	assert_eq!(mastering.red_x, None);
+	assert_eq!(mastering.min_luminance, None);
+	assert_eq!(stream.peak_luminance(), Some(1000.0));
+}
+
+#[test]
+fn hlg_without_side_data() {
+	let probe = probe_with_stream(r#"{"index": 0, "codec_type": "video", "color_transfer": "arib-std-b67"}"#);
+
+	let stream = probe.get_first_video_stream().unwrap();
+	assert_eq!(stream.hdr_format(), Some(HdrFormat::Hlg));
+	assert!(stream.is_hdr());
+	assert_eq!(stream.peak_luminance(), None);
+}
+
+#[test]
+fn dolby_vision_profiles() {
+	let dovi_stream = |profile: u8, compatibility_id: u8, transfer: &str| {
+		probe_with_stream(&format!(
+			r#"{{
+				"index": 0,
+				"codec_type": "video",
+				"color_transfer": "{transfer}",
+				"side_data_list": [
+					{{
+						"side_data_type": "DOVI configuration record",
+						"dv_version_major": 1,
+						"dv_version_minor": 0,
+						"dv_profile": {profile},
+						"dv_level": 6,
+						"rpu_present_flag": 1,
+						"el_present_flag": 0,
+						"bl_present_flag": 1,
+						"dv_bl_signal_compatibility_id": {compatibility_id}
+					}}
+				]
+			}}"#
+		))
+	};
+
+	let profile5 = dovi_stream(5, 0, "unknown");
+	let stream = profile5.get_first_video_stream().unwrap();
+	assert_eq!(
+		stream.hdr_format(),
+		Some(HdrFormat::DolbyVision {
+			profile: 5,
+			compatibility: DoviCompatibility::None
+		})
+	);
+	assert!(stream.is_hdr());
+	assert!(stream.dovi_configuration().unwrap().rpu_present_flag);
+
+	let profile81 = dovi_stream(8, 1, "smpte2084");
+	assert_eq!(
+		profile81.get_first_video_stream().unwrap().hdr_format(),
+		Some(HdrFormat::DolbyVision {
+			profile: 8,
+			compatibility: DoviCompatibility::Hdr10
+		})
+	);
+
+	let profile84 = dovi_stream(8, 4, "arib-std-b67");
+	assert_eq!(
+		profile84.get_first_video_stream().unwrap().hdr_format(),
+		Some(HdrFormat::DolbyVision {
+			profile: 8,
+			compatibility: DoviCompatibility::Hlg
+		})
+	);
+
+	let profile82 = dovi_stream(8, 2, "bt709");
+	assert!(!profile82.get_first_video_stream().unwrap().is_hdr());
+}
+
+#[test]
+fn sdr_stream() {
+	let probe = probe_with_stream(r#"{"index": 0, "codec_type": "video", "color_transfer": "bt709"}"#);
+
+	let stream = probe.get_first_video_stream().unwrap();
+	assert_eq!(stream.hdr_format(), None);
+	assert!(!stream.is_hdr());
+}
@@ -0,0 +1,69 @@
+use ffmpeg::ffmpeg::ffprobe_struct::PacketInfo;
+use ffmpeg::ffmpeg::keyframes::{
+	CutSegment, keyframe_at_or_after, keyframe_at_or_before, keyframe_times, keyframes_from_start, smart_cut_segments,
+};
+
+fn packet(stream_index: u64, time: f64, key: bool) -> PacketInfo {
+	PacketInfo {
+		stream_index,
+		codec_type: None,
+		pts_time: Some(time),
+		dts_time: Some(time),
+		duration_time: None,
+		size: 100,
+		key,
+		discard: false,
+	}
+}
+
+#[test]
+fn collects_sorted_keyframes() {
+	let packets = vec![
+		packet(0, 4.0, true),
+		packet(0, 0.0, true),
+		packet(1, 1.0, true),
+		packet(0, 2.0, true),
+		packet(0, 3.0, false),
+		packet(0, 2.0, true),
+	];
+	assert_eq!(keyframe_times(&packets, 0), vec![0.0, 2.0, 4.0]);
+	assert_eq!(keyframe_times(&packets, 1), vec![1.0]);
+}
+
+#[test]
+fn moves_keyframes_to_the_seek_timeline() {
+	// MPEG-TS usually starts at 1.4s, which -ss doesn't count
+	let keyframes = keyframes_from_start(&[1.4, 3.4064, 5.4], 1.4);
+	assert_eq!(keyframes, vec![0.0, 2.0064, 4.0]);
+	assert_eq!(keyframe_at_or_before(&keyframes, 2.5), Some(2.0064));
+}
+
+#[test]
+fn snaps_to_keyframes() {
+	let keyframes = [0.0, 2.0, 4.0];
+	assert_eq!(keyframe_at_or_before(&keyframes, 3.5), Some(2.0));
+	assert_eq!(keyframe_at_or_before(&keyframes, 2.0), Some(2.0));
+	assert_eq!(keyframe_at_or_after(&keyframes, 2.5), Some(4.0));
+	assert_eq!(keyframe_at_or_after(&keyframes, 4.5), None);
+}
+
+#[test]
+fn smart_cut_splits_partial_gops() {
+	let keyframes = [0.0, 2.0, 4.0, 6.0];
+	assert_eq!(
+		smart_cut_segments(&keyframes, 1.0, 5.0),
+		vec![
+			CutSegment::Encode { start: 1.0, end: 2.0 },
+			CutSegment::Copy { start: 2.0, end: 4.0 },
+			CutSegment::Encode { start: 4.0, end: 5.0 },
+		]
+	);
+	assert_eq!(
+		smart_cut_segments(&keyframes, 2.0, 6.0),
+		vec![CutSegment::Copy { start: 2.0, end: 6.0 }]
+	);
+	assert_eq!(
+		smart_cut_segments(&keyframes, 2.5, 3.5),
+		vec![CutSegment::Encode { start: 2.5, end: 3.5 }]
+	);
+}
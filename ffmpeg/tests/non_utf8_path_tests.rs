@@ -0,0 +1,96 @@
+#![cfg(unix)]
+
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::process::Command;
+
+use ffmpeg::ffmpeg::binaries::set_ffmpeg_binary;
+use ffmpeg::ffmpeg::command::{FfmpegCommand, Input, Output};
+use ffmpeg::ffmpeg::ffmpeg::ffmpeg_with_progress;
+use ffmpeg::ffmpeg::ffprobe::ffprobe;
+use ffmpeg::ffmpeg::script::{Shell, format_command, quote};
+
+/// "café.mkv" in Latin-1
+const LATIN1: &[u8] = b"caf\xe9.mkv";
+/// "ソフト.mkv" in Shift-JIS, where the second byte of "ソ" is a backslash
+const SHIFT_JIS: &[u8] = b"\x83\x5c\x83\x74\x83\x67.mkv";
+
+/// Stands in for ffprobe and only succeeds if the input exists under exactly the name it was given.
+const FAKE_FFPROBE: &str = r#"#!/bin/sh
+for input; do :; done
+test -f "$input" || exit 1
+printf '{ "streams": [{ "index": 0, "codec_type": "video", "tags": { "title": "%s" } }], "format": { "duration": "1.000000" } }\n' "$input"
+"#;
+
+/// Stands in for ffmpeg, which gets called as `ffmpeg -progress <file> -i <input> <output>`.
+const FAKE_FFMPEG: &str = r#"#!/bin/sh
+printf 'progress=end\n' > "$2"
+cp "$4" "$5"
+"#;
+
+fn os(bytes: &[u8]) -> &OsStr {
+	OsStr::from_bytes(bytes)
+}
+
+#[test]
+fn command_keeps_bytes() {
+	let mut command = FfmpegCommand::new();
+	command
+		.input(Input::new(os(LATIN1)))
+		.output(Output::new(os(SHIFT_JIS)));
+
+	assert_eq!(command.args(), [OsStr::new("-i"), os(LATIN1), os(SHIFT_JIS)]);
+}
+
+#[test]
+fn quoting_keeps_bytes() {
+	for name in [LATIN1, SHIFT_JIS] {
+		let output = Command::new("sh")
+			.arg("-c")
+			.arg(format!("printf '%s' {}", quote(os(name), Shell::Posix)))
+			.output()
+			.unwrap();
+		assert_eq!(output.stdout, name);
+	}
+
+	// PowerShell can't pass such bytes on, so they're only replaced for display
+	let args = [OsString::from("-i"), os(LATIN1).to_os_string()];
+	assert_eq!(
+		format_command("ffmpeg", &args, Shell::PowerShell),
+		"ffmpeg -i 'caf\u{fffd}.mkv'"
+	);
+}
+
+// the ffmpeg binary is process-wide, so everything that runs it is in one test
+#[test]
+fn runs_with_non_utf8_names() {
+	let dir = tempfile::tempdir().unwrap();
+	for (name, script) in [("ffmpeg", FAKE_FFMPEG), ("ffprobe", FAKE_FFPROBE)] {
+		let path = dir.path().join(name);
+		fs::write(&path, script).unwrap();
+		fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+	}
+	set_ffmpeg_binary(dir.path().join("ffmpeg"));
+
+	let input = dir.path().join(os(LATIN1));
+	let output = dir.path().join(os(SHIFT_JIS));
+	fs::write(&input, b"video").unwrap();
+
+	// the output repeats the name, so it only parses if stdout is read without requiring UTF-8
+	let probe = ffprobe(&input, false).unwrap();
+	let title = probe.streams[0]
+		.tags
+		.as_ref()
+		.and_then(|t| t.title.as_deref());
+	assert!(title.unwrap().ends_with("caf\u{fffd}.mkv"));
+
+	let mut command = FfmpegCommand::new();
+	command
+		.input(Input::new(&input))
+		.output(Output::new(&output));
+	ffmpeg_with_progress(&command.args(), |_| ()).unwrap();
+
+	assert_eq!(fs::read(&output).unwrap(), b"video");
+}
@@ -0,0 +1,88 @@
+use ffmpeg::ffmpeg::ffprobe_struct::{FrameInfo, PacketInfo, PictType, StreamType};
+
+/// Stands in for ffprobe with `-show_packets` on a long input, warning about every packet like a damaged file does.
+#[cfg(unix)]
+const VERBOSE_FFPROBE: &str = r#"#!/bin/sh
+i=0
+while [ $i -lt 20000 ]; do
+	echo "codec_type=video|stream_index=0|pts_time=$i|dts_time=$i|duration_time=1|size=100|flags=___"
+	echo "[h264 @ 0x55d0c0] Invalid NAL unit size in packet $i, skipping it" >&2
+	i=$((i + 1))
+done
+"#;
+
+#[test]
+fn frame_parsing() {
+	let frame: FrameInfo = "key_frame=1|pts_time=0.041708|duration_time=0.041708|pkt_size=153044|pict_type=I|interlaced_frame=0|top_field_first=0"
+		.parse()
+		.unwrap();
+	assert!(frame.key_frame);
+	assert_eq!(frame.pict_type, PictType::I);
+	assert_eq!(frame.pts_time, Some(0.041708));
+	assert_eq!(frame.pkt_size, Some(153044));
+	assert!(!frame.interlaced_frame);
+
+	let frame: FrameInfo =
+		"key_frame=0|pts_time=N/A|duration_time=N/A|pkt_size=412|pict_type=B|interlaced_frame=1|top_field_first=1"
+			.parse()
+			.unwrap();
+	assert!(!frame.key_frame);
+	assert_eq!(frame.pict_type, PictType::B);
+	assert_eq!(frame.pts_time, None);
+	assert_eq!(frame.duration_time, None);
+	assert!(frame.interlaced_frame);
+	assert!(frame.top_field_first);
+
+	// side data sections and empty lines aren't frames
+	assert!(
+		"side_data_type=Mastering display metadata"
+			.parse::<FrameInfo>()
+			.is_err()
+	);
+	assert!("".parse::<FrameInfo>().is_err());
+}
+
+#[test]
+fn packet_parsing() {
+	let packet: PacketInfo =
+		"codec_type=video|stream_index=0|pts_time=2.002000|dts_time=1.918583|duration_time=0.041708|size=86422|flags=K__"
+			.parse()
+			.unwrap();
+	assert_eq!(packet.stream_index, 0);
+	assert_eq!(packet.codec_type, Some(StreamType::Video));
+	assert_eq!(packet.time(), Some(2.002));
+	assert_eq!(packet.size, 86422);
+	assert!(packet.key);
+	assert!(!packet.discard);
+
+	let packet: PacketInfo =
+		"codec_type=audio|stream_index=1|pts_time=N/A|dts_time=0.021333|duration_time=0.021333|size=371|flags=__D"
+			.parse()
+			.unwrap();
+	assert_eq!(packet.codec_type, Some(StreamType::Audio));
+	assert_eq!(packet.time(), Some(0.021333));
+	assert!(!packet.key);
+	assert!(packet.discard);
+
+	assert!("key_frame=1|pict_type=I".parse::<PacketInfo>().is_err());
+}
+
+// the binary is process-wide, and only this test sets it
+#[cfg(unix)]
+#[test]
+fn packet_listing_survives_verbose_stderr() {
+	use std::fs;
+	use std::os::unix::fs::PermissionsExt;
+
+	use ffmpeg::ffmpeg::binaries::set_ffmpeg_binary;
+	use ffmpeg::ffmpeg::ffprobe::ffprobe_packets;
+
+	let dir = tempfile::tempdir().unwrap();
+	let ffprobe = dir.path().join("ffprobe");
+	fs::write(&ffprobe, VERBOSE_FFPROBE).unwrap();
+	fs::set_permissions(&ffprobe, fs::Permissions::from_mode(0o755)).unwrap();
+	set_ffmpeg_binary(dir.path().join("ffmpeg"));
+
+	let packets = ffprobe_packets("input.mkv", Some("V:0"), None).unwrap();
+	assert_eq!(packets.len(), 20000);
+}
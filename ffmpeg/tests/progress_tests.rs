@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use ffmpeg::ffmpeg::progress::{ProgressParser, ProgressTotal, StreamQuality, estimate_remaining, format_eta};
+
+#[test]
+fn fraction_from_duration_or_frames() {
+	let by_duration = ProgressTotal::Duration(Duration::from_secs(200));
+	let fraction = by_duration
+		.fraction(Some(Duration::from_secs(50)), Some(1200))
+		.unwrap();
+	assert!((fraction - 0.25).abs() < 1e-9);
+	assert!(by_duration.fraction(None, Some(1200)).is_none());
+
+	let by_frames = ProgressTotal::Frames(2400);
+	let fraction = by_frames
+		.fraction(Some(Duration::from_secs(50)), Some(1800))
+		.unwrap();
+	assert!((fraction - 0.75).abs() < 1e-9);
+
+	// ffmpeg's out_time can overshoot the probed duration a little
+	let overshoot = by_duration
+		.fraction(Some(Duration::from_secs(201)), None)
+		.unwrap();
+	assert!((overshoot - 1.0).abs() < 1e-9);
+
+	assert!(
+		ProgressTotal::Unknown
+			.fraction(Some(Duration::from_secs(50)), Some(1200))
+			.is_none()
+	);
+	assert!(ProgressTotal::Frames(0).fraction(None, Some(0)).is_none());
+}
+
+#[test]
+fn eta() {
+	assert_eq!(
+		estimate_remaining(0.25, Duration::from_secs(30)),
+		Some(Duration::from_secs(90))
+	);
+	assert_eq!(estimate_remaining(0.0, Duration::from_secs(30)), None);
+
+	assert_eq!(format_eta(Duration::from_secs(75)), "1:15");
+	assert_eq!(format_eta(Duration::from_secs(3 * 3600 + 5 * 60 + 9)), "3:05:09");
+}
+
+const PROGRESS: &str = "frame=120
+fps=59.94
+stream_0_0_q=28.0
+stream_0_1_q=-1.0
+bitrate=N/A
+total_size=262192
+out_time_us=2002000
+out_time_ms=2002000
+out_time=00:00:02.002000
+dup_frames=0
+drop_frames=3
+speed=N/A
+progress=continue
+frame=240
+fps=60.12
+stream_0_0_q=-1.0
+bitrate=1250.4kbits/s
+total_size=625612
+out_time_us=4004000
+out_time_ms=4004000
+out_time=00:00:04.004000
+dup_frames=1
+drop_frames=3
+speed=1.98x
+progress=end
+";
+
+#[test]
+fn parses_progress_blocks() {
+	let mut parser = ProgressParser::new();
+	let events = PROGRESS
+		.lines()
+		.filter_map(|line| parser.push_line(line))
+		.collect::<Vec<_>>();
+	assert_eq!(events.len(), 2);
+
+	let first = &events[0];
+	assert_eq!(first.frame, Some(120));
+	assert!((first.fps.unwrap() - 59.94).abs() < 1e-9);
+	assert_eq!(first.out_time, Some(Duration::from_millis(2002)));
+	assert_eq!(first.total_size, Some(262_192));
+	assert_eq!((first.bitrate, first.speed), (None, None));
+	assert_eq!((first.dup_frames, first.drop_frames), (Some(0), Some(3)));
+	assert_eq!(
+		first.stream_q,
+		vec![
+			StreamQuality { file_index: 0, stream_index: 0, q: 28.0 },
+			StreamQuality { file_index: 0, stream_index: 1, q: -1.0 },
+		]
+	);
+	assert!(!first.end);
+
+	// values from the previous block don't carry over
+	let last = &events[1];
+	assert_eq!(last.frame, Some(240));
+	assert!((last.bitrate.unwrap() - 1250.4).abs() < 1e-9);
+	assert!((last.speed.unwrap() - 1.98).abs() < 1e-9);
+	assert_eq!(last.stream_q.len(), 1);
+	assert!(last.end);
+}
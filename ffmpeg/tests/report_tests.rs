@@ -0,0 +1,42 @@
+use ffmpeg::ffmpeg::progress::StreamQuality;
+use ffmpeg::ffmpeg::report::Report;
+
+#[test]
+fn reports_are_tagged_json_lines() {
+	let progress = Report::Progress {
+		frame: Some(240),
+		fps: Some(60.0),
+		out_time: Some(4.0),
+		bitrate: None,
+		total_size: Some(625612),
+		speed: Some(2.0),
+		dup_frames: Some(0),
+		drop_frames: Some(1),
+		stream_q: &[StreamQuality { file_index: 0, stream_index: 0, q: 28.0 }],
+		percent: Some(50.0),
+		eta: None,
+	};
+	assert_eq!(
+		serde_json::to_string(&progress).unwrap(),
+		r#"{"type":"progress","frame":240,"fps":60.0,"out_time":4.0,"bitrate":null,"total_size":625612,"speed":2.0,"dup_frames":0,"drop_frames":1,"stream_q":[{"file_index":0,"stream_index":0,"q":28.0}],"percent":50.0,"eta":null}"#
+	);
+
+	let error = Report::Error {
+		message: "ffmpeg exited with status code 1",
+		exit_code: 3,
+	};
+	assert_eq!(
+		serde_json::to_string(&error).unwrap(),
+		r#"{"type":"error","message":"ffmpeg exited with status code 1","exit_code":3}"#
+	);
+
+	assert_eq!(
+		serde_json::to_string(&Report::EncodingTook { seconds: 1.5 }).unwrap(),
+		r#"{"type":"encoding_took","seconds":1.5}"#
+	);
+
+	assert_eq!(
+		serde_json::to_string(&Report::PartialOutputRemoved { path: "output.mp4" }).unwrap(),
+		r#"{"type":"partial_output_removed","path":"output.mp4"}"#
+	);
+}
@@ -0,0 +1,90 @@
+use std::ffi::OsString;
+use std::path::Path;
+use std::sync::Arc;
+
+use ffmpeg::ffmpeg::error::FfError;
+use ffmpeg::ffmpeg::ffmpeg::ffmpeg_with_progress;
+use ffmpeg::ffmpeg::ffprobe::{ffprobe, ffprobe_packets};
+use ffmpeg::ffmpeg::runner::{LocalRunner, RecordingRunner, ReplayRunner, Runner, set_runner};
+
+const PROBE: &str = r#"{
+	"streams": [
+		{ "index": 0, "codec_type": "video", "codec_name": "h264", "width": 1920, "height": 1080 },
+		{ "index": 1, "codec_type": "audio", "codec_name": "aac" }
+	],
+	"format": { "duration": "12.500000" }
+}"#;
+
+const PACKETS: &str =
+	"codec_type=video|stream_index=0|pts_time=0.000000|dts_time=0.000000|duration_time=0.041708|size=86422|flags=K__
+codec_type=video|stream_index=0|pts_time=0.041708|dts_time=0.041708|duration_time=0.041708|size=412|flags=___";
+
+fn args(args: &[&str]) -> Vec<OsString> {
+	args.iter().map(OsString::from).collect()
+}
+
+// the runner is process-wide, so everything runs in one test
+#[test]
+fn records_and_replays() {
+	let replay = ReplayRunner::new()
+		.with_output("V:0", PACKETS)
+		.with_output("input.mkv", PROBE);
+	let recorder = Arc::new(RecordingRunner::new(Arc::new(replay)));
+	set_runner(recorder.clone());
+
+	let probe = ffprobe("input.mkv", false).unwrap();
+	assert_eq!(probe.streams.len(), 2);
+	assert!((probe.duration().unwrap().as_secs_f64() - 12.5).abs() < 1e-9);
+
+	let packets = ffprobe_packets("input.mkv", Some("V:0"), None).unwrap();
+	assert_eq!(packets.len(), 2);
+	assert!(packets[0].key);
+
+	assert!(matches!(
+		ffprobe("missing.mkv", false),
+		Err(FfError::NonZeroExit { .. })
+	));
+
+	let mut ended = false;
+	let encode = args(&["-i", "input.mkv", "-c:v", "libx264", "-crf", "20", "output.mp4"]);
+	ffmpeg_with_progress(&encode, |event| ended = event.end).unwrap();
+	assert!(ended);
+
+	let calls = recorder.calls();
+	assert_eq!(calls.len(), 4);
+	assert_eq!(
+		calls[0].args,
+		args(&[
+			"-hide_banner",
+			"-loglevel",
+			"error",
+			"-print_format",
+			"json",
+			"-show_streams",
+			"-show_format",
+			"-i",
+			"input.mkv"
+		])
+	);
+	assert!(calls[3].binary.ends_with(Path::new("ffmpeg")));
+	assert_eq!(calls[3].args, encode);
+}
+
+// a child that writes more to stderr than the pipe holds used to block while its stdout was being read
+#[cfg(unix)]
+#[test]
+fn drains_stderr_while_reading_stdout() {
+	let script = "head -c 1000000 /dev/zero | tr '\\0' x >&2; echo done";
+	let output = LocalRunner
+		.output(Path::new("sh"), &args(&["-c", script]))
+		.unwrap();
+	assert_eq!(output, "done\n");
+
+	let failing = format!("{script}; exit 3");
+	let Err(FfError::NonZeroExit { code, stderr, .. }) = LocalRunner.output(Path::new("sh"), &args(&["-c", &failing]))
+	else {
+		panic!("expected the exit code to be reported");
+	};
+	assert_eq!(code, Some(3));
+	assert_eq!(stderr.len(), 1_000_000);
+}
@@ -0,0 +1,125 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use ffmpeg::ffmpeg::script::{ScriptStep, Shell, format_command, quote, render_script};
+
+const TRICKY_ARGS: [&str; 6] = [
+	"[0:v:0]scale=w=-2:h=720[v];[v][1:s:0]overlay",
+	"it's",
+	"",
+	"$HOME `date` \\n",
+	"drawtext=text='a, b'",
+	"@list.txt",
+];
+
+#[test]
+fn quotes_arguments() {
+	assert_eq!(quote("-c:v", Shell::Posix), "-c:v");
+	assert_eq!(quote("0:V:0", Shell::PowerShell), "0:V:0");
+	assert_eq!(quote("[0:v]fps=10[v]", Shell::Posix), "'[0:v]fps=10[v]'");
+	assert_eq!(quote("it's", Shell::Posix), r"'it'\''s'");
+	assert_eq!(quote("it's", Shell::PowerShell), "'it''s'");
+	assert_eq!(quote("", Shell::Posix), "''");
+	assert_eq!(quote("a,b", Shell::PowerShell), "'a,b'");
+
+	let args = vec!["-i".to_string(), "my video.mkv".to_string()];
+	assert_eq!(
+		format_command("ffmpeg", &args, Shell::Posix),
+		"ffmpeg -i 'my video.mkv'"
+	);
+	assert_eq!(
+		format_command(r"C:\ffmpeg\ffmpeg.exe", &args, Shell::PowerShell),
+		r"& 'C:\ffmpeg\ffmpeg.exe' -i 'my video.mkv'"
+	);
+}
+
+#[cfg(unix)]
+#[test]
+fn posix_quoting_round_trips() {
+	let args = TRICKY_ARGS
+		.iter()
+		.map(ToString::to_string)
+		.collect::<Vec<_>>();
+	let command = format_command("printf", &[vec!["%s\\n".to_string()], args].concat(), Shell::Posix);
+
+	let output = std::process::Command::new("sh")
+		.args(["-c", &command])
+		.output()
+		.unwrap();
+	assert_eq!(
+		String::from_utf8(output.stdout).unwrap(),
+		format!("{}\n", TRICKY_ARGS.join("\n"))
+	);
+}
+
+#[test]
+fn renders_script_with_temp_files() {
+	let steps = vec![
+		ScriptStep::WriteFile {
+			path: PathBuf::from("/tmp/ffautoAbc/segments.txt"),
+			contents: b"file '/tmp/ffautoAbc/segment0.mkv'\n".to_vec(),
+		},
+		ScriptStep::WriteFile {
+			path: PathBuf::from("/tmp/chart.ppm"),
+			contents: vec![0, 255, b'\''],
+		},
+		ScriptStep::Run {
+			binary: "ffmpeg".into(),
+			args: ["-f", "concat", "-i", "/tmp/ffautoAbc/segments.txt", "out put.mkv"]
+				.map(OsString::from)
+				.to_vec(),
+		},
+	];
+
+	let script = render_script(&steps, Path::new("/tmp/"));
+	assert_eq!(
+		script,
+		r#"#!/bin/sh
+set -e
+
+tmpdir=$(mktemp -d)
+trap 'rm -rf "$tmpdir"' EXIT
+mkdir -p "$tmpdir"/ffautoAbc
+
+cat > "$tmpdir"/ffautoAbc/segments.txt <<FFAUTO_EOF
+file '${tmpdir}/ffautoAbc/segment0.mkv'
+FFAUTO_EOF
+
+: > "$tmpdir"/chart.ppm
+printf '\000\377\047' >> "$tmpdir"/chart.ppm
+
+ffmpeg -f concat -i "$tmpdir"/ffautoAbc/segments.txt 'out put.mkv'
+"#
+	);
+}
+
+#[test]
+fn only_moves_whole_temp_paths() {
+	let steps = vec![
+		ScriptStep::WriteFile {
+			path: PathBuf::from("/tmp/list.txt"),
+			contents: b"file '/tmp/a.mkv'\nfile '/tmpfoo/b.mkv'\nfile '/data/tmp/c.mkv'\n# /tmp\n".to_vec(),
+		},
+		ScriptStep::Run {
+			binary: "ffmpeg".into(),
+			args: ["-i", "/tmpfoo/in.mkv", "/data/tmp/out.mkv", "/tmp"]
+				.map(OsString::from)
+				.to_vec(),
+		},
+	];
+
+	let script = render_script(&steps, Path::new("/tmp"));
+	let heredoc = concat!(
+		"cat > \"$tmpdir\"/list.txt <<FFAUTO_EOF\n",
+		"file '${tmpdir}/a.mkv'\n",
+		"file '/tmpfoo/b.mkv'\n",
+		"file '/data/tmp/c.mkv'\n",
+		"# /tmp\n",
+		"FFAUTO_EOF\n"
+	);
+	assert!(script.contains(heredoc), "{script}");
+	assert!(
+		script.contains("ffmpeg -i /tmpfoo/in.mkv /data/tmp/out.mkv /tmp\n"),
+		"{script}"
+	);
+}
@@ -0,0 +1,55 @@
+use ffmpeg::ffmpeg::size::{AspectRatio, Resize, Size};
+
+#[test]
+fn resize_parsing() {
+	let parse = |s: &str| s.parse::<Resize>().unwrap();
+
+	assert_eq!(parse("1280x720"), Resize::Fit(Size::new(1280, 720)));
+	assert_eq!(parse("hd720"), Resize::Fit(Size::new(1280, 720)));
+	assert_eq!(parse("1280x720!"), Resize::Exact(Size::new(1280, 720)));
+	assert_eq!(parse("1080x1080^"), Resize::Fill(Size::new(1080, 1080)));
+	assert_eq!(parse("hd1080^"), Resize::Fill(Size::new(1920, 1080)));
+	assert_eq!(parse("1280x"), Resize::Width(1280));
+	assert_eq!(parse("x720"), Resize::Height(720));
+	assert_eq!(parse("50%"), Resize::Percent(50.0));
+	assert_eq!(parse("12.5%"), Resize::Percent(12.5));
+
+	for invalid in [
+		"",
+		"x",
+		"0x",
+		"x0",
+		"0x720",
+		"0%",
+		"-50%",
+		"50%%",
+		"1280x720!^",
+		"huge!",
+		"x-720",
+	] {
+		assert!(invalid.parse::<Resize>().is_err(), "{invalid} shouldn't parse");
+	}
+}
+
+#[test]
+fn resize_even_sizes() {
+	for even in ["1280x720!", "1080x1080^", "1281x721", "1281x", "x721", "33%"] {
+		assert!(even.parse::<Resize>().unwrap().require_even().is_ok(), "{even}");
+	}
+	for odd in ["1281x720!", "1280x721!", "1081x1080^", "1080x1081^"] {
+		assert!(odd.parse::<Resize>().unwrap().require_even().is_err(), "{odd}");
+	}
+}
+
+#[test]
+fn aspect_ratio_parsing() {
+	assert_eq!(
+		"9:16".parse::<AspectRatio>().unwrap(),
+		AspectRatio { width: 9, height: 16 }
+	);
+	assert_eq!("1:1".parse::<AspectRatio>().unwrap().to_string(), "1:1");
+
+	for invalid in ["", "1", "16x9", "0:1", "1:0", "1.5:1", "-1:1"] {
+		assert!(invalid.parse::<AspectRatio>().is_err(), "{invalid} shouldn't parse");
+	}
+}
@@ -0,0 +1,126 @@
+use std::path::Path;
+use std::time::Duration;
+
+use ffmpeg::ffmpeg::error::FfError;
+use ffmpeg::ffmpeg::ffprobe_struct::FFProbeOutput;
+use ffmpeg::ffmpeg::filtergraph::{Chain, Filter};
+use ffmpeg::ffmpeg::subtitles::{
+	SubtitleKind, font_attachment_path, font_attachments, text_subtitle_filter, text_subtitle_filters,
+};
+
+fn chain(filters: Vec<Filter>) -> String {
+	filters.into_iter().collect::<Chain>().to_string()
+}
+
+#[test]
+fn detects_subtitle_kinds() {
+	for codec in ["subrip", "ass", "webvtt", "mov_text"] {
+		assert_eq!(SubtitleKind::from_codec(codec), Some(SubtitleKind::Text), "{codec}");
+	}
+	for codec in ["hdmv_pgs_subtitle", "dvd_subtitle", "dvb_subtitle"] {
+		assert_eq!(SubtitleKind::from_codec(codec), Some(SubtitleKind::Bitmap), "{codec}");
+	}
+	assert_eq!(SubtitleKind::from_codec("eia_608"), None);
+}
+
+#[test]
+fn builds_text_subtitle_filters() {
+	assert_eq!(
+		chain(text_subtitle_filters(Path::new("/media/movie.mkv"), Some(1), None, None).unwrap()),
+		"subtitles=filename=/media/movie.mkv:si=1"
+	);
+	assert_eq!(
+		chain(text_subtitle_filters(Path::new("/media/movie.en.ass"), None, None, None).unwrap()),
+		"ass=filename=/media/movie.en.ass"
+	);
+	// an ASS stream inside a media file still needs the subtitles filter to select it
+	assert_eq!(
+		chain(text_subtitle_filters(Path::new("/media/movie.ass"), Some(0), None, None).unwrap()),
+		"subtitles=filename=/media/movie.ass:si=0"
+	);
+	assert_eq!(
+		chain(
+			text_subtitle_filters(
+				Path::new(r"C:\subs\movie.srt"),
+				None,
+				Some(Path::new("/tmp/fonts")),
+				None
+			)
+			.unwrap()
+		),
+		r"subtitles=filename=C\\:\\\\subs\\\\movie.srt:fontsdir=/tmp/fonts"
+	);
+}
+
+#[test]
+fn picks_the_text_subtitle_filter() {
+	assert_eq!(text_subtitle_filter(Path::new("movie.SSA"), None), "ass");
+	assert_eq!(text_subtitle_filter(Path::new("movie.srt"), None), "subtitles");
+	assert_eq!(text_subtitle_filter(Path::new("movie.ass"), Some(0)), "subtitles");
+}
+
+#[cfg(unix)]
+#[test]
+fn rejects_non_utf8_subtitle_paths() {
+	use std::ffi::OsStr;
+	use std::os::unix::ffi::OsStrExt;
+
+	let path = Path::new(OsStr::from_bytes(b"movie\xff.srt"));
+	assert!(matches!(
+		text_subtitle_filters(path, None, None, None),
+		Err(FfError::InvalidPath(_))
+	));
+	assert!(matches!(
+		text_subtitle_filters(Path::new("movie.srt"), None, Some(path), None),
+		Err(FfError::InvalidPath(_))
+	));
+}
+
+#[test]
+fn shifts_text_subtitles_by_the_seek() {
+	assert_eq!(
+		chain(
+			text_subtitle_filters(
+				Path::new("movie.mkv"),
+				Some(0),
+				None,
+				Some(Duration::from_millis(90_500))
+			)
+			.unwrap()
+		),
+		"setpts=PTS+90.5/TB,subtitles=filename=movie.mkv:si=0,setpts=PTS-90.5/TB"
+	);
+	assert_eq!(
+		chain(text_subtitle_filters(Path::new("movie.mkv"), Some(0), None, Some(Duration::ZERO)).unwrap()),
+		"subtitles=filename=movie.mkv:si=0"
+	);
+}
+
+#[test]
+fn finds_font_attachments() {
+	let probe: FFProbeOutput = serde_json::from_str(
+		r#"{
+			"streams": [
+				{ "index": 0, "codec_type": "video" },
+				{ "index": 1, "codec_type": "subtitle", "codec_name": "ass" },
+				{ "index": 2, "codec_type": "attachment", "tags": { "filename": "Font.TTF", "mimetype": "application/x-truetype-font" } },
+				{ "index": 3, "codec_type": "attachment", "tags": { "filename": "cover.jpg", "mimetype": "image/jpeg" } },
+				{ "index": 4, "codec_type": "attachment", "tags": { "filename": "../../Bold.otf" } }
+			],
+			"format": {}
+		}"#,
+	)
+	.unwrap();
+
+	let fonts = font_attachments(&probe);
+	assert_eq!(fonts.iter().map(|s| s.index).collect::<Vec<_>>(), vec![2, 4]);
+	assert_eq!(
+		font_attachment_path(Path::new("/tmp/fonts"), fonts[0]),
+		Path::new("/tmp/fonts/2-Font.TTF")
+	);
+	// attachment names can't escape the directory
+	assert_eq!(
+		font_attachment_path(Path::new("/tmp/fonts"), fonts[1]),
+		Path::new("/tmp/fonts/4-Bold.otf")
+	);
+}
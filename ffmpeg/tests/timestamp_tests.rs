@@ -1,4 +1,7 @@
-use ffmpeg::ffmpeg::timestamps::{TimestampFormat, format_ffmpeg_timestamp, parse_ffmpeg_duration};
+use ffmpeg::ffmpeg::timestamps::{
+	Timestamp, TimestampFormat, format_ffmpeg_timestamp, parse_ffmpeg_duration, parse_relative_timestamp,
+	parse_timestamp,
+};
 use std::time::Duration;
 
 struct TimestampTest {
@@ -63,3 +66,126 @@ fn timestamp_parsing() {
 		// println!("---");
 	}
 }
+
+const NTSC: f64 = 30000.0 / 1001.0;
+
+fn assert_parses_to(timestamp: &str, frame_rate: Option<f64>, expected_secs: f64) {
+	let duration = parse_timestamp(timestamp, frame_rate);
+	assert!(duration.is_some(), "{timestamp}: parsing failed!");
+	let secs = duration.unwrap().as_secs_f64();
+	assert!(
+		(secs - expected_secs).abs() < 1e-6,
+		"{timestamp}: got {secs}, expected {expected_secs}"
+	);
+}
+
+#[test]
+fn smpte_timecode_parsing() {
+	assert_parses_to("00:01:02:12", Some(25.0), 62.48);
+	assert_parses_to("01:00:00:00", Some(24.0), 3600.0);
+	// non-drop-frame timecodes at NTSC rates count frames at the rounded rate
+	assert_parses_to("00:00:01:00", Some(24000.0 / 1001.0), 24.0 / (24000.0 / 1001.0));
+
+	// drop-frame skips frame numbers 0 and 1 at the start of every minute except each tenth
+	assert_parses_to("00:00:59;29", Some(NTSC), 1799.0 / NTSC);
+	assert_parses_to("00:01:00;02", Some(NTSC), 1800.0 / NTSC);
+	assert_parses_to("00:10:00;00", Some(NTSC), 17982.0 / NTSC);
+	assert_parses_to("01:00:00;00", Some(NTSC), 107_892.0 / NTSC);
+	assert_parses_to("00:01:00;04", Some(60000.0 / 1001.0), 3600.0 / (60000.0 / 1001.0));
+
+	assert!(parse_timestamp("00:01:00;00", Some(NTSC)).is_none());
+	assert!(parse_timestamp("00:00:00;00", Some(25.0)).is_none());
+	assert!(parse_timestamp("00:00:00:25", Some(25.0)).is_none());
+	assert!(parse_timestamp("00:00:00:00", None).is_none());
+}
+
+#[test]
+fn frame_number_parsing() {
+	assert_parses_to("f0", Some(25.0), 0.0);
+	assert_parses_to("f1234", Some(25.0), 49.36);
+	assert_parses_to("f1800", Some(NTSC), 60.06);
+	assert!(parse_timestamp("f1234", None).is_none());
+	assert!(parse_timestamp("f12.5", Some(25.0)).is_none());
+
+	// everything else is parsed like before and doesn't need a frame rate
+	assert_parses_to("01:59:24.32", None, 7164.32);
+	assert_parses_to("24", None, 24.0);
+}
+
+#[test]
+fn smpte_timecode_formatting() {
+	let smpte = |frame_rate, drop_frame| TimestampFormat::Smpte { frame_rate, drop_frame };
+
+	assert_eq!(
+		format_ffmpeg_timestamp(Duration::from_secs_f64(62.48), &smpte(25.0, false)),
+		"00:01:02:12"
+	);
+	assert_eq!(
+		format_ffmpeg_timestamp(Duration::from_secs_f64(3600.0), &smpte(24.0, true)),
+		"01:00:00:00"
+	);
+
+	for (frame, timecode) in [
+		(1799.0, "00:00:59;29"),
+		(1800.0, "00:01:00;02"),
+		(17982.0, "00:10:00;00"),
+		(107_892.0, "01:00:00;00"),
+	] {
+		let duration = Duration::from_secs_f64(frame / NTSC);
+		assert_eq!(format_ffmpeg_timestamp(duration, &smpte(NTSC, true)), timecode);
+		assert_eq!(
+			parse_timestamp(timecode, Some(NTSC)).map(|d| (d.as_secs_f64() * NTSC).round()),
+			Some(frame)
+		);
+	}
+
+	assert_eq!(
+		format_ffmpeg_timestamp(Duration::from_secs_f64(1800.0 / NTSC), &smpte(NTSC, false)),
+		"00:01:00:00"
+	);
+}
+
+#[test]
+fn unit_suffix_parsing() {
+	assert_parses_to("500ms", None, 0.5);
+	assert_parses_to("250000us", None, 0.25);
+	assert_parses_to("1.5s", None, 1.5);
+	assert_parses_to("2m", None, 120.0);
+	assert_parses_to("1h30m", None, 5400.0);
+	assert_parses_to("1m30s500ms", None, 90.5);
+
+	assert!(parse_timestamp("1s1h", None).is_none());
+	assert!(parse_timestamp("5x", None).is_none());
+	assert!(parse_timestamp("", None).is_none());
+	assert!(parse_ffmpeg_duration("-10").is_none());
+	assert!(parse_ffmpeg_duration("NaN").is_none());
+}
+
+#[test]
+fn relative_timestamps() {
+	let total = Duration::from_secs(100);
+	let resolve = |timestamp: &str| parse_relative_timestamp(timestamp, Some(25.0)).and_then(|t| t.resolve(total));
+
+	assert_eq!(
+		parse_relative_timestamp("-10", None),
+		Some(Timestamp::End(Duration::from_secs(10)))
+	);
+	assert_eq!(parse_relative_timestamp("50%", None), Some(Timestamp::Percent(50.0)));
+	assert_eq!(
+		parse_relative_timestamp("1.5s", None),
+		Some(Timestamp::Start(Duration::from_millis(1500)))
+	);
+
+	assert_eq!(resolve("-10"), Some(Duration::from_secs(90)));
+	assert_eq!(resolve("-5s"), Some(Duration::from_secs(95)));
+	assert_eq!(resolve("-f250"), Some(Duration::from_secs(90)));
+	assert_eq!(resolve("50%"), Some(Duration::from_secs(50)));
+	assert_eq!(resolve("12.5%"), Some(Duration::from_millis(12500)));
+	assert_eq!(resolve("150"), Some(Duration::from_secs(150)));
+
+	assert_eq!(resolve("-150"), None);
+	assert_eq!(resolve("150%"), None);
+	assert_eq!(resolve("-10%"), None);
+	assert_eq!(resolve("--10"), None);
+	assert_eq!(resolve("ten"), None);
+}
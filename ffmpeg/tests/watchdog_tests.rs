@@ -0,0 +1,69 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::time::{Duration, Instant};
+
+use ffmpeg::ffmpeg::binaries::set_ffmpeg_binary;
+use ffmpeg::ffmpeg::error::FfError;
+use ffmpeg::ffmpeg::failure::FailureKind;
+use ffmpeg::ffmpeg::ffmpeg::ffmpeg_with_progress;
+use ffmpeg::ffmpeg::watchdog::{Watchdog, set_watchdog};
+
+/// Stands in for ffmpeg, which gets called as `ffmpeg -progress <file> <mode>`.
+const FAKE_FFMPEG: &str = r#"#!/bin/sh
+printf 'frame=36\nfps=24.0\nout_time=00:00:01.500000\nprogress=continue\n' > "$2"
+case "$3" in
+	done) printf 'frame=48\nfps=24.0\nout_time=00:00:02.000000\nprogress=end\n' >> "$2" ;;
+	fail) echo "[vost#0:0 @ 0x1] Unknown encoder 'libfoo'" >&2; exit 1 ;;
+	stall) exec sleep 30 ;;
+	busy) while true; do printf 'frame=36\nprogress=continue\n' >> "$2"; sleep 0.2; done ;;
+esac
+"#;
+
+fn run(mode: &str) -> (Result<(), FfError>, Vec<Option<u64>>) {
+	let mut frames = Vec::new();
+	let result = ffmpeg_with_progress(&[mode.into()], |event| frames.push(event.frame));
+	(result, frames)
+}
+
+// the watchdog and the ffmpeg binary are process-wide, so everything runs in one test
+#[test]
+fn fake_ffmpeg() {
+	let dir = tempfile::tempdir().unwrap();
+	let script = dir.path().join("ffmpeg");
+	fs::write(&script, FAKE_FFMPEG).unwrap();
+	fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+	set_ffmpeg_binary(script);
+
+	let (result, frames) = run("done");
+	assert!(result.is_ok());
+	assert_eq!(frames, vec![Some(36), Some(48)]);
+
+	let (result, _) = run("fail");
+	let error = result.unwrap_err();
+	assert!(matches!(&error, FfError::NonZeroExit { code: Some(1), .. }));
+	assert_eq!(error.failure_kind(), Some(FailureKind::UnknownEncoder));
+
+	set_watchdog(Watchdog {
+		stall_timeout: Some(Duration::from_secs(1)),
+		max_runtime: None,
+	});
+	let started = Instant::now();
+	let (result, _) = run("stall");
+	assert!(matches!(
+		result,
+		Err(FfError::Stalled { last_position: Some(position), .. }) if position == Duration::from_millis(1500)
+	));
+	assert!(started.elapsed() < Duration::from_secs(10));
+
+	set_watchdog(Watchdog {
+		stall_timeout: Some(Duration::from_secs(1)),
+		max_runtime: Some(Duration::from_secs(2)),
+	});
+	let started = Instant::now();
+	let (result, frames) = run("busy");
+	assert!(matches!(result, Err(FfError::TimedOut { .. })));
+	assert!(frames.len() > 2);
+	assert!(started.elapsed() < Duration::from_secs(10));
+}